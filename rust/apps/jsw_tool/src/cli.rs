@@ -1,10 +1,14 @@
 // use clap::value_parser;
 use std::path::PathBuf;
 
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use open_jsw_core::converter::raw_to_tiled_converter::RoomLayout;
+use open_jsw_tiled::tiled::layer::Compression;
 
 mod validation;
-use validation::{file_exists, is_not_file_and_parent_dir_exists};
+use validation::{
+    file_exists, is_not_dir_and_parent_dir_exists, is_not_file_and_parent_dir_exists,
+};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -29,7 +33,13 @@ pub enum Commands {
     Convert(ConvertArgs),
 
     ///  Read a Tiled map
-    ReadMap(ConvertArgs),
+    ReadMap(ReadMapArgs),
+
+    /// Render a Tiled map to a PNG image
+    Render(RenderArgs),
+
+    /// Check a Tiled map for problems, such as duplicate layers or tiles outside the tilesets
+    Validate(ValidateArgs),
 }
 
 #[derive(Args)]
@@ -42,11 +52,91 @@ pub struct ConvertArgs {
     #[arg(value_parser = is_not_file_and_parent_dir_exists)]
     pub output: PathBuf,
     // output: Option<String>,
+    /// How to arrange the rooms in the map
+    #[arg(long, value_enum, default_value_t = LayoutArg::Stacked)]
+    pub layout: LayoutArg,
+
+    /// Format of the map file
+    #[arg(long, value_enum, default_value_t = FormatArg::Json)]
+    pub format: FormatArg,
+
+    /// Compression of the tile layer data, which is base64 encoded when compressed
+    #[arg(long, value_enum, default_value_t = CompressionArg::None)]
+    pub compression: CompressionArg,
+
+    /// Store the map as an infinite map, with the tile layers split into chunks
+    #[arg(long)]
+    pub infinite: bool,
+
+    /// Add a hidden `collision` layer to each room, with its solid and platform cells merged into
+    /// rectangles
+    #[arg(long)]
+    pub collision: bool,
+
+    /// Write each room to its own map in a `rooms` directory, listed by `rooms.json`, instead of
+    /// all the rooms to a single map
+    #[arg(long)]
+    pub per_room: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum FormatArg {
+    /// Tiled JSON map (map.json)
+    Json,
+    /// Tiled XML map (map.tmx)
+    Tmx,
+}
+
+impl FormatArg {
+    /// Returns the file extension of the format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            FormatArg::Json => "json",
+            FormatArg::Tmx => "tmx",
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum LayoutArg {
+    /// All rooms at the same position, one group layer per room
+    Stacked,
+    /// Rooms placed next to each other using the exits between them
+    Grid,
+}
+
+impl From<LayoutArg> for RoomLayout {
+    fn from(layout: LayoutArg) -> Self {
+        match layout {
+            LayoutArg::Stacked => RoomLayout::Stacked,
+            LayoutArg::Grid => RoomLayout::Grid,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum CompressionArg {
+    /// Uncompressed CSV data
+    None,
+    Zlib,
+    Gzip,
+    Zstd,
+}
+
+impl From<CompressionArg> for Option<Compression> {
+    fn from(compression: CompressionArg) -> Self {
+        match compression {
+            CompressionArg::None => None,
+            CompressionArg::Zlib => Some(Compression::Zlib),
+            CompressionArg::Gzip => Some(Compression::Gzip),
+            CompressionArg::Zstd => Some(Compression::Zstd),
+        }
+    }
 }
 
 #[derive(Args)]
 pub struct ReadMapArgs {
-    /// Path to original binary game
+    /// Path to the Tiled map (.json or .tmx)
     #[arg(value_parser = file_exists)]
     pub input: PathBuf,
 
@@ -55,3 +145,25 @@ pub struct ReadMapArgs {
     pub output: Option<PathBuf>,
     // output: Option<String>,
 }
+
+#[derive(Args)]
+pub struct RenderArgs {
+    /// Path to the Tiled map (.json or .tmx)
+    #[arg(value_parser = file_exists)]
+    pub input: PathBuf,
+
+    /// Path to the PNG image
+    #[arg(value_parser = is_not_dir_and_parent_dir_exists)]
+    pub output: PathBuf,
+
+    /// Also draw the hidden layers and objects
+    #[arg(long)]
+    pub hidden: bool,
+}
+
+#[derive(Args)]
+pub struct ValidateArgs {
+    /// Path to the Tiled map (.json or .tmx)
+    #[arg(value_parser = file_exists)]
+    pub input: PathBuf,
+}
@@ -1,18 +1,36 @@
-use std::fs::{self};
+use std::{
+    fs::{self},
+    path::Path,
+};
 
 // Import and re-export the `error` module
 pub use self::error::{Error, Result};
 mod error;
 
 use clap::Parser;
-use cli::{Cli, Commands};
+use cli::{Cli, Commands, ConvertArgs, FormatArg};
 use open_jsw_core::{
-    converter::{Converter, raw_to_tiled_converter::RawToTiledConverter},
+    converter::{
+        Converter,
+        raw_to_tiled_converter::{
+            CELL_TILESET_SOURCE, MapWithSpritesheet, RawToTiledConverter, RoomMapsWithSpritesheet,
+        },
+        room_manifest::{ROOM_MANIFEST_FILE_NAME, ROOM_MAPS_DIR, RoomManifest},
+        tiled_project::{PROJECT_FILE_NAME, create_project},
+    },
     raw_game::JswRawGame,
 };
 
+use open_jsw_tiled::tiled::{
+    layer::{Compression, LayerEncoding},
+    map::Map,
+    project::Project,
+    validation,
+};
+
 mod cli;
 mod logging;
+mod render;
 
 fn main() -> Result<()> {
     if let Err(e) = run() {
@@ -59,7 +77,10 @@ fn run() -> Result<()> {
             let output_dir_path = &args.output;
 
             // Get the base folder of the output path, and create a gfx folder
-            let output_map_file_path = output_dir_path.join("map.json");
+            let output_map_file_path =
+                output_dir_path.join(format!("map.{}", args.format.extension()));
+            let output_tileset_file_path = output_dir_path.join(CELL_TILESET_SOURCE);
+            let output_project_file_path = output_dir_path.join(PROJECT_FILE_NAME);
             let gfx_dir_path = output_dir_path.join("gfx");
             let gfx_cells_dir_path = gfx_dir_path.join("cells");
             let cell_spritesheet_path = gfx_dir_path.join("cells.png");
@@ -70,18 +91,67 @@ fn run() -> Result<()> {
             }
             // println!("{:?}", raw_game.rooms);
 
-            let converter = RawToTiledConverter;
+            let converter = RawToTiledConverter::new(args.layout.into(), args.collision);
+            let project = create_project();
+            let extension = args.format.extension();
+
+            let (maps, cell_tileset, cell_spritesheet, cell_sprites) = if args.per_room {
+                let game: RoomMapsWithSpritesheet = converter.convert(&raw_game)?;
 
-            let game = converter.convert(&raw_game)?;
-            // println!("{:?}", game);
+                // List the rooms, whose maps are written to their own directory
+                let manifest = RoomManifest::new(&game.rooms, extension);
+                let manifest_path = output_dir_path.join(ROOM_MANIFEST_FILE_NAME);
+                fs::write(&manifest_path, manifest.to_json()).map_err(|source| {
+                    Error::Custom(format!(
+                        "Cannot write room manifest file: {} {:?}",
+                        manifest_path.to_string_lossy(),
+                        source
+                    ))
+                })?;
+                let room_maps_dir_path = output_dir_path.join(ROOM_MAPS_DIR);
+                fs::create_dir_all(&room_maps_dir_path)?;
 
-            let json = open_jsw_tiled::serialize_map(&game.map)?;
+                let maps = game
+                    .rooms
+                    .into_iter()
+                    .map(|room| (room_maps_dir_path.join(room.file_name(extension)), room.map))
+                    .collect();
+                (
+                    maps,
+                    game.cell_tileset,
+                    game.cell_spritesheet,
+                    game.cell_sprites,
+                )
+            } else {
+                let game: MapWithSpritesheet = converter.convert(&raw_game)?;
+                (
+                    vec![(output_map_file_path, game.map)],
+                    game.cell_tileset,
+                    game.cell_spritesheet,
+                    game.cell_sprites,
+                )
+            };
+
+            for (path, mut map) in maps {
+                write_map(&mut map, &path, args, &project)?;
+            }
+
+            // Write the cell tileset, which the map refers to
+            let tileset_json = open_jsw_tiled::serialize_tileset(&cell_tileset)?;
+            fs::write(output_tileset_file_path.as_path(), &tileset_json).map_err(|source| {
+                Error::Custom(format!(
+                    "Cannot write tileset file: {} {:?}",
+                    output_tileset_file_path.as_path().to_string_lossy(),
+                    source
+                ))
+            })?;
 
-            // Write the converted game to a file
-            fs::write(output_map_file_path.as_path(), &json).map_err(|source| {
+            // Write the project, which declares the classes used by the map
+            let project_json = open_jsw_tiled::serialize_project(&project)?;
+            fs::write(output_project_file_path.as_path(), &project_json).map_err(|source| {
                 Error::Custom(format!(
-                    "Cannot write map file: {} {:?}",
-                    output_map_file_path.as_path().to_string_lossy(),
+                    "Cannot write project file: {} {:?}",
+                    output_project_file_path.as_path().to_string_lossy(),
                     source
                 ))
             })?;
@@ -91,7 +161,6 @@ fn run() -> Result<()> {
             fs::create_dir_all(&gfx_cells_dir_path)?;
 
             // Write the cell spritesheet to the gfx folder
-            let cell_spritesheet = game.cell_spritesheet;
             image::save_buffer(
                 &cell_spritesheet_path,
                 &cell_spritesheet.bytes,
@@ -101,7 +170,7 @@ fn run() -> Result<()> {
             )?;
 
             // Write the cell images to the cells folder
-            for (id, sprite) in game.cell_sprites.iter() {
+            for (id, sprite) in cell_sprites.iter() {
                 let cell_path = gfx_cells_dir_path.join(format!("cell_{:05}.png", id));
                 image::save_buffer(
                     &cell_path,
@@ -123,15 +192,79 @@ fn run() -> Result<()> {
             // let res = convert(file).with_context(|| format!("Failed to convert '{:?}'", path))?;
 
             // let file = File::open(path)?;
-            let data = fs::read_to_string(path)?;
-
-            let res = open_jsw_tiled::deserialize_map(&data)?;
+            let res = open_jsw_tiled::load_map(path)?;
             // for room in res.rooms {
             //     println!("{} - {:?}", room.room_no, room.name);
             // }
             println!("{:?}", res);
         }
+        Commands::Render(args) => {
+            println!("Rendering Tiled map: {:?}", args.input);
+
+            let map = open_jsw_tiled::load_map(&args.input)?;
+            let dir = args.input.parent().unwrap_or(std::path::Path::new(""));
+
+            let image = render::render_map(&map, dir, args.hidden)?;
+            image.save(&args.output)?;
+        }
+        Commands::Validate(args) => {
+            println!("Validating Tiled map: {:?}", args.input);
+
+            // Read the map leniently, to report all the values which can't be read
+            let (map, errors) = open_jsw_tiled::load_map_lenient(&args.input)?;
+            let dir = args.input.parent().unwrap_or(std::path::Path::new(""));
+            for error in &errors {
+                println!("{}", error);
+            }
+
+            let mut diagnostics = validation::validate(&map);
+            diagnostics.extend(validation::validate_images(&map, dir));
+            for diagnostic in &diagnostics {
+                println!("{}", diagnostic);
+            }
+            let problems = errors.len() + diagnostics.len();
+            if problems > 0 {
+                return Err(Error::Custom(format!(
+                    "{} problem(s) found in {:?}",
+                    problems, args.input
+                )));
+            }
+            println!("No problems found");
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes a converted map in the format and with the storage options of the arguments.
+fn write_map(map: &mut Map, path: &Path, args: &ConvertArgs, project: &Project) -> Result<()> {
+    if args.infinite {
+        map.set_infinite(true);
+    }
+
+    // Check the custom properties against the types declared by the project
+    project.apply_property_types(map)?;
+
+    let compression: Option<Compression> = args.compression.into();
+    if compression.is_some() {
+        for layer in &mut map.layers {
+            layer.set_data_encoding(Some(LayerEncoding::Base64), compression.clone());
+        }
     }
 
+    let data = match args.format {
+        FormatArg::Json => open_jsw_tiled::serialize_map(map)?,
+        FormatArg::Tmx => open_jsw_tiled::serialize_map_tmx(map)?,
+    };
+
+    // Write the converted game to a file
+    fs::write(path, &data).map_err(|source| {
+        Error::Custom(format!(
+            "Cannot write map file: {} {:?}",
+            path.to_string_lossy(),
+            source
+        ))
+    })?;
+
     Ok(())
 }
@@ -0,0 +1,188 @@
+//! Renders Tiled maps to images, to preview converted games.
+
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    path::Path,
+};
+
+use image::{Pixel, RgbaImage};
+use open_jsw_tiled::tiled::{
+    gid::Gid,
+    layer::{Layer, LayerType},
+    map::{Map, MapOrientation},
+};
+
+use crate::{Error, Result};
+
+/// A tile to draw, aligned to the bottom-left corner `(x, y)` in pixels, as Tiled does.
+struct DrawnTile {
+    gid: Gid,
+    x: f64,
+    y: f64,
+    /// The size of a tile object, or `None` to draw the tile at the size of its tileset.
+    size: Option<(f64, f64)>,
+    opacity: f64,
+}
+
+/// Renders the tile layers and tile objects of an orthogonal map, with the tileset images loaded
+/// relative to `dir` (the directory of the map file). Hidden layers and objects are only drawn if
+/// `hidden` is set.
+///
+/// Flipped tiles are drawn flipped. Object rotation is not drawn, nor are tiles from collections
+/// of images.
+pub fn render_map(map: &Map, dir: &Path, hidden: bool) -> Result<RgbaImage> {
+    if !matches!(map.orientation, MapOrientation::Orthogonal) {
+        return Err(Error::Custom(
+            "Only orthogonal maps can be rendered".to_string(),
+        ));
+    }
+
+    let mut tiles = Vec::new();
+    collect_tiles(map, &map.layers, (0.0, 0.0), 1.0, hidden, &mut tiles);
+
+    // The image covers the map, and any tiles outside it (on infinite maps or offset layers)
+    let mut left = 0.0_f64;
+    let mut top = 0.0_f64;
+    let mut right = (map.width * map.tilewidth) as f64;
+    let mut bottom = (map.height * map.tileheight) as f64;
+    for tile in &tiles {
+        let (width, height) = drawn_size(map, tile);
+        left = left.min(tile.x);
+        top = top.min(tile.y - height as f64);
+        right = right.max(tile.x + width as f64);
+        bottom = bottom.max(tile.y);
+    }
+
+    let mut image = RgbaImage::new((right - left) as u32, (bottom - top) as u32);
+    let mut tileset_images: HashMap<&str, RgbaImage> = HashMap::new();
+
+    for tile in &tiles {
+        let Some((tileset, id)) = map.tileset_for_gid(tile.gid) else {
+            continue;
+        };
+        let Some((tile_left, tile_top)) = tileset.tile_position(id) else {
+            continue;
+        };
+        let source = match tileset_images.entry(&tileset.image) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(image::open(dir.join(&tileset.image))?.to_rgba8()),
+        };
+
+        let (tile_width, tile_height) = (tileset.tilewidth, tileset.tileheight);
+        let (flipped_width, flipped_height) = if tile.gid.flipped_diagonally() {
+            (tile_height, tile_width)
+        } else {
+            (tile_width, tile_height)
+        };
+        let (width, height) = drawn_size(map, tile);
+        let x = (tile.x - left) as i64;
+        let y = (tile.y - top) as i64 - height as i64;
+
+        for row in 0..height {
+            for column in 0..width {
+                // Tile objects are scaled to their size
+                let (source_x, source_y) = tile.gid.source_pixel(
+                    column * flipped_width / width,
+                    row * flipped_height / height,
+                    tile_width,
+                    tile_height,
+                );
+                let (source_x, source_y) = (tile_left + source_x, tile_top + source_y);
+                if source_x >= source.width() || source_y >= source.height() {
+                    continue;
+                }
+
+                let mut pixel = *source.get_pixel(source_x, source_y);
+                pixel[3] = (pixel[3] as f64 * tile.opacity) as u8;
+                image
+                    .get_pixel_mut((x + column as i64) as u32, (y + row as i64) as u32)
+                    .blend(&pixel);
+            }
+        }
+    }
+
+    Ok(image)
+}
+
+/// Collects the tiles of the tile layers and tile objects, in drawing order.
+fn collect_tiles(
+    map: &Map,
+    layers: &[Layer],
+    offset: (f64, f64),
+    opacity: f64,
+    hidden: bool,
+    tiles: &mut Vec<DrawnTile>,
+) {
+    for layer in layers.iter().filter(|layer| hidden || layer.visible) {
+        let offset = (offset.0 + layer.offsetx, offset.1 + layer.offsety);
+        let opacity = opacity * layer.opacity;
+
+        match layer.typ {
+            LayerType::TileLayer => {
+                let Some((left, top, width, height)) = layer.tile_bounds() else {
+                    continue;
+                };
+                for y in top..top + height as i32 {
+                    for x in left..left + width as i32 {
+                        let gid = Gid::from(layer.tile(x, y).unwrap_or(0));
+                        if gid.is_empty() {
+                            continue;
+                        }
+                        tiles.push(DrawnTile {
+                            gid,
+                            x: offset.0 + (x as f64) * map.tilewidth as f64,
+                            y: offset.1 + (y + 1) as f64 * map.tileheight as f64,
+                            size: None,
+                            opacity,
+                        });
+                    }
+                }
+            }
+            LayerType::ObjectGroup => {
+                for object in layer.objects.iter().flatten() {
+                    let Some(gid) = object
+                        .gid
+                        .filter(|gid| (hidden || object.visible) && !gid.is_empty())
+                    else {
+                        continue;
+                    };
+                    tiles.push(DrawnTile {
+                        gid,
+                        x: offset.0 + object.x,
+                        y: offset.1 + object.y,
+                        size: (object.width > 0.0 && object.height > 0.0)
+                            .then_some((object.width, object.height)),
+                        opacity,
+                    });
+                }
+            }
+            LayerType::Group => collect_tiles(
+                map,
+                layer.layers.as_deref().unwrap_or_default(),
+                offset,
+                opacity,
+                hidden,
+                tiles,
+            ),
+            LayerType::ImageLayer => (),
+        }
+    }
+}
+
+/// Returns the size in pixels a tile is drawn at.
+fn drawn_size(map: &Map, tile: &DrawnTile) -> (u32, u32) {
+    if let Some((width, height)) = tile.size {
+        return (width.round() as u32, height.round() as u32);
+    }
+
+    let (width, height) = map
+        .tileset_for_gid(tile.gid)
+        .map(|(tileset, _)| (tileset.tilewidth, tileset.tileheight))
+        .unwrap_or((map.tilewidth, map.tileheight));
+
+    if tile.gid.flipped_diagonally() {
+        (height, width)
+    } else {
+        (width, height)
+    }
+}
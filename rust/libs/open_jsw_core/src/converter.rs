@@ -1,6 +1,11 @@
 use crate::Result;
 
+mod collision;
 pub mod raw_to_tiled_converter;
+mod room_grid;
+pub mod room_manifest;
+pub mod tiled_project;
+mod wang_sets;
 
 pub trait Converter<I, O> {
     fn convert(&self, input: &I) -> Result<O>;
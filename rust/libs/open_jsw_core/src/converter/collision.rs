@@ -0,0 +1,235 @@
+use macroquad::texture::Image;
+use open_jsw_tiled::tiled::{
+    builder::MapBuilder,
+    layer::{DrawOrder, Layer, LayerType},
+    object::Object,
+    point::Point,
+};
+use rust_utils::matrix::Matrix;
+
+use crate::{
+    Result,
+    raw_game::{CellBehaviour, JswRawRoom, ROOM_LAYOUT_HEIGHT, ROOM_LAYOUT_WIDTH},
+};
+
+/// Creates the collision shapes of the tile of a cell, as read by engines which support Tiled's
+/// collision editor, or `None` if the cell doesn't collide.
+///
+/// - Earth and doors: a `solid` rectangle covering the tile
+/// - Ramps: a `ramp` triangle, rising to the left (`LRamp`) or right (`RRamp`)
+/// - Water, conveyors, crumbly floors and trampolines: a `platform` line along the top edge
+/// - Fire and traps: a `hazard` rectangle around the pixels drawn by the tile
+///
+/// See <https://doc.mapeditor.org/en/stable/manual/editing-tilesets/#tile-collision-editor>.
+pub(super) fn cell_collision(behaviour: CellBehaviour, image: &Image) -> Option<Layer> {
+    use CellBehaviour::*;
+
+    let (width, height) = (image.width as f64, image.height as f64);
+    let object = match behaviour {
+        Earth | Door => rectangle("solid", (0.0, 0.0), (width, height)),
+        LRamp => polygon("ramp", vec![(0.0, 0.0), (width, height), (0.0, height)]),
+        RRamp => polygon("ramp", vec![(width, 0.0), (width, height), (0.0, height)]),
+        Water | LConveyor | RConveyor | Crumbly | Trampoline => Object {
+            class: Some("platform".to_string()),
+            polyline: Some(vec![Point { x: 0.0, y: 0.0 }, Point { x: width, y: 0.0 }]),
+            ..shape()
+        },
+        Fire | Trap => {
+            let (x, y, w, h) = drawn_bounds(image).unwrap_or((0, 0, image.width, image.height));
+            rectangle("hazard", (x as f64, y as f64), (w as f64, h as f64))
+        }
+        Air | Item | Trigger => return None,
+    };
+
+    Some(Layer {
+        typ: LayerType::ObjectGroup,
+        draworder: Some(DrawOrder::Index),
+        objects: Some(vec![object]),
+        opacity: 1.0,
+        parallaxx: 1.0,
+        parallaxy: 1.0,
+        visible: true,
+        ..Default::default()
+    })
+}
+
+/// Creates the collision areas of a room, as few rectangles as the cells allow, for engines which
+/// don't collide with each tile.
+///
+/// Earth and doors are merged into `solid` rectangles, and water, conveyors, crumbly floors and
+/// trampolines into `platform` rectangles, which can be stood on from their top edge. Ramps are
+/// left to the shapes of their tiles (see [`cell_collision`]).
+pub(super) fn room_collision_objects(
+    map: &mut MapBuilder,
+    room: &JswRawRoom,
+    (cell_width, cell_height): (usize, usize),
+) -> Result<Vec<Object>> {
+    use CellBehaviour::*;
+
+    let behaviours = Matrix::from_fn(ROOM_LAYOUT_WIDTH, ROOM_LAYOUT_HEIGHT, |col, row| {
+        room.cell_at(col, row).map(|cell| cell.behaviour)
+    });
+    let is_solid = |b: CellBehaviour| matches!(b, Earth | Door);
+    let is_platform =
+        |b: CellBehaviour| matches!(b, Water | LConveyor | RConveyor | Crumbly | Trampoline);
+
+    let mut objects = Vec::new();
+    for (name, class, is_in) in [
+        ("Solid", "solid", is_solid as fn(CellBehaviour) -> bool),
+        ("Platform", "platform", is_platform),
+    ] {
+        for rect in behaviours.merge_rectangles(|behaviour| behaviour.is_some_and(is_in)) {
+            let object = map
+                .object(name)
+                .class(class)
+                .position(
+                    (rect.col * cell_width) as f64,
+                    (rect.row * cell_height) as f64,
+                )
+                .size(
+                    (rect.cols * cell_width) as f64,
+                    (rect.rows * cell_height) as f64,
+                );
+            objects.push(object.build()?);
+        }
+    }
+
+    Ok(objects)
+}
+
+/// Returns an object with the ID of the first shape of a tile.
+fn shape() -> Object {
+    Object {
+        id: 1,
+        visible: true,
+        ..Default::default()
+    }
+}
+
+fn rectangle(class: &str, (x, y): (f64, f64), (width, height): (f64, f64)) -> Object {
+    Object {
+        class: Some(class.to_string()),
+        x,
+        y,
+        width,
+        height,
+        ..shape()
+    }
+}
+
+fn polygon(class: &str, points: Vec<(f64, f64)>) -> Object {
+    Object {
+        class: Some(class.to_string()),
+        polygon: Some(points.into_iter().map(|(x, y)| Point { x, y }).collect()),
+        ..shape()
+    }
+}
+
+/// Returns the bounds (x, y, width, height) of the pixels of an image which aren't transparent,
+/// or `None` if it is empty.
+fn drawn_bounds(image: &Image) -> Option<(u16, u16, u16, u16)> {
+    let mut bounds: Option<(u16, u16, u16, u16)> = None;
+
+    for y in 0..image.height {
+        for x in 0..image.width {
+            if image.get_pixel(x as u32, y as u32).a == 0.0 {
+                continue;
+            }
+            let (min_x, min_y, max_x, max_y) = bounds.unwrap_or((x, y, x, y));
+            bounds = Some((min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)));
+        }
+    }
+
+    bounds.map(|(min_x, min_y, max_x, max_y)| (min_x, min_y, max_x - min_x + 1, max_y - min_y + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        image::{TRANSPARENT, create_image_from_sprite_data},
+        raw_game::{JswRawCell, JswRawExits},
+    };
+    use macroquad::color::WHITE;
+
+    fn image(sprite: [u8; 8]) -> Image {
+        create_image_from_sprite_data(&sprite, 8, 8, WHITE, TRANSPARENT).unwrap()
+    }
+
+    fn object(behaviour: CellBehaviour, sprite: [u8; 8]) -> Option<Object> {
+        let layer = cell_collision(behaviour, &image(sprite))?;
+        layer.objects?.into_iter().next()
+    }
+
+    #[test]
+    fn shapes_follow_the_cell_behaviour() {
+        let earth = object(CellBehaviour::Earth, [0xff; 8]).unwrap();
+        assert_eq!(earth.class.as_deref(), Some("solid"));
+        assert_eq!(
+            (earth.x, earth.y, earth.width, earth.height),
+            (0.0, 0.0, 8.0, 8.0)
+        );
+
+        let ramp = object(CellBehaviour::RRamp, [0x01; 8]).unwrap();
+        let points = ramp.polygon.unwrap();
+        assert_eq!((points[0].x, points[0].y), (8.0, 0.0));
+
+        let water = object(CellBehaviour::Water, [0xff, 0, 0, 0, 0, 0, 0, 0]).unwrap();
+        let line = water.polyline.unwrap();
+        assert_eq!((line[1].x, line[1].y), (8.0, 0.0));
+
+        assert!(object(CellBehaviour::Air, [0; 8]).is_none());
+    }
+
+    #[test]
+    fn solid_cells_are_merged() {
+        let mut layout = [0; ROOM_LAYOUT_WIDTH * ROOM_LAYOUT_HEIGHT];
+        // A floor along the bottom, under a wall 2 cells wide
+        layout[ROOM_LAYOUT_WIDTH * (ROOM_LAYOUT_HEIGHT - 1)..].fill(2);
+        for row in 10..ROOM_LAYOUT_HEIGHT - 1 {
+            layout[row * ROOM_LAYOUT_WIDTH + 4] = 1;
+            layout[row * ROOM_LAYOUT_WIDTH + 5] = 1;
+        }
+        let room = JswRawRoom {
+            room_no: 0,
+            name: "Room".to_string(),
+            layout,
+            cells: vec![
+                JswRawCell::new(0, 0, CellBehaviour::Air, [0; 8]),
+                JswRawCell::new(1, 0x02, CellBehaviour::Earth, [0xff; 8]),
+                JswRawCell::new(2, 0x04, CellBehaviour::Water, [0xff; 8]),
+            ],
+            border: None,
+            items: vec![],
+            entities: vec![],
+            player_start: None,
+            exits: JswRawExits::default(),
+        };
+        let mut map = MapBuilder::new(32, 16, 8, 8);
+
+        let objects = room_collision_objects(&mut map, &room, (8, 8)).unwrap();
+
+        let rects: Vec<_> = objects
+            .iter()
+            .map(|o| (o.class.as_deref().unwrap(), o.x, o.y, o.width, o.height))
+            .collect();
+        assert_eq!(
+            rects,
+            [
+                ("solid", 32.0, 80.0, 16.0, 40.0),
+                ("platform", 0.0, 120.0, 256.0, 8.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn hazards_cover_the_drawn_pixels() {
+        let fire = object(CellBehaviour::Fire, [0, 0, 0, 0x18, 0x3c, 0x7e, 0, 0]).unwrap();
+
+        assert_eq!(fire.class.as_deref(), Some("hazard"));
+        assert_eq!(
+            (fire.x, fire.y, fire.width, fire.height),
+            (1.0, 3.0, 6.0, 3.0)
+        );
+    }
+}
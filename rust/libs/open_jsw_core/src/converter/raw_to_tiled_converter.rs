@@ -1,35 +1,102 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use macroquad::{color::Color, texture::Image};
+use macroquad::{
+    color::{BLACK, Color},
+    texture::Image,
+};
 // use macroquad::texture::Image;
 use open_jsw_tiled::tiled::{
-    layer::{Layer, LayerType},
-    map::{Map, MapOrientation},
-    tileset::Tileset,
+    builder::{LayerBuilder, MapBuilder, ObjectBuilder, TilesetBuilder},
+    gid::Gid,
+    layer::Layer,
+    map::Map,
+    object::Object,
+    point::Point,
+    property::{Property, PropertyVal},
+    tileset::{Frame, Tile, Tileset, WangSet},
 };
 
 use crate::{
     Error, Result,
     error::GameConversionError,
     image::{TRANSPARENT, create_image_from_sprite_data, create_spritesheet},
-    raw_game::{JswRawGame, JswRawRoom, ROOM_LAYOUT_SIZE},
+    raw_game::{
+        CONVEYOR_FRAME_COUNT, CellBehaviour, Direction, EntityKind, JswRawCell, JswRawEntity,
+        JswRawGame, JswRawRoom, ROOM_LAYOUT_HEIGHT, ROOM_LAYOUT_SIZE, ROOM_LAYOUT_WIDTH,
+    },
 };
 
-use super::Converter;
+use super::{
+    Converter,
+    collision::{cell_collision, room_collision_objects},
+    room_grid::room_grid,
+    room_manifest::room_file_name,
+    wang_sets::{merge_wang_sets, room_terrain},
+};
 
 const CELL_WIDTH: usize = 8;
 const CELL_HEIGHT: usize = 8;
 const CELL_BYTES: usize = (CELL_WIDTH / 8) * CELL_HEIGHT;
 const EMPTY_CELL_SPRITE: [u8; CELL_BYTES] = [0; CELL_BYTES];
+const CELL_TILESET_FIRST_GID: u32 = 1;
+/// Path of the cell tileset file, relative to the map.
+pub const CELL_TILESET_SOURCE: &str = "cells.tsj";
+const GUARDIAN_WIDTH: u32 = 16;
+const GUARDIAN_HEIGHT: u32 = 16;
+const PLAYER_WIDTH: u32 = 16;
+const PLAYER_HEIGHT: u32 = 16;
+/// Name of the enum of the `direction` properties, declared by the project (see
+/// [`super::tiled_project`]).
+pub(super) const DIRECTION_PROPERTY_TYPE: &str = "direction";
+/// Approximate duration of one pass of the original game loop, which moves conveyors once.
+const CONVEYOR_FRAME_DURATION_MS: u32 = 80;
+/// Duration of each FLASH phase: the Spectrum swaps ink and paper every 16 frames at 50Hz.
+const FLASH_FRAME_DURATION_MS: u32 = 16 * 1000 / 50;
+
+#[derive(Default)]
+pub struct RawToTiledConverter {
+    pub room_layout: RoomLayout,
+    /// Whether to add a `collision` layer of merged solid and platform rectangles to each room
+    pub collision_layer: bool,
+}
 
-pub struct RawToTiledConverter;
+/// How the rooms are arranged in the map.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum RoomLayout {
+    /// All the rooms at the same position, as groups on a map the size of the screen.
+    #[default]
+    Stacked,
+
+    /// Rooms next to each other on a grid worked out from their exits, as groups offset on a map
+    /// large enough for all of them.
+    Grid,
+}
 
 pub struct MapWithSpritesheet {
     pub map: Map,
+    /// The cell tileset, which the map refers to as an external tileset (see
+    /// [`CELL_TILESET_SOURCE`]).
+    pub cell_tileset: Tileset,
+    pub cell_spritesheet: Image,
+    pub cell_sprites: HashMap<u32, Image>,
+}
+
+/// The rooms of a game as separate maps, which refer to the cell tileset in the parent directory
+/// (see [`super::room_manifest::ROOM_MAPS_DIR`]).
+pub struct RoomMapsWithSpritesheet {
+    pub rooms: Vec<RoomMap>,
+    pub cell_tileset: Tileset,
     pub cell_spritesheet: Image,
     pub cell_sprites: HashMap<u32, Image>,
 }
 
+/// The map of a single room.
+pub struct RoomMap {
+    pub room_no: u8,
+    pub name: String,
+    pub map: Map,
+}
+
 struct ConvertContext {
     rooms: HashMap<u8, RoomContext>,
     cell_sprites: SpriteSetContext,
@@ -39,6 +106,9 @@ struct ConvertContext {
 
 struct RoomContext {
     cells: HashMap<u8, CellContext>,
+    bg_colour: Color,
+    /// The terrain of the walls and floors of the room, if it has any
+    wang_set: Option<WangSet>,
 }
 
 struct CellContext {
@@ -46,32 +116,92 @@ struct CellContext {
     fg_sprite_id: u32,
 }
 
+struct SpriteFrames {
+    images: Vec<Image>,
+    duration: u32,
+}
+
 struct SpriteSetContext {
     sprites: HashMap<u32, Image>,
+    /// Map of the sprite id of an animated sprite to its frames
+    animations: HashMap<u32, Vec<Frame>>,
+    /// Map of the sprite id to the behaviour of the cell it draws (foreground sprites only)
+    behaviours: HashMap<u32, CellBehaviour>,
+    /// Map of a sprite id and the edges it joins on (top, right, bottom, left) to the sprite
+    /// drawing it as a Wang tile with these edges
+    edge_tiles: HashMap<(u32, [bool; 4]), u32>,
+    /// Sprites which copy another sprite, so it has a Wang tile for each way of joining
+    copies: HashSet<u32>,
     next_sprite_id: u32,
 }
 
 impl Converter<JswRawGame, MapWithSpritesheet> for RawToTiledConverter {
     fn convert(&self, raw_game: &JswRawGame) -> Result<MapWithSpritesheet> {
         let mut context = ConvertContext::new();
-        let mut map = Map::new(
-            None,
-            MapOrientation::Orthogonal,
-            32,
-            24,
-            CELL_WIDTH as u32,
-            CELL_HEIGHT as u32,
-        );
+
+        // Grid maps are resized to fit the rooms once they are placed
+        let (width, height) = match self.room_layout {
+            RoomLayout::Stacked => (32, 24),
+            RoomLayout::Grid => (ROOM_LAYOUT_WIDTH as u32, ROOM_LAYOUT_HEIGHT as u32),
+        };
+        let mut map = MapBuilder::new(width, height, CELL_WIDTH as u32, CELL_HEIGHT as u32);
 
         let room_layers = self.convert_rooms(&mut context, &mut map, &raw_game.rooms)?;
 
         // Create the spritesheet
-        let cell_spritesheet = self.create_cell_spritesheet(&context, &mut map)?;
+        let (cell_spritesheet, mut cell_tileset) = self.create_cell_spritesheet(&context)?;
+        cell_tileset.firstgid = map.add_external_tileset(CELL_TILESET_SOURCE, &cell_tileset);
 
-        map.layers = room_layers;
+        for room_layer in room_layers {
+            map.add_layer(room_layer);
+        }
+
+        // Rooms are drawn over the map background, so only use it if all the rooms agree
+        map.background_color(context.common_bg_colour().map(color_to_string));
+        let map = map.build()?;
 
         Ok(MapWithSpritesheet {
             map,
+            cell_tileset,
+            cell_spritesheet,
+            cell_sprites: context.cell_sprites.sprites,
+        })
+    }
+}
+
+impl Converter<JswRawGame, RoomMapsWithSpritesheet> for RawToTiledConverter {
+    fn convert(&self, raw_game: &JswRawGame) -> Result<RoomMapsWithSpritesheet> {
+        let mut context = ConvertContext::new();
+
+        // Each room is alone on a map the size of the screen, so it is always shown
+        let mut room_maps = Vec::with_capacity(raw_game.rooms.len());
+        for room in &raw_game.rooms {
+            let mut map = MapBuilder::new(32, 24, CELL_WIDTH as u32, CELL_HEIGHT as u32);
+            let room_layer = self.convert_room(&mut context, &mut map, room, (0.0, 0.0), true)?;
+            map.add_layer(room_layer);
+            room_maps.push((room, map));
+        }
+
+        // The tileset is only complete once all the rooms are converted
+        let (cell_spritesheet, mut cell_tileset) = self.create_cell_spritesheet(&context)?;
+        let source = format!("../{}", CELL_TILESET_SOURCE);
+
+        let mut rooms = Vec::with_capacity(room_maps.len());
+        for (room, mut map) in room_maps {
+            cell_tileset.firstgid = map.add_external_tileset(&source, &cell_tileset);
+            let bg_colour = context.rooms[&room.room_no].bg_colour;
+            map.background_color(Some(color_to_string(bg_colour)));
+
+            rooms.push(RoomMap {
+                room_no: room.room_no,
+                name: room.name.clone(),
+                map: map.build()?,
+            });
+        }
+
+        Ok(RoomMapsWithSpritesheet {
+            rooms,
+            cell_tileset,
             cell_spritesheet,
             cell_sprites: context.cell_sprites.sprites,
         })
@@ -79,16 +209,47 @@ impl Converter<JswRawGame, MapWithSpritesheet> for RawToTiledConverter {
 }
 
 impl RawToTiledConverter {
+    pub fn new(room_layout: RoomLayout, collision_layer: bool) -> Self {
+        Self {
+            room_layout,
+            collision_layer,
+        }
+    }
+
     fn convert_rooms(
         &self,
         context: &mut ConvertContext,
-        map: &mut Map,
+        map: &mut MapBuilder,
         rooms: &Vec<JswRawRoom>,
     ) -> Result<Vec<Layer>> {
         let mut layers = Vec::new();
 
+        let grid = match self.room_layout {
+            RoomLayout::Stacked => None,
+            RoomLayout::Grid => Some(room_grid(rooms)),
+        };
+
         for room in rooms {
-            layers.push(self.convert_room(context, map, room)?);
+            let offset = match grid.as_ref().and_then(|grid| grid.get(&room.room_no)) {
+                Some((col, row)) => (
+                    (*col as usize * ROOM_LAYOUT_WIDTH * CELL_WIDTH) as f64,
+                    (*row as usize * ROOM_LAYOUT_HEIGHT * CELL_HEIGHT) as f64,
+                ),
+                None => (0.0, 0.0),
+            };
+
+            // Stacked rooms overlap, so only show them in a grid
+            let visible = self.room_layout == RoomLayout::Grid;
+            layers.push(self.convert_room(context, map, room, offset, visible)?);
+        }
+
+        if let Some(grid) = grid {
+            let cols = grid.values().map(|(col, _)| col + 1).max().unwrap_or(1);
+            let rows = grid.values().map(|(_, row)| row + 1).max().unwrap_or(1);
+            map.size(
+                cols as u32 * ROOM_LAYOUT_WIDTH as u32,
+                rows as u32 * ROOM_LAYOUT_HEIGHT as u32,
+            );
         }
 
         // Layers are stored in reverse order
@@ -100,36 +261,34 @@ impl RawToTiledConverter {
     fn convert_room(
         &self,
         context: &mut ConvertContext,
-        map: &mut Map,
+        map: &mut MapBuilder,
         room: &JswRawRoom,
+        (offsetx, offsety): (f64, f64),
+        visible: bool,
     ) -> Result<Layer> {
         let mut room_context = RoomContext::new();
-        let mut room_layer = Layer::new(map, LayerType::Group, room.name.clone());
-        let mut room_layer_layers = Vec::new();
-
-        let mut bg_layer = Layer::new(map, LayerType::TileLayer, "Background 1".to_string());
-        bg_layer.class = Some("bg".to_string());
-        bg_layer.visible = true;
-
-        let mut object_layer = Layer::new(map, LayerType::ObjectGroup, "Dynamic 1".to_string());
-        object_layer.class = Some("dynamic".to_string());
-        object_layer.visible = true;
-
-        let mut fg_layer = Layer::new(map, LayerType::TileLayer, "Foreground 1".to_string());
-        fg_layer.class = Some("fg".to_string());
-        fg_layer.visible = true;
-
-        // TODO - set the background colour from the first cell (air)
-        // NEED TO IMPLEMENT PROPERTY SERIALIZATION
-        // let mut bg_colour = BLACK;
-        // if let Some(c) = room.cells.first().map(|c| c.paper) {
-        //     bg_colour = c;
-        // }
-        // room_layer.properties.push(Property {
-        //     typ: property_type::STRING.to_string(),
-        //     name: "bg_color".to_string(),
-        //     value: PropertyVal::String(color_to_string(bg_colour)),
-        // });
+        let mut room_layer = map
+            .group(&room.name)
+            .class("room")
+            .visible(visible)
+            .offset(offsetx, offsety);
+        let mut bg_layer = map.tile_layer("Background 1").class("bg");
+        let object_layer = map.object_group("Dynamic 1").class("dynamic");
+        let mut fg_layer = map.tile_layer("Foreground 1").class("fg");
+
+        // Set the background colour from the air cell, and the border colour
+        let bg_colour = room.bg_colour().unwrap_or(BLACK);
+        room_layer = room_layer.property(Property::new(
+            "bg_color",
+            PropertyVal::Color(color_to_string(bg_colour)),
+        ));
+        if let Some(border) = room.border {
+            room_layer = room_layer.property(Property::new(
+                "border_color",
+                PropertyVal::Color(color_to_string(border)),
+            ));
+        }
+        room_context.bg_colour = bg_colour;
 
         // Convert the cells to sprites
         let cell_sprites = &mut context.cell_sprites;
@@ -139,38 +298,10 @@ impl RawToTiledConverter {
             //     continue;
             // }
 
-            let bg_sprite_image = create_image_from_sprite_data(
-                &EMPTY_CELL_SPRITE,
-                CELL_WIDTH,
-                CELL_HEIGHT,
-                TRANSPARENT,
-                cell.paper,
-            )?;
-            let fg_sprite_image = create_image_from_sprite_data(
-                &cell.sprite,
-                CELL_WIDTH,
-                CELL_HEIGHT,
-                cell.ink,
-                TRANSPARENT,
-            )?;
-
-            // See if the same image already exists, otherwise add it
-            let mut find_sprite_id = |image: Image| -> u32 {
-                let sprite_id = cell_sprites
-                    .sprites
-                    .iter()
-                    .find(|(_, existing_image)| existing_image.bytes == image.bytes)
-                    .map(|(id, _)| *id)
-                    .unwrap_or_else(|| {
-                        let sprite_id = cell_sprites.get_next_sprite_id();
-                        cell_sprites.sprites.insert(sprite_id, image);
-                        sprite_id
-                    });
-                sprite_id
-            };
-
-            let bg_sprite_id = find_sprite_id(bg_sprite_image);
-            let fg_sprite_id = find_sprite_id(fg_sprite_image);
+            let bg_sprite_id =
+                cell_sprites.find_or_insert_frames(self.create_bg_frames(cell)?, None);
+            let fg_sprite_id = cell_sprites
+                .find_or_insert_frames(self.create_fg_frames(cell)?, Some(cell.behaviour));
 
             let cell_context = CellContext {
                 bg_sprite_id,
@@ -179,13 +310,20 @@ impl RawToTiledConverter {
             room_context.cells.insert(cell.id, cell_context);
         }
 
+        let tile_ids: HashMap<u8, u32> = room_context
+            .cells
+            .iter()
+            .map(|(id, cell)| (*id, cell.fg_sprite_id - CELL_TILESET_FIRST_GID))
+            .collect();
+        let terrain = room_terrain(room, &tile_ids, |tileid, edges| {
+            let sprite_id = tileid + CELL_TILESET_FIRST_GID;
+            cell_sprites.find_or_insert_edge_tile(sprite_id, edges) - CELL_TILESET_FIRST_GID
+        });
+
         // Add some tiles to the static layer
-        let cols = bg_layer.width.unwrap() as usize;
-        let mut bg_data = bg_layer.get_tile_matrix();
-        let mut fg_data = fg_layer.get_tile_matrix();
         for (i, cell_id) in room.layout.iter().enumerate().take(ROOM_LAYOUT_SIZE) {
-            let col = i % cols;
-            let row = i / cols;
+            let col = (i % ROOM_LAYOUT_WIDTH) as i32;
+            let row = (i / ROOM_LAYOUT_WIDTH) as i32;
 
             // Find the cell for the cell_id
             // If the cell is not found, use the first cell (and if there is no first cell, raise an error):
@@ -195,58 +333,312 @@ impl RawToTiledConverter {
                 .find(|c| c.id == *cell_id)
                 .or(room.cells.first());
 
-            if let Some(cell) = cell {
-                if let Some(cell_context) = room_context.cells.get(&cell.id) {
-                    bg_data[row][col] = cell_context.bg_sprite_id;
-                    fg_data[row][col] = cell_context.fg_sprite_id;
-                } else {
-                    bg_data[row][col] = 0;
-                    fg_data[row][col] = 0;
-                };
-            } else {
+            let Some(cell) = cell else {
                 return Err(Error::GameConversionFailed {
                     mode: GameConversionError::RoomConversionFailed {
                         room: room.name.clone(),
                     },
                     message: "No cells found".to_string(),
                 });
-            }
+            };
+            let (bg_sprite_id, fg_sprite_id) = room_context
+                .cells
+                .get(&cell.id)
+                .map_or((0, 0), |cell_context| {
+                    (cell_context.bg_sprite_id, cell_context.fg_sprite_id)
+                });
+            // Walls and floors are drawn with the tile of the way they join
+            let fg_sprite_id = terrain
+                .as_ref()
+                .and_then(|terrain| terrain.tiles.get(&i))
+                .map_or(fg_sprite_id, |tileid| tileid + CELL_TILESET_FIRST_GID);
+            bg_layer = bg_layer.tile(col, row, Gid(bg_sprite_id));
+            fg_layer = fg_layer.tile(col, row, Gid(fg_sprite_id));
         }
 
-        // data[0][0] = 1;
-        // data[20][10] = 1;
+        // Add the items, guardians, ropes, arrows and player start to the dynamic layer
+        let item_sprite_id = room
+            .item_cell()
+            .and_then(|cell| room_context.cells.get(&cell.id))
+            .map(|cell_context| cell_context.fg_sprite_id);
+        let object_layer =
+            object_layer.objects(self.create_room_objects(map, room, item_sprite_id)?);
 
-        room_layer_layers.push(bg_layer);
-        room_layer_layers.push(object_layer);
-        room_layer_layers.push(fg_layer);
+        let mut room_layer = room_layer
+            .layer(bg_layer.build()?)
+            .layer(object_layer.build()?)
+            .layer(fg_layer.build()?);
 
-        // Layers are stored in reverse order
-        // room_layer_layers.reverse();
-        room_layer.layers = Some(room_layer_layers);
+        if self.collision_layer {
+            let collision_layer = map
+                .object_group("Collision")
+                .class("collision")
+                .visible(false);
+            let objects = room_collision_objects(map, room, (CELL_WIDTH, CELL_HEIGHT))?;
+            room_layer = room_layer.layer(collision_layer.objects(objects).build()?);
+        }
+        let room_layer = room_layer.build()?;
 
         // Add the room to the context
+        room_context.wang_set = terrain.map(|terrain| terrain.wang_set);
         context.rooms.insert(room.room_no, room_context);
 
         Ok(room_layer)
     }
 
-    fn create_cell_spritesheet(&self, context: &ConvertContext, map: &mut Map) -> Result<Image> {
+    /// Creates the objects of a room.
+    ///
+    /// Items are tile objects drawn with the item cell, and guardians are linked to a polyline of
+    /// their patrol by their `patrol` property, so both can be moved in the editor.
+    fn create_room_objects(
+        &self,
+        map: &mut MapBuilder,
+        room: &JswRawRoom,
+        item_sprite_id: Option<u32>,
+    ) -> Result<Vec<Object>> {
+        let mut objects = Vec::new();
+
+        for item in &room.items {
+            let x = item.col as f64 * CELL_WIDTH as f64;
+            // Tile objects are positioned by their bottom left corner
+            let y = (item.row as f64 + 1.0) * CELL_HEIGHT as f64;
+            let mut object = new_object(
+                map,
+                "Item",
+                "item",
+                (x, y),
+                (CELL_WIDTH as u32, CELL_HEIGHT as u32),
+            )
+            .property(Property::new(
+                "color",
+                PropertyVal::Color(color_to_string(item.ink)),
+            ));
+            if let Some(item_sprite_id) = item_sprite_id {
+                object = object.gid(Gid(item_sprite_id));
+            }
+            objects.push(object.build()?);
+        }
+
+        for entity in &room.entities {
+            objects.extend(self.create_entity_objects(map, entity)?);
+        }
+
+        if let Some(start) = &room.player_start {
+            let object = new_object(
+                map,
+                "Player start",
+                "player",
+                (start.x as f64, start.y as f64),
+                (PLAYER_WIDTH, PLAYER_HEIGHT),
+            )
+            .property(direction_property(start.direction));
+            objects.push(object.build()?);
+        }
+
+        Ok(objects)
+    }
+
+    /// Creates the objects of a guardian (and its patrol), rope or arrow.
+    fn create_entity_objects(
+        &self,
+        map: &mut MapBuilder,
+        entity: &JswRawEntity,
+    ) -> Result<Vec<Object>> {
+        let position = (entity.x as f64, entity.y as f64);
+        let speed = Property::new("speed", PropertyVal::Integer(entity.speed as i64));
+        let color = entity
+            .ink
+            .map(|ink| Property::new("color", PropertyVal::Color(color_to_string(ink))));
+
+        match entity.kind {
+            EntityKind::HorizontalGuardian | EntityKind::VerticalGuardian => {
+                let (min, max) = entity.bounds.unwrap_or((0, 0));
+                // Bounds are read from the game data, which isn't checked to be in order
+                let length = max.saturating_sub(min) as f64;
+                let (start, end) = if entity.kind == EntityKind::HorizontalGuardian {
+                    ((min as f64, position.1), Point { x: length, y: 0.0 })
+                } else {
+                    ((position.0, min as f64), Point { x: 0.0, y: length })
+                };
+                let patrol = new_object(map, "Guardian patrol", "patrol", start, (0, 0))
+                    .polyline(vec![Point { x: 0.0, y: 0.0 }, end]);
+
+                let mut guardian = new_object(
+                    map,
+                    "Guardian",
+                    "guardian",
+                    position,
+                    (GUARDIAN_WIDTH, GUARDIAN_HEIGHT),
+                )
+                .property(speed)
+                .property(direction_property(entity.direction))
+                .property(Property::new("patrol", PropertyVal::Object(patrol.id())));
+                if let Some(color) = color {
+                    guardian = guardian.property(color);
+                }
+
+                Ok(vec![guardian.build()?, patrol.build()?])
+            }
+            EntityKind::Rope => {
+                let rope = new_object(map, "Rope", "rope", position, (0, 0))
+                    .polyline(vec![
+                        Point { x: 0.0, y: 0.0 },
+                        Point {
+                            x: 0.0,
+                            y: entity.length as f64,
+                        },
+                    ])
+                    .property(direction_property(entity.direction));
+
+                Ok(vec![rope.build()?])
+            }
+            EntityKind::Arrow => {
+                let arrow = new_object(map, "Arrow", "arrow", position, (CELL_WIDTH as u32, 1))
+                    .property(speed)
+                    .property(direction_property(entity.direction))
+                    .property(Property::new(
+                        "delay",
+                        PropertyVal::Integer(entity.delay as i64),
+                    ));
+
+                Ok(vec![arrow.build()?])
+            }
+        }
+    }
+
+    /// Creates the paper frames of a cell, alternating with the ink colour if the cell flashes.
+    fn create_bg_frames(&self, cell: &JswRawCell) -> Result<SpriteFrames> {
+        let mut colours = vec![cell.paper];
+        if cell.flash {
+            colours.push(cell.ink);
+        }
+
+        let mut images = Vec::with_capacity(colours.len());
+        for colour in colours {
+            images.push(create_image_from_sprite_data(
+                &EMPTY_CELL_SPRITE,
+                CELL_WIDTH,
+                CELL_HEIGHT,
+                TRANSPARENT,
+                colour,
+            )?);
+        }
+
+        Ok(SpriteFrames {
+            images,
+            duration: FLASH_FRAME_DURATION_MS,
+        })
+    }
+
+    /// Creates the ink frames of a cell, moving conveyors and swapping to the paper colour if the
+    /// cell flashes.
+    fn create_fg_frames(&self, cell: &JswRawCell) -> Result<SpriteFrames> {
+        // Conveyors whose rotated rows are symmetrical do not visibly move
+        let conveyor_frames = cell
+            .conveyor_frames()
+            .filter(|frames| frames.iter().any(|frame| *frame != cell.sprite));
+
+        // Conveyor frames are shorter than a flash phase, so a flashing conveyor repeats its
+        // frames in each phase
+        let (sprites, duration) = match conveyor_frames {
+            Some(frames) if cell.flash => {
+                let frame_count = 2 * (FLASH_FRAME_DURATION_MS / CONVEYOR_FRAME_DURATION_MS);
+                let sprites = (0..frame_count as usize)
+                    .map(|i| frames[i % CONVEYOR_FRAME_COUNT])
+                    .collect();
+                (sprites, CONVEYOR_FRAME_DURATION_MS)
+            }
+            Some(frames) => (frames, CONVEYOR_FRAME_DURATION_MS),
+            None if cell.flash => (vec![cell.sprite; 2], FLASH_FRAME_DURATION_MS),
+            None => (vec![cell.sprite], FLASH_FRAME_DURATION_MS),
+        };
+
+        let mut images = Vec::with_capacity(sprites.len());
+        for (i, sprite) in sprites.iter().enumerate() {
+            let elapsed = i as u32 * duration;
+            let colour = if cell.flash && (elapsed / FLASH_FRAME_DURATION_MS) % 2 == 1 {
+                cell.paper
+            } else {
+                cell.ink
+            };
+
+            images.push(create_image_from_sprite_data(
+                sprite,
+                CELL_WIDTH,
+                CELL_HEIGHT,
+                colour,
+                TRANSPARENT,
+            )?);
+        }
+
+        Ok(SpriteFrames { images, duration })
+    }
+
+    fn create_cell_spritesheet(&self, context: &ConvertContext) -> Result<(Image, Tileset)> {
         let sprite_images: Vec<&Image> = context.get_cell_sprites_vec();
         let spritesheet = create_spritesheet(sprite_images);
 
-        // Add the tileset for the spritesheet
-        let tileset = Tileset::new(
-            "cells".to_string(),
-            "gfx/cells.png".to_string(),
+        // Create the tileset for the spritesheet
+        let tileset = TilesetBuilder::new("cells", CELL_WIDTH as u32, CELL_HEIGHT as u32).image(
+            "gfx/cells.png",
             spritesheet.width as u32,
             spritesheet.height as u32,
-            8,
-            8,
-            1,
         );
-        map.tilesets.push(tileset);
 
-        Ok(spritesheet)
+        // Add the animated tiles, and the cell behaviour and collision shapes of the foreground
+        // tiles
+        let cell_sprites = &context.cell_sprites;
+        let mut sprite_ids: Vec<u32> = cell_sprites
+            .animations
+            .keys()
+            .chain(cell_sprites.behaviours.keys())
+            .copied()
+            .collect();
+        sprite_ids.sort();
+        sprite_ids.dedup();
+
+        let tiles: Vec<Tile> = sprite_ids
+            .into_iter()
+            .map(|sprite_id| {
+                let behaviour = cell_sprites.behaviours.get(&sprite_id);
+                Tile {
+                    id: sprite_id - CELL_TILESET_FIRST_GID,
+                    animation: cell_sprites.animations.get(&sprite_id).cloned(),
+                    typ: behaviour.map(|b| cell_class(*b).to_string()),
+                    properties: behaviour.map(|b| cell_properties(*b)),
+                    objectgroup: behaviour
+                        .zip(cell_sprites.sprites.get(&sprite_id))
+                        .and_then(|(b, image)| cell_collision(*b, image)),
+                    ..Default::default()
+                }
+            })
+            .collect();
+        let mut tileset = if tiles.is_empty() {
+            tileset
+        } else {
+            tileset.tiles(tiles)
+        };
+
+        // Add the terrain of the rooms in their order, merging the rooms with the same walls and
+        // floors into the set of the first of them
+        let mut room_nos: Vec<&u8> = context.rooms.keys().collect();
+        room_nos.sort();
+        let wang_sets = room_nos
+            .into_iter()
+            .filter_map(|room_no| context.rooms[room_no].wang_set.as_ref());
+        for wang_set in merge_wang_sets(wang_sets) {
+            tileset = tileset.wang_set(wang_set);
+        }
+
+        Ok((spritesheet, tileset.build()?))
+    }
+}
+
+impl RoomMap {
+    /// Returns the file name of the map, such as `00_central_cavern.json` (see
+    /// [`room_file_name`]).
+    pub fn file_name(&self, extension: &str) -> String {
+        room_file_name(self.room_no, &self.name, extension)
     }
 }
 
@@ -264,6 +656,14 @@ impl ConvertContext {
         }
     }
 
+    /// Returns the background colour shared by all the rooms, if there is one.
+    fn common_bg_colour(&self) -> Option<Color> {
+        let mut bg_colours = self.rooms.values().map(|room| room.bg_colour);
+        let first = bg_colours.next()?;
+
+        bg_colours.all(|c| c == first).then_some(first)
+    }
+
     fn get_cell_sprites_vec(&self) -> Vec<&Image> {
         let mut sprites_vec: Vec<&Image> = vec![];
 
@@ -287,6 +687,8 @@ impl RoomContext {
     fn new() -> Self {
         Self {
             cells: HashMap::new(),
+            bg_colour: BLACK,
+            wang_set: None,
         }
     }
 }
@@ -295,8 +697,133 @@ impl SpriteSetContext {
     fn new() -> Self {
         Self {
             sprites: HashMap::new(),
-            next_sprite_id: 1,
+            animations: HashMap::new(),
+            behaviours: HashMap::new(),
+            edge_tiles: HashMap::new(),
+            copies: HashSet::new(),
+            next_sprite_id: CELL_TILESET_FIRST_GID,
+        }
+    }
+
+    /// Returns the id of a static sprite with the same image and behaviour, adding the image if
+    /// not found.
+    fn find_or_insert_sprite(&mut self, image: Image, behaviour: Option<CellBehaviour>) -> u32 {
+        let existing_sprite_id = self
+            .sprites
+            .iter()
+            .find(|(id, existing_image)| {
+                !self.animations.contains_key(id)
+                    && !self.copies.contains(id)
+                    && self.behaviours.get(id) == behaviour.as_ref()
+                    && existing_image.bytes == image.bytes
+            })
+            .map(|(id, _)| *id);
+
+        existing_sprite_id.unwrap_or_else(|| self.insert_sprite(image, behaviour))
+    }
+
+    /// Returns the id of a sprite for the frames, which is animated if there is more than one frame.
+    fn find_or_insert_frames(
+        &mut self,
+        frames: SpriteFrames,
+        behaviour: Option<CellBehaviour>,
+    ) -> u32 {
+        let SpriteFrames {
+            mut images,
+            duration,
+        } = frames;
+
+        if images.len() > 1 {
+            self.find_or_insert_animation(images, duration, behaviour)
+        } else {
+            images
+                .pop()
+                .map(|image| self.find_or_insert_sprite(image, behaviour))
+                .unwrap_or(0)
+        }
+    }
+
+    /// Returns the id of an animated sprite with the same frames and behaviour, adding the frames
+    /// if not found.
+    ///
+    /// Each frame is stored as a static sprite, and the animated sprite (which shows the first
+    /// frame) references them. Returns a static sprite if all frames are the same, or 0 (no
+    /// sprite) if there are no frames.
+    fn find_or_insert_animation(
+        &mut self,
+        images: Vec<Image>,
+        duration: u32,
+        behaviour: Option<CellBehaviour>,
+    ) -> u32 {
+        let Some(first_image) = images.first().cloned() else {
+            return 0;
+        };
+
+        let frames: Vec<Frame> = images
+            .into_iter()
+            .map(|image| Frame {
+                tileid: self.find_or_insert_sprite(image, None) - CELL_TILESET_FIRST_GID,
+                duration,
+            })
+            .collect();
+
+        // Frames that all look the same are not animated
+        if frames.iter().all(|frame| frame.tileid == frames[0].tileid) {
+            return self.find_or_insert_sprite(first_image, behaviour);
         }
+
+        let existing_sprite_id = self
+            .animations
+            .iter()
+            .find(|(id, existing_frames)| {
+                !self.copies.contains(id)
+                    && self.behaviours.get(id) == behaviour.as_ref()
+                    && existing_frames.len() == frames.len()
+                    && existing_frames
+                        .iter()
+                        .zip(&frames)
+                        .all(|(a, b)| a.tileid == b.tileid && a.duration == b.duration)
+            })
+            .map(|(id, _)| *id);
+
+        existing_sprite_id.unwrap_or_else(|| {
+            let sprite_id = self.insert_sprite(first_image, behaviour);
+            self.animations.insert(sprite_id, frames);
+            sprite_id
+        })
+    }
+
+    /// Returns the id of a sprite drawing another sprite as a Wang tile joining on some edges (top,
+    /// right, bottom, left): the sprite itself for the first edges, and a copy of it for others.
+    fn find_or_insert_edge_tile(&mut self, sprite_id: u32, edges: [bool; 4]) -> u32 {
+        if let Some(edge_sprite_id) = self.edge_tiles.get(&(sprite_id, edges)) {
+            return *edge_sprite_id;
+        }
+
+        let edge_sprite_id = if self.edge_tiles.keys().any(|(id, _)| *id == sprite_id) {
+            let image = self.sprites[&sprite_id].clone();
+            let behaviour = self.behaviours.get(&sprite_id).copied();
+            let copy_id = self.insert_sprite(image, behaviour);
+            if let Some(frames) = self.animations.get(&sprite_id).cloned() {
+                self.animations.insert(copy_id, frames);
+            }
+            self.copies.insert(copy_id);
+            copy_id
+        } else {
+            sprite_id
+        };
+        self.edge_tiles.insert((sprite_id, edges), edge_sprite_id);
+
+        edge_sprite_id
+    }
+
+    fn insert_sprite(&mut self, image: Image, behaviour: Option<CellBehaviour>) -> u32 {
+        let sprite_id = self.get_next_sprite_id();
+        self.sprites.insert(sprite_id, image);
+        if let Some(behaviour) = behaviour {
+            self.behaviours.insert(sprite_id, behaviour);
+        }
+        sprite_id
     }
 
     fn get_next_sprite_id(&mut self) -> u32 {
@@ -306,12 +833,348 @@ impl SpriteSetContext {
     }
 }
 
-fn color_to_string(color: Color) -> String {
+/// Returns the Tiled class of the tiles of a cell.
+pub(super) fn cell_class(behaviour: CellBehaviour) -> &'static str {
+    match behaviour {
+        CellBehaviour::Air => "air",
+        CellBehaviour::Water => "water",
+        CellBehaviour::Earth => "earth",
+        CellBehaviour::Fire => "fire",
+        CellBehaviour::LRamp => "lramp",
+        CellBehaviour::RRamp => "rramp",
+        CellBehaviour::LConveyor => "lconveyor",
+        CellBehaviour::RConveyor => "rconveyor",
+        CellBehaviour::Crumbly => "crumbly",
+        CellBehaviour::Item => "item",
+        CellBehaviour::Door => "door",
+        CellBehaviour::Trigger => "trigger",
+        CellBehaviour::Trampoline => "trampoline",
+        CellBehaviour::Trap => "trap",
+    }
+}
+
+/// Returns the properties of the tiles of a cell, so collision can be derived from the map alone.
+///
+/// - `solid`: blocks movement from all sides
+/// - `platform`: can be stood on
+/// - `deadly`: kills the player on contact
+/// - `collectable`: is collected on contact
+/// - `conveyor_direction` (conveyors only): -1 moves the player left, 1 moves the player right
+/// - `ramp_direction` (ramps only): -1 rises to the left, 1 rises to the right
+pub(super) fn cell_properties(behaviour: CellBehaviour) -> Vec<Property> {
+    use CellBehaviour::*;
+
+    let mut properties = vec![
+        Property::new(
+            "solid",
+            PropertyVal::Boolean(matches!(behaviour, Earth | Door)),
+        ),
+        Property::new(
+            "platform",
+            PropertyVal::Boolean(matches!(
+                behaviour,
+                Water | Earth | LConveyor | RConveyor | Crumbly | Door | Trampoline
+            )),
+        ),
+        Property::new(
+            "deadly",
+            PropertyVal::Boolean(matches!(behaviour, Fire | Trap)),
+        ),
+        Property::new("collectable", PropertyVal::Boolean(behaviour == Item)),
+    ];
+
+    match behaviour {
+        LConveyor => properties.push(Property::new(
+            "conveyor_direction",
+            PropertyVal::Integer(-1),
+        )),
+        RConveyor => properties.push(Property::new("conveyor_direction", PropertyVal::Integer(1))),
+        LRamp => properties.push(Property::new("ramp_direction", PropertyVal::Integer(-1))),
+        RRamp => properties.push(Property::new("ramp_direction", PropertyVal::Integer(1))),
+        _ => (),
+    }
+
+    properties
+}
+
+/// Starts building an object of a class.
+fn new_object(
+    map: &mut MapBuilder,
+    name: &str,
+    class: &str,
+    (x, y): (f64, f64),
+    (width, height): (u32, u32),
+) -> ObjectBuilder {
+    map.object(name)
+        .class(class)
+        .position(x, y)
+        .size(width as f64, height as f64)
+}
+
+/// Returns the `direction` property of an object: "left", "right", "up" or "down".
+fn direction_property(direction: Direction) -> Property {
+    let direction = match direction {
+        Direction::Left => "left",
+        Direction::Right => "right",
+        Direction::Up => "up",
+        Direction::Down => "down",
+    };
+
+    Property::new("direction", PropertyVal::String(direction.to_string()))
+        .with_property_type(DIRECTION_PROPERTY_TYPE)
+}
+
+/// Formats a colour as a Tiled colour (#AARRGGBB).
+pub(super) fn color_to_string(color: Color) -> String {
     format!(
         "#{:02x}{:02x}{:02x}{:02x}",
+        (color.a * 255.0) as u8,
         (color.r * 255.0) as u8,
         (color.g * 255.0) as u8,
-        (color.b * 255.0) as u8,
-        (color.a * 255.0) as u8
+        (color.b * 255.0) as u8
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::room_manifest::RoomManifest;
+    use super::*;
+    use crate::{game::GameType, raw_game::JswRawExits};
+
+    const FLASH: u8 = 0x80;
+
+    /// Creates a cell with yellow ink on blue paper, and the top left pixel of the sprite set.
+    fn cell(behaviour: CellBehaviour, flash: u8) -> JswRawCell {
+        let sprite = [0b1000_0000, 0, 0b1100_0000, 0, 0, 0, 0, 0];
+        JswRawCell::new(1, flash | (1 << 3) | 6, behaviour, sprite)
+    }
+
+    #[test]
+    fn flashing_cells_swap_ink_and_paper() {
+        let converter = RawToTiledConverter::default();
+        let cell = cell(CellBehaviour::Earth, FLASH);
+
+        let bg = converter.create_bg_frames(&cell).unwrap();
+        assert_eq!(bg.images.len(), 2);
+        assert_eq!(bg.duration, 320);
+        assert_eq!(bg.images[0].get_pixel(0, 0), cell.paper);
+        assert_eq!(bg.images[1].get_pixel(0, 0), cell.ink);
+
+        let fg = converter.create_fg_frames(&cell).unwrap();
+        assert_eq!(fg.images.len(), 2);
+        assert_eq!(fg.duration, 320);
+        assert_eq!(fg.images[0].get_pixel(0, 0), cell.ink);
+        assert_eq!(fg.images[1].get_pixel(0, 0), cell.paper);
+        assert_eq!(fg.images[1].get_pixel(1, 0), TRANSPARENT);
+    }
+
+    #[test]
+    fn steady_cells_have_a_single_frame() {
+        let converter = RawToTiledConverter::default();
+        let cell = cell(CellBehaviour::Earth, 0);
+
+        assert_eq!(converter.create_bg_frames(&cell).unwrap().images.len(), 1);
+        assert_eq!(converter.create_fg_frames(&cell).unwrap().images.len(), 1);
+    }
+
+    #[test]
+    fn flashing_conveyors_move_in_both_phases() {
+        let converter = RawToTiledConverter::default();
+        let cell = cell(CellBehaviour::LConveyor, FLASH);
+
+        let fg = converter.create_fg_frames(&cell).unwrap();
+
+        // Each 320 ms phase shows the 4 conveyor frames of 80 ms
+        assert_eq!(fg.images.len(), 8);
+        assert_eq!(fg.duration, 80);
+        assert_eq!(fg.images[0].get_pixel(0, 0), cell.ink);
+        assert_eq!(fg.images[1].get_pixel(0, 0), TRANSPARENT);
+        assert_eq!(fg.images[1].get_pixel(6, 0), cell.ink);
+        assert_eq!(fg.images[4].get_pixel(0, 0), cell.paper);
+        assert_eq!(fg.images[5].get_pixel(6, 0), cell.paper);
+    }
+
+    /// Returns the value of a property by name.
+    fn property(properties: &[Property], name: &str) -> Option<PropertyVal> {
+        properties
+            .iter()
+            .find(|property| property.name == name)
+            .map(|property| property.value.clone())
+    }
+
+    #[test]
+    fn earth_is_solid() {
+        let properties = cell_properties(CellBehaviour::Earth);
+
+        assert_eq!(cell_class(CellBehaviour::Earth), "earth");
+        assert_eq!(
+            property(&properties, "solid"),
+            Some(PropertyVal::Boolean(true))
+        );
+        assert_eq!(
+            property(&properties, "platform"),
+            Some(PropertyVal::Boolean(true))
+        );
+        assert_eq!(
+            property(&properties, "deadly"),
+            Some(PropertyVal::Boolean(false))
+        );
+        assert_eq!(property(&properties, "conveyor_direction"), None);
+    }
+
+    #[test]
+    fn water_is_a_platform() {
+        let properties = cell_properties(CellBehaviour::Water);
+
+        assert_eq!(cell_class(CellBehaviour::Water), "water");
+        assert_eq!(
+            property(&properties, "solid"),
+            Some(PropertyVal::Boolean(false))
+        );
+        assert_eq!(
+            property(&properties, "platform"),
+            Some(PropertyVal::Boolean(true))
+        );
+    }
+
+    #[test]
+    fn conveyors_have_a_direction() {
+        let left = cell_properties(CellBehaviour::LConveyor);
+        let right = cell_properties(CellBehaviour::RConveyor);
+
+        assert_eq!(cell_class(CellBehaviour::LConveyor), "lconveyor");
+        assert_eq!(cell_class(CellBehaviour::RConveyor), "rconveyor");
+        assert_eq!(
+            property(&left, "conveyor_direction"),
+            Some(PropertyVal::Integer(-1))
+        );
+        assert_eq!(
+            property(&right, "conveyor_direction"),
+            Some(PropertyVal::Integer(1))
+        );
+        assert_eq!(
+            property(&left, "platform"),
+            Some(PropertyVal::Boolean(true))
+        );
+    }
+
+    #[test]
+    fn colors_are_written_as_argb() {
+        assert_eq!(color_to_string(Color::new(1.0, 0.0, 0.5, 1.0)), "#ffff007f");
+        assert_eq!(color_to_string(Color::new(0.0, 1.0, 0.0, 0.0)), "#0000ff00");
+    }
+
+    #[test]
+    fn reversed_guardian_bounds_give_an_empty_patrol() {
+        let converter = RawToTiledConverter::new(RoomLayout::default(), false);
+        let mut map = MapBuilder::new(32, 16, 8, 8);
+        let guardian = JswRawEntity::guardian(
+            EntityKind::HorizontalGuardian,
+            (32, 16),
+            Direction::Right,
+            2,
+            BLACK,
+            (64, 8),
+        );
+
+        let objects = converter
+            .create_entity_objects(&mut map, &guardian)
+            .unwrap();
+
+        let patrol = objects
+            .iter()
+            .find(|object| object.class.as_deref() == Some("patrol"))
+            .unwrap();
+        let polyline = patrol.polyline.as_ref().unwrap();
+        assert_eq!(polyline[1].x, 0.0);
+        assert_eq!(patrol.x, 64.0);
+    }
+
+    /// Creates a game of two rooms next to each other, with a floor along the bottom, and paper of
+    /// the colour of their number.
+    fn game() -> JswRawGame {
+        let room = |room_no: u8, name: &str, exits: JswRawExits| {
+            let mut layout = [0; ROOM_LAYOUT_SIZE];
+            layout[ROOM_LAYOUT_SIZE - ROOM_LAYOUT_WIDTH..].fill(1);
+            JswRawRoom {
+                room_no,
+                name: name.to_string(),
+                layout,
+                cells: vec![
+                    JswRawCell::new(0, room_no << 3, CellBehaviour::Air, EMPTY_CELL_SPRITE),
+                    JswRawCell::new(1, 0x04, CellBehaviour::Water, [0xff; 8]),
+                ],
+                border: None,
+                items: vec![],
+                entities: vec![],
+                player_start: None,
+                exits,
+            }
+        };
+        let left = JswRawExits {
+            right: Some(1),
+            ..Default::default()
+        };
+        let right = JswRawExits {
+            left: Some(0),
+            ..Default::default()
+        };
+
+        JswRawGame::new(
+            GameType::JSW,
+            vec![room(0, "Left", left), room(1, "Right", right)],
+        )
+    }
+
+    /// Returns the visibility of the room groups of a map.
+    fn room_visibility(map: &Map) -> Vec<bool> {
+        map.layers
+            .iter()
+            .filter(|layer| layer.class.as_deref() == Some("room"))
+            .map(|layer| layer.visible)
+            .collect()
+    }
+
+    #[test]
+    fn only_grid_rooms_are_visible() {
+        let game = game();
+
+        let stacked: MapWithSpritesheet = RawToTiledConverter::new(RoomLayout::Stacked, false)
+            .convert(&game)
+            .unwrap();
+        assert_eq!(room_visibility(&stacked.map), [false, false]);
+
+        let grid: MapWithSpritesheet = RawToTiledConverter::new(RoomLayout::Grid, false)
+            .convert(&game)
+            .unwrap();
+        assert_eq!(room_visibility(&grid.map), [true, true]);
+    }
+
+    #[test]
+    fn room_maps_show_their_room() {
+        let game = game();
+
+        let room_maps: RoomMapsWithSpritesheet =
+            RawToTiledConverter::default().convert(&game).unwrap();
+
+        assert_eq!(room_maps.rooms.len(), 2);
+        assert_ne!(
+            room_maps.rooms[0].map.backgroundcolor,
+            room_maps.rooms[1].map.backgroundcolor
+        );
+        for (room, raw_room) in room_maps.rooms.iter().zip(&game.rooms) {
+            assert_eq!(room_visibility(&room.map), [true]);
+            let tileset = &room.map.tilesets[0];
+            assert_eq!(tileset.source.as_deref(), Some("../cells.tsj"));
+            assert_eq!(tileset.firstgid, 1);
+            assert_eq!(
+                room.map.backgroundcolor,
+                Some(color_to_string(raw_room.bg_colour().unwrap()))
+            );
+        }
+
+        let manifest = RoomManifest::new(&room_maps.rooms, "json");
+        let files: Vec<&str> = manifest.rooms.iter().map(|e| e.file.as_str()).collect();
+        assert_eq!(files, ["rooms/00_left.json", "rooms/01_right.json"]);
+    }
+}
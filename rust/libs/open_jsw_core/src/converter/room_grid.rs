@@ -0,0 +1,162 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::raw_game::JswRawRoom;
+
+/// Works out the grid position (column, row) of each room from the exits between rooms.
+///
+/// Rooms are placed by walking the exits from the room the player starts in (or the first room): a
+/// room reached through its neighbour's left exit is placed one column to the left, and so on. The
+/// original games do not have to be spatially consistent, so an exit leading to a position which is
+/// already taken is not followed. Rooms which cannot be reached this way are laid out as separate
+/// groups to the right of the rooms already placed.
+///
+/// Positions start at (0, 0), and rows increase downwards.
+pub fn room_grid(rooms: &[JswRawRoom]) -> HashMap<u8, (i32, i32)> {
+    let rooms_by_no: HashMap<u8, &JswRawRoom> =
+        rooms.iter().map(|room| (room.room_no, room)).collect();
+
+    let mut positions: HashMap<u8, (i32, i32)> = HashMap::new();
+    let mut next_col = 0;
+
+    let start_rooms = rooms.iter().filter(|room| room.player_start.is_some());
+    for room in start_rooms.chain(rooms) {
+        if positions.contains_key(&room.room_no) {
+            continue;
+        }
+
+        let group = place_connected_rooms(room, &rooms_by_no, &positions);
+
+        let min_col = group.values().map(|(col, _)| *col).min().unwrap_or(0);
+        let max_col = group.values().map(|(col, _)| *col).max().unwrap_or(0);
+        let min_row = group.values().map(|(_, row)| *row).min().unwrap_or(0);
+
+        for (room_no, (col, row)) in group {
+            positions.insert(room_no, (col - min_col + next_col, row - min_row));
+        }
+        next_col += max_col - min_col + 1;
+    }
+
+    positions
+}
+
+/// Places the rooms connected to `first_room` relative to it, skipping rooms already placed.
+fn place_connected_rooms(
+    first_room: &JswRawRoom,
+    rooms_by_no: &HashMap<u8, &JswRawRoom>,
+    placed: &HashMap<u8, (i32, i32)>,
+) -> HashMap<u8, (i32, i32)> {
+    let mut group = HashMap::from([(first_room.room_no, (0, 0))]);
+    let mut occupied = HashSet::from([(0, 0)]);
+    let mut queue = VecDeque::from([first_room]);
+
+    while let Some(room) = queue.pop_front() {
+        let (col, row) = group[&room.room_no];
+        let exits = [
+            (room.exits.left, (col - 1, row)),
+            (room.exits.right, (col + 1, row)),
+            (room.exits.up, (col, row - 1)),
+            (room.exits.down, (col, row + 1)),
+        ];
+
+        for (exit, position) in exits {
+            let Some(next_room) = exit.and_then(|room_no| rooms_by_no.get(&room_no)) else {
+                continue;
+            };
+            if group.contains_key(&next_room.room_no)
+                || placed.contains_key(&next_room.room_no)
+                || occupied.contains(&position)
+            {
+                continue;
+            }
+
+            group.insert(next_room.room_no, position);
+            occupied.insert(position);
+            queue.push_back(next_room);
+        }
+    }
+
+    group
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::raw_game::{JswRawExits, ROOM_LAYOUT_SIZE};
+
+    fn room(room_no: u8, exits: JswRawExits) -> JswRawRoom {
+        JswRawRoom {
+            room_no,
+            name: format!("Room {}", room_no),
+            layout: [0; ROOM_LAYOUT_SIZE],
+            cells: vec![],
+            border: None,
+            items: vec![],
+            entities: vec![],
+            player_start: None,
+            exits,
+        }
+    }
+
+    #[test]
+    fn rooms_are_placed_by_their_exits() {
+        let rooms = [
+            room(
+                0,
+                JswRawExits {
+                    right: Some(1),
+                    up: Some(2),
+                    ..Default::default()
+                },
+            ),
+            room(
+                1,
+                JswRawExits {
+                    left: Some(0),
+                    up: Some(3),
+                    ..Default::default()
+                },
+            ),
+            room(
+                2,
+                JswRawExits {
+                    down: Some(0),
+                    // Room 3 is already to the right, so this exit is not followed
+                    right: Some(4),
+                    ..Default::default()
+                },
+            ),
+            room(3, JswRawExits::default()),
+            room(4, JswRawExits::default()),
+        ];
+
+        let grid = room_grid(&rooms);
+
+        assert_eq!(grid[&0], (0, 1));
+        assert_eq!(grid[&1], (1, 1));
+        assert_eq!(grid[&2], (0, 0));
+        assert_eq!(grid[&3], (1, 0));
+        assert_eq!(grid[&4], (2, 0));
+    }
+
+    #[test]
+    fn unconnected_rooms_are_placed_to_the_right() {
+        let rooms = [
+            room(
+                0,
+                JswRawExits {
+                    down: Some(1),
+                    ..Default::default()
+                },
+            ),
+            room(1, JswRawExits::default()),
+            room(2, JswRawExits::default()),
+        ];
+
+        let grid = room_grid(&rooms);
+
+        assert_eq!(grid[&0], (0, 0));
+        assert_eq!(grid[&1], (0, 1));
+        assert_eq!(grid[&2], (1, 0));
+    }
+}
@@ -0,0 +1,78 @@
+use nanoserde::{DeJson, SerJson};
+
+use super::raw_to_tiled_converter::RoomMap;
+
+/// Directory of the maps of the rooms, relative to the manifest.
+pub const ROOM_MAPS_DIR: &str = "rooms";
+/// File name of the manifest written next to the directory of the maps of the rooms.
+pub const ROOM_MANIFEST_FILE_NAME: &str = "rooms.json";
+
+/// Lists the maps of a game converted to one map per room.
+#[derive(Clone, Debug, Default, PartialEq, DeJson, SerJson)]
+pub struct RoomManifest {
+    pub rooms: Vec<RoomManifestEntry>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, DeJson, SerJson)]
+pub struct RoomManifestEntry {
+    pub room_no: u8,
+    pub name: String,
+    /// Path of the map of the room, relative to the manifest.
+    pub file: String,
+}
+
+impl RoomManifest {
+    /// Creates the manifest of the maps of rooms, written as files with an extension such as
+    /// "json" or "tmx" in [`ROOM_MAPS_DIR`].
+    pub fn new(rooms: &[RoomMap], extension: &str) -> Self {
+        let rooms = rooms
+            .iter()
+            .map(|room| RoomManifestEntry {
+                room_no: room.room_no,
+                name: room.name.clone(),
+                file: format!("{}/{}", ROOM_MAPS_DIR, room.file_name(extension)),
+            })
+            .collect();
+
+        Self { rooms }
+    }
+
+    pub fn to_json(&self) -> String {
+        SerJson::serialize_json(self)
+    }
+}
+
+/// Returns the file name of the map of a room from its number and name, such as
+/// `00_central_cavern.json`.
+pub fn room_file_name(room_no: u8, name: &str, extension: &str) -> String {
+    let mut slug = String::new();
+    for c in name.trim().chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('_') {
+            slug.push('_');
+        }
+    }
+    let slug = slug.trim_end_matches('_');
+    let slug = if slug.is_empty() { "room" } else { slug };
+
+    format!("{:02}_{}.{}", room_no, slug, extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_names_have_the_room_number_and_name() {
+        assert_eq!(
+            room_file_name(0, "Central Cavern", "json"),
+            "00_central_cavern.json"
+        );
+        assert_eq!(
+            room_file_name(17, "Amoebatrons' Revenge", "tmx"),
+            "17_amoebatrons_revenge.tmx"
+        );
+        assert_eq!(room_file_name(120, "  ...  ", "json"), "120_room.json");
+    }
+}
@@ -0,0 +1,130 @@
+use open_jsw_tiled::tiled::{
+    project::Project,
+    property::{Property, PropertyVal},
+};
+
+use crate::raw_game::CellBehaviour;
+
+use super::raw_to_tiled_converter::{DIRECTION_PROPERTY_TYPE, cell_class, cell_properties};
+
+/// File name of the project written next to converted maps.
+pub const PROJECT_FILE_NAME: &str = "open-jsw.tiled-project";
+
+const CELL_BEHAVIOURS: [CellBehaviour; 14] = [
+    CellBehaviour::Air,
+    CellBehaviour::Water,
+    CellBehaviour::Earth,
+    CellBehaviour::Fire,
+    CellBehaviour::LRamp,
+    CellBehaviour::RRamp,
+    CellBehaviour::LConveyor,
+    CellBehaviour::RConveyor,
+    CellBehaviour::Crumbly,
+    CellBehaviour::Item,
+    CellBehaviour::Door,
+    CellBehaviour::Trigger,
+    CellBehaviour::Trampoline,
+    CellBehaviour::Trap,
+];
+
+/// Creates a Tiled project declaring the classes of the layers, objects and tiles of converted
+/// maps, so the editor shows their properties with default values.
+pub fn create_project() -> Project {
+    let mut project = Project::default();
+
+    project.add_enum(DIRECTION_PROPERTY_TYPE, &["left", "right", "up", "down"]);
+    let direction = || {
+        Property::new("direction", PropertyVal::String("right".to_string()))
+            .with_property_type(DIRECTION_PROPERTY_TYPE)
+    };
+    let color = || Property::new("color", PropertyVal::Color("#ffffffff".to_string()));
+    let speed = || Property::new("speed", PropertyVal::Integer(0));
+
+    // Rooms are groups of a background, dynamic and foreground layer
+    project.add_class(
+        "room",
+        &["layer"],
+        vec![
+            Property::new("bg_color", PropertyVal::Color("#ff000000".to_string())),
+            Property::new("border_color", PropertyVal::Color("#ff000000".to_string())),
+        ],
+    );
+    for class in ["bg", "dynamic", "fg"] {
+        project.add_class(class, &["layer"], vec![]);
+    }
+
+    project.add_class("item", &["object"], vec![color()]);
+    project.add_class("player", &["object"], vec![direction()]);
+    project.add_class(
+        "guardian",
+        &["object"],
+        vec![
+            speed(),
+            direction(),
+            Property::new("patrol", PropertyVal::Object(0)),
+            color(),
+        ],
+    );
+    project.add_class("patrol", &["object"], vec![]);
+    project.add_class("rope", &["object"], vec![direction()]);
+    project.add_class(
+        "arrow",
+        &["object"],
+        vec![
+            speed(),
+            direction(),
+            Property::new("delay", PropertyVal::Integer(0)),
+        ],
+    );
+
+    for behaviour in CELL_BEHAVIOURS {
+        project.add_class(cell_class(behaviour), &["tile"], cell_properties(behaviour));
+    }
+
+    project
+}
+
+#[cfg(test)]
+mod tests {
+    use open_jsw_tiled::tiled::project::PropertyTypeKind;
+
+    use super::*;
+
+    #[test]
+    fn cell_classes_have_their_properties_as_members() {
+        let project = create_project();
+
+        let earth = project.property_type("earth").unwrap();
+        assert_eq!(earth.typ, PropertyTypeKind::Class);
+        assert_eq!(earth.use_as.as_deref().unwrap(), ["tile"]);
+        let members = earth.members.as_ref().unwrap();
+        assert!(
+            members
+                .iter()
+                .any(|member| member.name == "solid" && member.value == PropertyVal::Boolean(true))
+        );
+        assert!(project.property_type("trap").is_some());
+    }
+
+    #[test]
+    fn direction_members_use_the_direction_enum() {
+        let project = create_project();
+
+        let guardian = project.property_type("guardian").unwrap();
+        let direction = guardian
+            .members
+            .iter()
+            .flatten()
+            .find(|member| member.name == "direction")
+            .unwrap();
+        assert_eq!(
+            direction.propertytype.as_deref(),
+            Some(DIRECTION_PROPERTY_TYPE)
+        );
+        let values = project
+            .property_type(DIRECTION_PROPERTY_TYPE)
+            .and_then(|typ| typ.values.clone())
+            .unwrap();
+        assert_eq!(values, ["left", "right", "up", "down"]);
+    }
+}
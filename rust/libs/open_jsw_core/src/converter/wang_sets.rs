@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+
+use open_jsw_tiled::tiled::tileset::{WangColor, WangSet, WangSetType, WangTile};
+
+use crate::raw_game::{CellBehaviour, JswRawRoom, ROOM_LAYOUT_SIZE, ROOM_LAYOUT_WIDTH};
+
+use super::raw_to_tiled_converter::color_to_string;
+
+/// The cells painted by the terrain brush, with the name of their Wang color.
+const TERRAINS: [(CellBehaviour, &str); 2] = [
+    (CellBehaviour::Earth, "Wall"),
+    (CellBehaviour::Water, "Floor"),
+];
+
+/// Offsets of the neighbours of a cell, in the order of the edges of a Wang ID (top, right,
+/// bottom, left).
+const EDGES: [(i32, i32); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+/// The walls and floors of a room, as painted by the terrain brush.
+pub(super) struct RoomTerrain {
+    pub wang_set: WangSet,
+    /// The local ID of the tile drawn at each position of the room which is part of the terrain,
+    /// by index in the layout
+    pub tiles: HashMap<usize, u32>,
+}
+
+/// Creates an edge Wang set for the walls and floors of a room, or `None` if the room has
+/// neither.
+///
+/// Each edge of a wall or floor has the cell's color where it touches another cell of the same
+/// kind, so walls join on all sides and floors only to the left and right, as the room draws
+/// them. The room needs a tile for each way its cells join: `edge_tile` returns the local ID of a
+/// tile which draws a tile with the connected edges (top, right, bottom, left), such as a copy of
+/// it. Cells which join nothing keep their tile, and the air tile erases (Wang ID 0).
+///
+/// `tile_ids` maps the ID of each cell of the room to the local ID of its foreground tile.
+/// See <https://doc.mapeditor.org/en/stable/manual/terrain/>.
+pub(super) fn room_terrain(
+    room: &JswRawRoom,
+    tile_ids: &HashMap<u8, u32>,
+    mut edge_tile: impl FnMut(u32, [bool; 4]) -> u32,
+) -> Option<RoomTerrain> {
+    let mut colors = Vec::new();
+    let mut wangtiles: Vec<WangTile> = Vec::new();
+    let mut tiles = HashMap::new();
+
+    for (behaviour, name) in TERRAINS {
+        let Some(cell) = room.cells.iter().find(|cell| cell.behaviour == behaviour) else {
+            continue;
+        };
+        let Some(&tileid) = tile_ids.get(&cell.id) else {
+            continue;
+        };
+
+        let color = colors.len() as u8 + 1;
+        let mut joined = false;
+        for i in 0..ROOM_LAYOUT_SIZE {
+            let (col, row) = (
+                (i % ROOM_LAYOUT_WIDTH) as i32,
+                (i / ROOM_LAYOUT_WIDTH) as i32,
+            );
+            if cell_at(room, col, row) != Some(cell.id) {
+                continue;
+            }
+            let edges = EDGES.map(|(dx, dy)| cell_at(room, col + dx, row + dy) == Some(cell.id));
+            if !edges.contains(&true) {
+                continue;
+            }
+
+            let edge_tileid = edge_tile(tileid, edges);
+            tiles.insert(i, edge_tileid);
+            joined = true;
+            if wangtiles.iter().all(|tile| tile.tileid != edge_tileid) {
+                let mut wangid = [0; 8];
+                for (edge, connected) in edges.into_iter().enumerate() {
+                    if connected {
+                        wangid[2 * edge] = color;
+                    }
+                }
+                wangtiles.push(WangTile {
+                    tileid: edge_tileid,
+                    wangid,
+                });
+            }
+        }
+
+        if joined {
+            colors.push(WangColor {
+                color: color_to_string(cell.ink),
+                name: name.to_string(),
+                probability: 1.0,
+                tile: tileid as i32,
+                ..Default::default()
+            });
+        }
+    }
+
+    let tile = colors.first()?.tile;
+
+    let air_tile = room
+        .cells
+        .iter()
+        .find(|cell| cell.behaviour == CellBehaviour::Air)
+        .and_then(|cell| tile_ids.get(&cell.id));
+    if let Some(&tileid) = air_tile {
+        wangtiles.push(WangTile {
+            tileid,
+            wangid: [0; 8],
+        });
+    }
+    wangtiles.sort_by_key(|tile| tile.tileid);
+
+    Some(RoomTerrain {
+        wang_set: WangSet {
+            colors,
+            name: room.name.clone(),
+            tile,
+            typ: WangSetType::Edge,
+            wangtiles,
+            ..Default::default()
+        },
+        tiles,
+    })
+}
+
+/// Merges the Wang sets which paint with the same colors and tiles, such as those of rooms built
+/// from the same cells, into the first of them, which gets the tiles of all of them.
+pub(super) fn merge_wang_sets<'a>(
+    wang_sets: impl IntoIterator<Item = &'a WangSet>,
+) -> Vec<WangSet> {
+    let mut merged: Vec<WangSet> = Vec::new();
+
+    for wang_set in wang_sets {
+        let Some(same) = merged.iter_mut().find(|other| same_colors(other, wang_set)) else {
+            merged.push(wang_set.clone());
+            continue;
+        };
+        for wangtile in &wang_set.wangtiles {
+            if same
+                .wangtiles
+                .iter()
+                .all(|tile| tile.tileid != wangtile.tileid)
+            {
+                same.wangtiles.push(wangtile.clone());
+            }
+        }
+        same.wangtiles.sort_by_key(|tile| tile.tileid);
+    }
+
+    merged
+}
+
+fn same_colors(a: &WangSet, b: &WangSet) -> bool {
+    a.colors.len() == b.colors.len()
+        && a.colors
+            .iter()
+            .zip(&b.colors)
+            .all(|(a, b)| a.name == b.name && a.color == b.color && a.tile == b.tile)
+}
+
+/// Returns the ID of the cell drawn at a position of the room (see [`JswRawRoom::cell_at`]).
+fn cell_at(room: &JswRawRoom, col: i32, row: i32) -> Option<u8> {
+    let col = usize::try_from(col).ok()?;
+    let row = usize::try_from(row).ok()?;
+
+    room.cell_at(col, row).map(|cell| cell.id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw_game::{JswRawCell, JswRawExits};
+
+    #[test]
+    fn each_way_of_joining_has_a_tile() {
+        let mut layout = [0; ROOM_LAYOUT_SIZE];
+        // A 2x2 wall and a floor 3 cells long
+        for (col, row) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            layout[row * ROOM_LAYOUT_WIDTH + col] = 2;
+        }
+        for col in 4..7 {
+            layout[10 * ROOM_LAYOUT_WIDTH + col] = 1;
+        }
+        let room = JswRawRoom {
+            room_no: 0,
+            name: "Room".to_string(),
+            layout,
+            cells: vec![
+                JswRawCell::new(0, 0, CellBehaviour::Air, [0; 8]),
+                JswRawCell::new(1, 0x04, CellBehaviour::Water, [0xff; 8]),
+                JswRawCell::new(2, 0x02, CellBehaviour::Earth, [0xaa; 8]),
+                // Not in the layout
+                JswRawCell::new(3, 0x06, CellBehaviour::Fire, [0x55; 8]),
+            ],
+            border: None,
+            items: vec![],
+            entities: vec![],
+            player_start: None,
+            exits: JswRawExits::default(),
+        };
+        let tile_ids = HashMap::from([(0, 0), (1, 4), (2, 7), (3, 9)]);
+        let mut edge_tiles = Vec::new();
+
+        let terrain = room_terrain(&room, &tile_ids, |tileid, edges| {
+            edge_tiles.push((tileid, edges));
+            10 + edge_tiles.len() as u32
+        })
+        .unwrap();
+
+        let wang_set = terrain.wang_set;
+        let names: Vec<&str> = wang_set.colors.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["Wall", "Floor"]);
+        assert_eq!(wang_set.tile, 7);
+        assert_eq!(wang_set.colors[1].tile, 4);
+        let wangtiles: Vec<(u32, [u8; 8])> = wang_set
+            .wangtiles
+            .iter()
+            .map(|tile| (tile.tileid, tile.wangid))
+            .collect();
+        assert_eq!(
+            wangtiles,
+            [
+                (0, [0; 8]),
+                // The corners of the wall
+                (11, [0, 0, 1, 0, 1, 0, 0, 0]),
+                (12, [0, 0, 0, 0, 1, 0, 1, 0]),
+                (13, [1, 0, 1, 0, 0, 0, 0, 0]),
+                (14, [1, 0, 0, 0, 0, 0, 1, 0]),
+                // The ends and middle of the floor
+                (15, [0, 0, 2, 0, 0, 0, 0, 0]),
+                (16, [0, 0, 2, 0, 0, 0, 2, 0]),
+                (17, [0, 0, 0, 0, 0, 0, 2, 0]),
+            ]
+        );
+        assert_eq!(edge_tiles[0], (7, [false, true, true, false]));
+        assert_eq!(terrain.tiles[&(10 * ROOM_LAYOUT_WIDTH + 5)], 16);
+        assert_eq!(terrain.tiles.len(), 7);
+    }
+
+    #[test]
+    fn sets_with_the_same_colors_are_merged() {
+        let wang_set = |name: &str, wangtiles: &[(u32, u8)]| WangSet {
+            colors: vec![WangColor {
+                name: "Wall".to_string(),
+                tile: 7,
+                ..Default::default()
+            }],
+            name: name.to_string(),
+            wangtiles: wangtiles
+                .iter()
+                .map(|&(tileid, color)| WangTile {
+                    tileid,
+                    wangid: [color, 0, 0, 0, color, 0, 0, 0],
+                })
+                .collect(),
+            ..Default::default()
+        };
+        let first = wang_set("First", &[(0, 0), (7, 1)]);
+        let second = wang_set("Second", &[(0, 0), (11, 1), (7, 1)]);
+        let mut other = wang_set("Other", &[(0, 0), (7, 1)]);
+        other.colors[0].tile = 11;
+
+        let merged = merge_wang_sets([&first, &second, &other]);
+
+        let names: Vec<&str> = merged.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["First", "Other"]);
+        let tile_ids: Vec<u32> = merged[0].wangtiles.iter().map(|t| t.tileid).collect();
+        assert_eq!(tile_ids, [0, 7, 11]);
+    }
+}
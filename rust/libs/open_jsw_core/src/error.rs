@@ -26,6 +26,8 @@ pub enum Error {
     // External errors
     #[error("IO::{:?}: {}", .0, .0)]
     Io(#[from] std::io::Error),
+    #[error("Tiled::{}", .0)]
+    Tiled(#[from] open_jsw_tiled::Error),
     // #[error("FlexiLogger::{:?}: {}", .0, .0)]
     // FlexiLogger(#[from] flexi_logger::FlexiLoggerError),
 }
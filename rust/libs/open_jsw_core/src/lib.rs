@@ -19,8 +19,6 @@ mod zx;
 
 #[cfg(test)]
 mod tests {
-    use super::*;
-
     #[test]
     fn it_works() {
         // let result = add(2, 2);
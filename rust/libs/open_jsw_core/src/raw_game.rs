@@ -17,6 +17,15 @@ pub const ROOM_LAYOUT_WIDTH: usize = 32;
 pub const ROOM_LAYOUT_HEIGHT: usize = 16;
 pub const ROOM_LAYOUT_SIZE: usize = ROOM_LAYOUT_WIDTH * ROOM_LAYOUT_HEIGHT;
 
+/// Number of frames before a conveyor graphic returns to its initial state.
+///
+/// The original engines rotate the conveyor rows by 2 bits every frame, so the 8 bit rows repeat
+/// after 4 frames.
+pub const CONVEYOR_FRAME_COUNT: usize = 4;
+
+/// Pixel height of a rope segment when the rope hangs straight down.
+pub const ROPE_SEGMENT_HEIGHT: u16 = 3;
+
 pub struct JswRawGame {
     pub game_type: GameType,
     pub rooms: Vec<JswRawRoom>,
@@ -27,6 +36,21 @@ pub struct JswRawRoom {
     pub name: String,
     pub layout: [u8; ROOM_LAYOUT_SIZE],
     pub cells: Vec<JswRawCell>,
+    /// The border colour, or `None` if the game doesn't store one per room.
+    pub border: Option<Color>,
+    pub items: Vec<JswRawItem>,
+    pub entities: Vec<JswRawEntity>,
+    pub player_start: Option<JswRawPlayerStart>,
+    pub exits: JswRawExits,
+}
+
+/// The rooms reached by leaving a room from each edge.
+#[derive(Copy, Clone, Default)]
+pub struct JswRawExits {
+    pub left: Option<u8>,
+    pub right: Option<u8>,
+    pub up: Option<u8>,
+    pub down: Option<u8>,
 }
 
 pub struct JswRawCell {
@@ -60,6 +84,55 @@ pub enum CellBehaviour {
     Trap,
 }
 
+/// An item to collect, positioned in cells.
+pub struct JswRawItem {
+    pub col: u8,
+    pub row: u8,
+    pub ink: Color,
+}
+
+/// A guardian, rope or arrow, positioned in pixels from the top left of the room.
+pub struct JswRawEntity {
+    pub kind: EntityKind,
+    pub x: u16,
+    pub y: u16,
+    /// Initial direction of travel (or swing, for ropes).
+    pub direction: Direction,
+    /// Pixels moved per game frame (0 for ropes).
+    pub speed: u8,
+    /// Colour of a guardian. Ropes and arrows take the colour of the cells they pass over.
+    pub ink: Option<Color>,
+    /// Minimum and maximum position of a guardian along its direction of travel, in pixels.
+    pub bounds: Option<(u16, u16)>,
+    /// Length of a rope in pixels.
+    pub length: u16,
+    /// Number of frames before an arrow enters the room.
+    pub delay: u8,
+}
+
+/// The position of Willy when the game (MM: the room) starts.
+pub struct JswRawPlayerStart {
+    pub x: u16,
+    pub y: u16,
+    pub direction: Direction,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum EntityKind {
+    HorizontalGuardian,
+    VerticalGuardian,
+    Rope,
+    Arrow,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub enum ConveyorDirection {
     Left,
@@ -103,7 +176,7 @@ impl JswRawGame {
         let mut data = ByteBuffer::from_bytes(game_bytes);
         data.set_endian(LittleEndian);
 
-        let game_type = game_data.game_type().clone();
+        let game_type = *game_data.game_type();
 
         match game_type {
             GameType::MM => RawMmGame::extract_game(game_type, &mut data),
@@ -113,6 +186,107 @@ impl JswRawGame {
     }
 }
 
+impl JswRawRoom {
+    /// Returns the paper colour of the air cell, which is the background of the room.
+    pub fn bg_colour(&self) -> Option<Color> {
+        self.cells
+            .iter()
+            .find(|cell| cell.behaviour == CellBehaviour::Air)
+            .or(self.cells.first())
+            .map(|cell| cell.paper)
+    }
+
+    /// Returns the border colour for a raw border byte (never bright).
+    pub fn border_colour(border: u8) -> Color {
+        SpeccyColour::from_raw(border & 0x07).to_rgba(false)
+    }
+
+    /// Returns the cell drawn at a position of the layout, which is the first cell if the layout
+    /// refers to a cell the room doesn't have, or `None` outside the layout.
+    pub fn cell_at(&self, col: usize, row: usize) -> Option<&JswRawCell> {
+        if col >= ROOM_LAYOUT_WIDTH || row >= ROOM_LAYOUT_HEIGHT {
+            return None;
+        }
+
+        let cell_id = self.layout[row * ROOM_LAYOUT_WIDTH + col];
+        self.cells
+            .iter()
+            .find(|cell| cell.id == cell_id)
+            .or(self.cells.first())
+    }
+
+    /// Returns the cell used to draw the items of the room.
+    pub fn item_cell(&self) -> Option<&JswRawCell> {
+        self.cells
+            .iter()
+            .find(|cell| cell.behaviour == CellBehaviour::Item)
+    }
+}
+
+impl JswRawEntity {
+    /// Creates a guardian patrolling between `min` and `max` pixels.
+    pub fn guardian(
+        kind: EntityKind,
+        (x, y): (u16, u16),
+        direction: Direction,
+        speed: u8,
+        ink: Color,
+        (min, max): (u16, u16),
+    ) -> Self {
+        Self {
+            kind,
+            x,
+            y,
+            direction,
+            speed,
+            ink: Some(ink),
+            bounds: Some((min, max)),
+            length: 0,
+            delay: 0,
+        }
+    }
+
+    /// Creates a rope hanging from the top of the room.
+    pub fn rope(x: u16, direction: Direction, segments: u8) -> Self {
+        Self {
+            kind: EntityKind::Rope,
+            x,
+            y: 0,
+            direction,
+            speed: 0,
+            ink: None,
+            bounds: None,
+            length: segments as u16 * ROPE_SEGMENT_HEIGHT,
+            delay: 0,
+        }
+    }
+
+    /// Creates an arrow from its raw column, which is off screen until the arrow enters the room.
+    ///
+    /// Arrows move one cell per frame and the column wraps at 256, so an off screen arrow is placed
+    /// at the edge it enters from, with the number of frames it takes to get there.
+    pub fn arrow(raw_col: u8, y: u16, direction: Direction) -> Self {
+        let last_col = (ROOM_LAYOUT_WIDTH - 1) as u8;
+        let (col, delay) = match direction {
+            _ if raw_col <= last_col => (raw_col, 0),
+            Direction::Left => (last_col, raw_col - last_col),
+            _ => (0, raw_col.wrapping_neg()),
+        };
+
+        Self {
+            kind: EntityKind::Arrow,
+            x: col as u16 * 8,
+            y,
+            direction,
+            speed: 8,
+            ink: None,
+            bounds: None,
+            length: 0,
+            delay,
+        }
+    }
+}
+
 impl JswRawCell {
     pub fn new(id: u8, attribute: u8, behaviour: CellBehaviour, sprite: [u8; 8]) -> Self {
         Self {
@@ -129,6 +303,45 @@ impl JswRawCell {
         }
     }
 
+    /// Returns the conveyor direction of the cell, or `None` if the cell is not a conveyor.
+    pub fn conveyor_direction(&self) -> Option<ConveyorDirection> {
+        match self.behaviour {
+            CellBehaviour::LConveyor => Some(ConveyorDirection::Left),
+            CellBehaviour::RConveyor => Some(ConveyorDirection::Right),
+            _ => None,
+        }
+    }
+
+    /// Generates the animation frames of a conveyor cell, starting with the cell sprite.
+    ///
+    /// Mirrors the original engines, which animate a conveyor by rotating the top row of the
+    /// graphic 2 bits in the direction of travel, and the third row 2 bits in the opposite
+    /// direction, once per frame.
+    ///
+    /// Returns `None` if the cell is not a conveyor.
+    pub fn conveyor_frames(&self) -> Option<Vec<[u8; 8]>> {
+        let direction = self.conveyor_direction()?;
+
+        let mut frames = Vec::with_capacity(CONVEYOR_FRAME_COUNT);
+        let mut sprite = self.sprite;
+        for _ in 0..CONVEYOR_FRAME_COUNT {
+            frames.push(sprite);
+
+            match direction {
+                ConveyorDirection::Left => {
+                    sprite[0] = sprite[0].rotate_left(2);
+                    sprite[2] = sprite[2].rotate_right(2);
+                }
+                ConveyorDirection::Right => {
+                    sprite[0] = sprite[0].rotate_right(2);
+                    sprite[2] = sprite[2].rotate_left(2);
+                }
+            }
+        }
+
+        Some(frames)
+    }
+
     fn ink(attribute: &u8) -> Color {
         SpeccyColour::from_raw(attribute & 0x07).to_rgba(Self::bright(attribute))
     }
@@ -145,3 +358,90 @@ impl JswRawCell {
         (attribute & 0x80) != 0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conveyor_frames_rotate_top_and_third_rows() {
+        let sprite = [0b1100_0000, 0xFF, 0b0000_0011, 0, 0, 0, 0, 0];
+        let cell = JswRawCell::new(5, 0x07, CellBehaviour::LConveyor, sprite);
+
+        let frames = cell.conveyor_frames().unwrap();
+
+        assert_eq!(frames.len(), CONVEYOR_FRAME_COUNT);
+        assert_eq!(frames[0], sprite);
+        assert_eq!(frames[1][0], 0b0000_0011);
+        assert_eq!(frames[1][1], 0xFF);
+        assert_eq!(frames[1][2], 0b1100_0000);
+        assert_eq!(frames[2][0], 0b0000_1100);
+        assert_eq!(frames[2][2], 0b0011_0000);
+    }
+
+    #[test]
+    fn conveyor_frames_only_for_conveyors() {
+        let cell = JswRawCell::new(2, 0x07, CellBehaviour::Earth, [0; 8]);
+
+        assert!(cell.conveyor_frames().is_none());
+    }
+
+    fn room(cells: Vec<JswRawCell>) -> JswRawRoom {
+        JswRawRoom {
+            room_no: 0,
+            name: "Room".to_string(),
+            layout: [0; ROOM_LAYOUT_SIZE],
+            cells,
+            border: None,
+            items: vec![],
+            entities: vec![],
+            player_start: None,
+            exits: JswRawExits::default(),
+        }
+    }
+
+    #[test]
+    fn bg_colour_is_the_paper_of_the_air_cell() {
+        let earth = JswRawCell::new(1, 2 << 3, CellBehaviour::Earth, [0; 8]);
+        let air = JswRawCell::new(0, 1 << 3, CellBehaviour::Air, [0; 8]);
+
+        let colour = room(vec![earth, air]).bg_colour();
+
+        assert_eq!(colour, Some(SpeccyColour::from_raw(1).to_rgba(false)));
+    }
+
+    #[test]
+    fn bg_colour_falls_back_to_the_first_cell() {
+        let earth = JswRawCell::new(1, 2 << 3, CellBehaviour::Earth, [0; 8]);
+
+        assert_eq!(
+            room(vec![earth]).bg_colour(),
+            Some(SpeccyColour::from_raw(2).to_rgba(false))
+        );
+        assert_eq!(room(vec![]).bg_colour(), None);
+    }
+
+    #[test]
+    fn border_colour_ignores_the_upper_bits() {
+        assert_eq!(
+            JswRawRoom::border_colour(0xFA),
+            SpeccyColour::from_raw(2).to_rgba(false)
+        );
+    }
+
+    #[test]
+    fn arrows_on_screen_have_no_delay() {
+        let arrow = JswRawEntity::arrow(10, 64, Direction::Left);
+
+        assert_eq!((arrow.x, arrow.y, arrow.delay), (80, 64, 0));
+    }
+
+    #[test]
+    fn arrows_off_screen_wait_at_their_entry_edge() {
+        let left = JswRawEntity::arrow(40, 64, Direction::Left);
+        let right = JswRawEntity::arrow(250, 64, Direction::Right);
+
+        assert_eq!((left.x, left.delay), (31 * 8, 9));
+        assert_eq!((right.x, right.delay), (0, 6));
+    }
+}
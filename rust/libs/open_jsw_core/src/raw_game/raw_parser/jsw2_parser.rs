@@ -1,23 +1,29 @@
-use std::{cell, io};
+use std::io;
 
 use bytebuffer::ByteBuffer;
-use macroquad::text;
 
 use super::RawParser;
 use crate::{
     Result,
     game::GameType,
-    raw_game::{CellBehaviour, JswRawCell, JswRawGame, JswRawRoom, ROOM_LAYOUT_SIZE},
+    raw_game::{CellBehaviour, JswRawCell, JswRawExits, JswRawGame, JswRawRoom, ROOM_LAYOUT_SIZE},
 };
 
 const RAM_OFFSET: usize = 0x5C00;
 const TEXT_COMPRESSION_TABLE_OFFSET: usize = 0xFA81 - RAM_OFFSET;
+#[allow(dead_code)]
 const ROOMS_OFFSET: usize = 0x0B000 - RAM_OFFSET;
 const ROOM_TABLE_POINTER_ADDR: usize = 0x7E69 - RAM_OFFSET;
+#[allow(dead_code)]
 const ROOM_SIZE: usize = 0x400;
 const ROOM_COUNT: u8 = 134;
+#[allow(dead_code)]
 const ROOM_NAME_LENGTH: usize = 0x20;
 
+/// Parses the rooms of JSW2: their names, cells and layouts.
+///
+/// The items, guardians, border colour and start position are stored differently from JSW and
+/// are not supported, so JSW2 rooms have none.
 pub struct RawJsw2Game {
     //
 }
@@ -27,6 +33,10 @@ impl RawParser for RawJsw2Game {
         // // Extract the word dictionary
         // let dictionary = Self::extract_dictionary(data)?;
 
+        log::warn!(
+            "JSW2 items, guardians and start positions are not supported, and are not converted"
+        );
+
         let raw_game = JswRawGame {
             game_type,
             rooms: Self::extract_rooms(data)?,
@@ -127,6 +137,13 @@ impl RawJsw2Game {
             name,
             layout,
             cells,
+            // The border colour isn't supported
+            border: None,
+            // Items, guardians and the start position aren't supported
+            items: vec![],
+            entities: vec![],
+            player_start: None,
+            exits: JswRawExits::default(),
         };
 
         Ok(room)
@@ -7,9 +7,11 @@ use crate::{
     Result,
     game::GameType,
     raw_game::{
-        CellBehaviour, ConveyorDirection, JswRawCell, JswRawGame, JswRawRoom, ROOM_LAYOUT_SIZE,
+        CellBehaviour, ConveyorDirection, Direction, EntityKind, JswRawCell, JswRawEntity,
+        JswRawExits, JswRawGame, JswRawItem, JswRawPlayerStart, JswRawRoom, ROOM_LAYOUT_SIZE,
         ROOM_LAYOUT_WIDTH, RampDirection,
     },
+    zx::colours::SpeccyColour,
 };
 
 const ADDR_OFFSET: usize = 0x8000;
@@ -25,6 +27,14 @@ const ATTRIBUTE_BUFFER_ADDRESS: u16 = 0x5E00;
 const ITEM_TABLE_OFFSET_1: usize = 0xA400 - ADDR_OFFSET;
 const ITEM_TABLE_OFFSET_2: usize = 0xA500 - ADDR_OFFSET;
 const ITEM_TABLE_LENGTH: usize = 0x100;
+const ENTITY_DEFINITIONS_OFFSET: usize = 0xA000 - ADDR_OFFSET;
+const ENTITY_DEFINITION_LENGTH: usize = 8;
+const ENTITY_SPEC_COUNT: usize = 8;
+/// Horizontal guardians move one cell every 4 frames
+const HORIZONTAL_GUARDIAN_SPEED: u8 = 2;
+/// Willy starts the game in The Bathroom, facing right
+const START_ROOM_NO: u8 = 33;
+const START_POSITION: (u16, u16) = (20 * 8, 104);
 
 pub struct RawJswGame {
     //
@@ -35,6 +45,7 @@ struct ItemTable {
     items: HashMap<u8, Vec<usize>>,
 }
 
+#[allow(dead_code)]
 struct ConveyorAndRamp {
     pub conveyor_direction: ConveyorDirection,
     pub conveyor_position: (u16, u16),
@@ -86,17 +97,39 @@ impl RawJswGame {
         let raw_name = read_string(data, ROOM_NAME_LENGTH)?;
         let name = raw_name.trim().to_string();
 
+        // Border
+        data.set_rpos(room_offset + 0xDE);
+        let border = Some(JswRawRoom::border_colour(data.read_u8()?));
+
         // Cells
         let cells = Self::extract_cells(data, room_no)?;
 
         // Layout
-        let layout = Self::extract_room_layout(data, room_no, &cells, item_table)?;
+        let layout = Self::extract_room_layout(data, room_no, &cells)?;
+
+        // Items, guardians, ropes and arrows
+        let items = Self::extract_items(room_no, &cells, item_table);
+        let entities = Self::extract_entities(data, room_no)?;
+
+        let player_start = (room_no == START_ROOM_NO).then_some(JswRawPlayerStart {
+            x: START_POSITION.0,
+            y: START_POSITION.1,
+            direction: Direction::Right,
+        });
+
+        // Exits
+        let exits = Self::extract_exits(data, room_no)?;
 
         let room = JswRawRoom {
             room_no,
             name,
             layout,
             cells,
+            border,
+            items,
+            entities,
+            player_start,
+            exits,
         };
 
         Ok(room)
@@ -106,7 +139,6 @@ impl RawJswGame {
         data: &mut ByteBuffer,
         room_no: u8,
         _cells: &[JswRawCell],
-        item_table: &ItemTable,
     ) -> Result<[u8; ROOM_LAYOUT_SIZE]> {
         // Read conveyor direction, position & length
         // let conveyor_and_ramp = Self::get_conveyor_and_ramp(data, room_no)?;
@@ -134,16 +166,114 @@ impl RawJswGame {
             }
         }
 
-        // Read the items from the item table and add them
-        if let Some(item_positions) = item_table.items.get(&room_no) {
-            for item_pos in item_positions {
-                if *item_pos < ROOM_LAYOUT_SIZE {
-                    layout[*item_pos] = ITEM_ID;
-                }
+        Ok(layout)
+    }
+
+    fn extract_exits(data: &mut ByteBuffer, room_no: u8) -> Result<JswRawExits> {
+        let room_offset = ROOMS_OFFSET + (room_no as usize * ROOM_SIZE);
+
+        // Left, right, up and down. An exit back to the same room leads nowhere.
+        data.set_rpos(room_offset + 0xE9);
+        let mut read_exit = || -> Result<Option<u8>> {
+            let exit = data.read_u8()?;
+            Ok((exit != room_no).then_some(exit))
+        };
+
+        Ok(JswRawExits {
+            left: read_exit()?,
+            right: read_exit()?,
+            up: read_exit()?,
+            down: read_exit()?,
+        })
+    }
+
+    fn extract_items(room_no: u8, cells: &[JswRawCell], item_table: &ItemTable) -> Vec<JswRawItem> {
+        let Some(item_cell) = cells.iter().find(|cell| cell.id == ITEM_ID) else {
+            return vec![];
+        };
+
+        item_table
+            .items
+            .get(&room_no)
+            .into_iter()
+            .flatten()
+            .filter(|item_pos| **item_pos < ROOM_LAYOUT_SIZE)
+            .map(|item_pos| JswRawItem {
+                col: (item_pos % ROOM_LAYOUT_WIDTH) as u8,
+                row: (item_pos / ROOM_LAYOUT_WIDTH) as u8,
+                ink: item_cell.ink,
+            })
+            .collect()
+    }
+
+    /// Extracts the guardians, ropes and arrows from the entity specifications of the room.
+    ///
+    /// Each specification is an entity definition number and a byte which replaces byte 2 of the
+    /// definition: the x-coordinate (bits 0-4) and animation frame (bits 5-7) of a guardian, the
+    /// x-coordinate of a rope, or the pixel y-coordinate x2 of an arrow.
+    fn extract_entities(data: &mut ByteBuffer, room_no: u8) -> Result<Vec<JswRawEntity>> {
+        let room_offset = ROOMS_OFFSET + (room_no as usize * ROOM_SIZE);
+
+        let mut entities = vec![];
+
+        for i in 0..ENTITY_SPEC_COUNT {
+            data.set_rpos(room_offset + 0xF0 + (i * 2));
+            let entity_no = data.read_u8()?;
+            if entity_no == 0xFF {
+                break;
             }
+            let spec = data.read_u8()?;
+
+            data.set_rpos(
+                ENTITY_DEFINITIONS_OFFSET + (entity_no & 0x7F) as usize * ENTITY_DEFINITION_LENGTH,
+            );
+            let def = data.read_bytes(ENTITY_DEFINITION_LENGTH)?;
+
+            // Bit 7 of byte 0 is set when moving (or swinging) right
+            let direction = if def[0] & 0x80 != 0 {
+                Direction::Right
+            } else {
+                Direction::Left
+            };
+            let col = (spec & 0x1F) as u16;
+            // Bits 0-2 of byte 1 are the ink, and bit 3 is bright
+            let ink = SpeccyColour::from_raw(def[1] & 0x07).to_rgba(def[1] & 0x08 != 0);
+
+            let entity = match def[0] & 0x07 {
+                1 => JswRawEntity::guardian(
+                    EntityKind::HorizontalGuardian,
+                    (col * 8, def[3] as u16 / 2),
+                    direction,
+                    HORIZONTAL_GUARDIAN_SPEED,
+                    ink,
+                    (def[6] as u16 * 8, def[7] as u16 * 8),
+                ),
+                2 => {
+                    let increment = def[4] as i8;
+                    let direction = if increment < 0 {
+                        Direction::Up
+                    } else {
+                        Direction::Down
+                    };
+
+                    JswRawEntity::guardian(
+                        EntityKind::VerticalGuardian,
+                        (col * 8, def[3] as u16 / 2),
+                        direction,
+                        increment.unsigned_abs() / 2,
+                        ink,
+                        (def[6] as u16 / 2, def[7] as u16 / 2),
+                    )
+                }
+                3 => JswRawEntity::rope(col * 8, direction, def[4]),
+                4 => JswRawEntity::arrow(def[4], spec as u16 / 2, direction),
+                _ => continue,
+            };
+
+            entities.push(entity);
         }
 
-        Ok(layout)
+        Ok(entities)
     }
 
     fn extract_cells(data: &mut ByteBuffer, room_no: u8) -> Result<Vec<JswRawCell>> {
@@ -200,7 +330,7 @@ impl RawJswGame {
             data.read_u8()?,
             data.read_u8()?,
         ];
-        let attribute = air_attribute | 0x80 | 0x07; // Bright white ink, air paper, no flash
+        let attribute = air_attribute | 0x80 | 0x07; // White ink, air paper, flashing
         let cell = JswRawCell::new(ITEM_ID, attribute, CellBehaviour::Item, sprite);
         cells.push(cell);
 
@@ -315,3 +445,37 @@ impl RawJswGame {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bytebuffer::Endian::LittleEndian;
+
+    use super::*;
+
+    #[test]
+    fn entities_are_read_from_their_definitions() {
+        let mut data = ByteBuffer::from_bytes(&vec![0; ROOMS_OFFSET + ROOM_SIZE]);
+        data.set_endian(LittleEndian);
+        // Entity 1 in column 5, entity 2 at pixel y 32, then the terminator
+        data.set_wpos(ROOMS_OFFSET + 0xF0);
+        data.write_bytes(&[1, 5, 2, 64, 0xFF]);
+        // A horizontal guardian moving right with bright magenta ink, and an arrow moving left
+        data.set_wpos(ENTITY_DEFINITIONS_OFFSET + ENTITY_DEFINITION_LENGTH);
+        data.write_bytes(&[0x81, 0x0B, 0, 64, 0, 0, 2, 10]);
+        data.write_bytes(&[0x04, 0, 0, 0, 40, 0, 0, 0]);
+
+        let entities = RawJswGame::extract_entities(&mut data, 0).unwrap();
+
+        assert_eq!(entities.len(), 2);
+        let guardian = &entities[0];
+        assert!(guardian.kind == EntityKind::HorizontalGuardian);
+        assert_eq!((guardian.x, guardian.y), (40, 32));
+        assert!(guardian.direction == Direction::Right);
+        assert_eq!(guardian.ink, Some(SpeccyColour::from_raw(3).to_rgba(true)));
+        assert_eq!(guardian.bounds, Some((16, 80)));
+        let arrow = &entities[1];
+        assert!(arrow.kind == EntityKind::Arrow);
+        assert!(arrow.direction == Direction::Left);
+        assert_eq!((arrow.x, arrow.y, arrow.delay), (31 * 8, 32, 9));
+    }
+}
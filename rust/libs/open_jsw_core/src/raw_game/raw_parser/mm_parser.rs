@@ -1,13 +1,18 @@
+use std::io;
+
 use bytebuffer::ByteBuffer;
+use macroquad::color::Color;
 
 use super::{RawParser, read_string};
 use crate::{
     Result,
     game::GameType,
     raw_game::{
-        CellBehaviour, ConveyorDirection, JswRawCell, JswRawGame, JswRawRoom, ROOM_LAYOUT_SIZE,
+        CellBehaviour, ConveyorDirection, Direction, EntityKind, JswRawCell, JswRawEntity,
+        JswRawExits, JswRawGame, JswRawItem, JswRawPlayerStart, JswRawRoom, ROOM_LAYOUT_SIZE,
         ROOM_LAYOUT_WIDTH,
     },
+    zx::colours::SpeccyColour,
 };
 
 const ADDR_OFFSET: usize = 0x8000;
@@ -18,6 +23,13 @@ const ROOM_NAME_LENGTH: usize = 0x20;
 const CELL_COUNT: usize = 8;
 const CELL_LENGTH: usize = 9;
 const ITEM_ID: u8 = 8;
+const ITEM_COUNT: usize = 5;
+const ITEM_LENGTH: usize = 5;
+const GUARDIAN_COUNT: usize = 4;
+const GUARDIAN_LENGTH: usize = 7;
+/// Vertical guardians only appear from Wacky Amoebatrons onwards
+const FIRST_VERTICAL_GUARDIAN_ROOM_NO: u8 = 8;
+const ATTRIBUTE_BUFFER_ADDRESS: u16 = 0x5C00;
 
 pub struct RawMmGame {
     //
@@ -59,17 +71,32 @@ impl RawMmGame {
         let raw_name = read_string(data, ROOM_NAME_LENGTH)?;
         let name = raw_name.trim().to_string();
 
+        // Border
+        data.set_rpos(room_offset + 0x273);
+        let border = Some(JswRawRoom::border_colour(data.read_u8()?));
+
         // Cells
         let cells = Self::extract_cells(data, room_no)?;
 
         // Layout
         let layout = Self::extract_room_layout(data, room_no, &cells)?;
 
+        // Items, guardians and Willy
+        let items = Self::extract_items(data, room_no)?;
+        let entities = Self::extract_guardians(data, room_no)?;
+        let player_start = Some(Self::extract_player_start(data, room_no)?);
+
         let room = JswRawRoom {
             room_no,
             name,
             layout,
             cells,
+            border,
+            items,
+            entities,
+            player_start,
+            // Caverns are played in sequence, and are not connected by exits
+            exits: JswRawExits::default(),
         };
 
         Ok(room)
@@ -94,33 +121,181 @@ impl RawMmGame {
             };
         }
 
-        // Read the items and add them
-        data.set_rpos(room_offset + 0x275);
-        loop {
-            // Check for item list terminator
-            let terminator = data.read_u8()?;
-            if terminator == 0x00 {
+        Ok(layout)
+    }
+
+    fn extract_items(data: &mut ByteBuffer, room_no: u8) -> Result<Vec<JswRawItem>> {
+        let room_offset = ROOMS_OFFSET + (room_no as usize * ROOM_SIZE);
+
+        let mut items = vec![];
+
+        for i in 0..ITEM_COUNT {
+            data.set_rpos(room_offset + 0x275 + (i * ITEM_LENGTH));
+
+            // An attribute of 0 is an unused item, and 0xFF terminates the list
+            let attribute = data.read_u8()?;
+            if attribute == 0xFF {
+                break;
+            }
+            if attribute == 0x00 {
                 continue;
             }
-            if terminator == 0xFF {
+
+            let Some((col, row)) = Self::read_position(data)? else {
+                log::warn!("Item out of bounds in room {}", room_no);
+                continue;
+            };
+
+            items.push(JswRawItem {
+                col,
+                row,
+                ink: Self::ink(attribute),
+            });
+        }
+
+        Ok(items)
+    }
+
+    /// Extracts the horizontal and vertical guardians of the room.
+    ///
+    /// Eugene and the Kong Beast are drawn and moved by code rather than room data, and are not
+    /// included.
+    fn extract_guardians(data: &mut ByteBuffer, room_no: u8) -> Result<Vec<JswRawEntity>> {
+        let room_offset = ROOMS_OFFSET + (room_no as usize * ROOM_SIZE);
+
+        let mut guardians = vec![];
+
+        // Horizontal guardians: attribute, attribute buffer address, screen buffer address MSB,
+        // animation frame, and the LSBs of the leftmost and rightmost attribute buffer addresses
+        for i in 0..GUARDIAN_COUNT {
+            data.set_rpos(room_offset + 0x2BE + (i * GUARDIAN_LENGTH));
+            let attribute = data.read_u8()?;
+            if attribute == 0xFF {
                 break;
             }
+            if attribute == 0x00 {
+                continue;
+            }
 
-            let raw_pos = (data.read_u16()? as usize) - 0x5c00;
-            // let row = raw_pos / ROOM_LAYOUT_WIDTH;
-            // let col = raw_pos % ROOM_LAYOUT_WIDTH;
+            let Some((col, row)) = Self::read_position(data)? else {
+                continue;
+            };
+            data.read_u8()?; // Skip the screen buffer address MSB
+            let frame = data.read_u8()?;
+            let min_col = data.read_u8()? & 0x1F;
+            let max_col = data.read_u8()? & 0x1F;
+
+            // Frames 0-3 face right and 4-7 face left, and bit 7 of the attribute slows the
+            // guardian down to move every other frame
+            let direction = if frame < 4 {
+                Direction::Right
+            } else {
+                Direction::Left
+            };
+            let speed = if attribute & 0x80 != 0 { 1 } else { 2 };
+
+            guardians.push(JswRawEntity::guardian(
+                EntityKind::HorizontalGuardian,
+                (col as u16 * 8, row as u16 * 8),
+                direction,
+                speed,
+                Self::ink(attribute),
+                (min_col as u16 * 8, max_col as u16 * 8),
+            ));
+        }
 
-            data.read_u8()?; // Skip the attribute
-            data.read_u8()?; // Skip the item terminator
+        if room_no < FIRST_VERTICAL_GUARDIAN_ROOM_NO {
+            return Ok(guardians);
+        }
 
-            if (raw_pos < ROOM_LAYOUT_SIZE) {
-                layout[raw_pos] = ITEM_ID;
-            } else {
-                println!("Item out of bounds: {}", raw_pos);
+        // Vertical guardians: attribute, animation frame, pixel y-coordinate, x-coordinate, pixel
+        // y-coordinate increment, and the minimum and maximum pixel y-coordinates
+        for i in 0..GUARDIAN_COUNT {
+            data.set_rpos(room_offset + 0x2DD + (i * GUARDIAN_LENGTH));
+            let attribute = data.read_u8()?;
+            if attribute == 0xFF {
+                break;
             }
+
+            data.read_u8()?; // Skip the animation frame
+            let y = data.read_u8()?;
+            let col = data.read_u8()?;
+            let increment = data.read_u8()? as i8;
+            let min_y = data.read_u8()?;
+            let max_y = data.read_u8()?;
+
+            let direction = if increment < 0 {
+                Direction::Up
+            } else {
+                Direction::Down
+            };
+
+            guardians.push(JswRawEntity::guardian(
+                EntityKind::VerticalGuardian,
+                (col as u16 * 8, y as u16),
+                direction,
+                increment.unsigned_abs(),
+                Self::ink(attribute),
+                (min_y as u16, max_y as u16),
+            ));
         }
 
-        Ok(layout)
+        Ok(guardians)
+    }
+
+    fn extract_player_start(data: &mut ByteBuffer, room_no: u8) -> Result<JswRawPlayerStart> {
+        let room_offset = ROOMS_OFFSET + (room_no as usize * ROOM_SIZE);
+
+        // Pixel y-coordinate x2, animation frame, direction, jumping flag and attribute buffer
+        // address
+        data.set_rpos(room_offset + 0x268);
+        let y = data.read_u8()? / 2;
+        let frame = data.read_u8()?;
+        let direction = if data.read_u8()? == 0 {
+            Direction::Right
+        } else {
+            Direction::Left
+        };
+        data.read_u8()?; // Skip the jumping flag
+        let (col, _) = Self::read_position(data)?.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Willy's start position is out of bounds in room {}",
+                    room_no
+                ),
+            )
+        })?;
+
+        // Each animation frame is 2 pixels further right
+        Ok(JswRawPlayerStart {
+            x: col as u16 * 8 + (frame & 0x03) as u16 * 2,
+            y: y as u16,
+            direction,
+        })
+    }
+
+    /// Reads an attribute buffer address, and returns the column and row it points to.
+    fn read_position(data: &mut ByteBuffer) -> Result<Option<(u8, u8)>> {
+        let address = data.read_u16()?;
+
+        let position = address
+            .checked_sub(ATTRIBUTE_BUFFER_ADDRESS)
+            .map(|pos| pos as usize)
+            .filter(|pos| *pos < ROOM_LAYOUT_SIZE)
+            .map(|pos| {
+                (
+                    (pos % ROOM_LAYOUT_WIDTH) as u8,
+                    (pos / ROOM_LAYOUT_WIDTH) as u8,
+                )
+            });
+
+        Ok(position)
+    }
+
+    /// Returns the ink colour of an attribute.
+    fn ink(attribute: u8) -> Color {
+        SpeccyColour::from_raw(attribute & 0x07).to_rgba(attribute & 0x40 != 0)
     }
 
     fn extract_cells(data: &mut ByteBuffer, room_no: u8) -> Result<Vec<JswRawCell>> {
@@ -173,7 +348,7 @@ impl RawMmGame {
             data.read_u8()?,
             data.read_u8()?,
         ];
-        let attribute = 0x87; // Bright white ink, black paper, no flash
+        let attribute = 0x87; // White ink, black paper, flashing
         let cell = JswRawCell::new(ITEM_ID, attribute, CellBehaviour::Item, sprite);
         cells.push(cell);
 
@@ -200,3 +375,95 @@ impl RawMmGame {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bytebuffer::Endian::LittleEndian;
+
+    use super::*;
+
+    /// Returns game data with an empty first room.
+    fn game_data() -> ByteBuffer {
+        let mut data = ByteBuffer::from_bytes(&vec![0; ROOMS_OFFSET + ROOM_SIZE]);
+        data.set_endian(LittleEndian);
+        data
+    }
+
+    /// Writes bytes at an offset into the first room.
+    fn write(data: &mut ByteBuffer, offset: usize, bytes: &[u8]) {
+        data.set_wpos(ROOMS_OFFSET + offset);
+        data.write_bytes(bytes);
+    }
+
+    /// Returns the little endian attribute buffer address of a cell.
+    fn address(col: u16, row: u16) -> [u8; 2] {
+        (ATTRIBUTE_BUFFER_ADDRESS + row * ROOM_LAYOUT_WIDTH as u16 + col).to_le_bytes()
+    }
+
+    #[test]
+    fn positions_are_read_from_attribute_addresses() {
+        let mut data = game_data();
+        write(&mut data, 0, &address(4, 3));
+        write(&mut data, 2, &(ATTRIBUTE_BUFFER_ADDRESS - 1).to_le_bytes());
+        write(&mut data, 4, &address(0, 16));
+        data.set_rpos(ROOMS_OFFSET);
+
+        assert_eq!(RawMmGame::read_position(&mut data).unwrap(), Some((4, 3)));
+        assert_eq!(RawMmGame::read_position(&mut data).unwrap(), None);
+        assert_eq!(RawMmGame::read_position(&mut data).unwrap(), None);
+    }
+
+    #[test]
+    fn items_stop_at_the_terminator() {
+        let mut data = game_data();
+        let [low, high] = address(4, 3);
+        write(&mut data, 0x275, &[0x46, low, high]);
+        write(&mut data, 0x275 + ITEM_LENGTH, &[0x00]);
+        write(&mut data, 0x275 + ITEM_LENGTH * 2, &[0xFF]);
+        write(&mut data, 0x275 + ITEM_LENGTH * 3, &[0x46, low, high]);
+
+        let items = RawMmGame::extract_items(&mut data, 0).unwrap();
+
+        assert_eq!(items.len(), 1);
+        assert_eq!((items[0].col, items[0].row), (4, 3));
+        assert_eq!(items[0].ink, SpeccyColour::from_raw(6).to_rgba(true));
+    }
+
+    #[test]
+    fn horizontal_guardians_patrol_between_columns() {
+        let mut data = game_data();
+        let [low, high] = address(3, 7);
+        write(&mut data, 0x2BE, &[0x85, low, high, 0x60, 5, 0xE1, 0xEC]);
+        write(&mut data, 0x2BE + GUARDIAN_LENGTH, &[0xFF]);
+
+        let guardians = RawMmGame::extract_guardians(&mut data, 0).unwrap();
+
+        assert_eq!(guardians.len(), 1);
+        let guardian = &guardians[0];
+        assert!(guardian.kind == EntityKind::HorizontalGuardian);
+        assert_eq!((guardian.x, guardian.y), (24, 56));
+        assert!(guardian.direction == Direction::Left);
+        assert_eq!(guardian.speed, 1);
+        assert_eq!(guardian.bounds, Some((8, 96)));
+    }
+
+    #[test]
+    fn player_start_includes_the_animation_frame() {
+        let mut data = game_data();
+        let [low, high] = address(5, 13);
+        write(&mut data, 0x268, &[208, 2, 1, 0, low, high]);
+
+        let start = RawMmGame::extract_player_start(&mut data, 0).unwrap();
+
+        assert_eq!((start.x, start.y), (44, 104));
+        assert!(start.direction == Direction::Left);
+    }
+
+    #[test]
+    fn player_start_out_of_bounds_is_an_error() {
+        let mut data = game_data();
+        write(&mut data, 0x268, &[208, 0, 0, 0, 0x34, 0x12]);
+
+        assert!(RawMmGame::extract_player_start(&mut data, 0).is_err());
+    }
+}
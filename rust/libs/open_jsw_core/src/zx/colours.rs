@@ -28,7 +28,7 @@ impl SpeccyColour {
         }
     }
 
-    pub fn to_rgba(&self, bright: bool) -> Color {
+    pub fn to_rgba(self, bright: bool) -> Color {
         match self {
             SpeccyColour::Black => Color::from_hex(0x000000),
             SpeccyColour::Blue => {
@@ -1,4 +1,3 @@
-use nanoserde::DeJsonErrReason;
 use thiserror::Error;
 
 pub type Result<T> = core::result::Result<T, Error>;
@@ -6,30 +5,73 @@ pub type Result<T> = core::result::Result<T, Error>;
 
 #[derive(Error, Debug)]
 pub enum Error {
-    #[error("[Line:{}, Col:{}] {:?}", .line, .col, .msg)]
+    /// A value which can't be read from JSON, at a path such as
+    /// `layers[12].layers[2].objects[4].properties[1].value`, with the type expected and the
+    /// type found if the value has the wrong type. The line and column start at 1, and are 0 if
+    /// unknown.
+    #[error("{}", de_json_message(.path, .message, *.line, *.col))]
     DeJsonErr {
-        msg: DeJsonErrReason,
+        path: String,
+        message: String,
+        expected: Option<String>,
+        found: Option<String>,
         line: usize,
         col: usize,
     },
-    #[error("{}", .layer)]
+    #[error("Layer name '{}' is used by another layer of the same group", .layer)]
     NonUniqueLayerName { layer: String },
-    #[error("{}", .texture)]
+    #[error("Layer ID {} is used by another layer", .id)]
+    NonUniqueLayerId { id: u32 },
+    #[error("Image '{}' not found", .texture)]
     TextureNotFound { texture: String },
-    #[error("{}", .layer_type)]
+    #[error("No layer of class '{}' in the room group", .layer_type)]
     LayerTypeNotFound { layer_type: String },
+    #[error("GID {} is not in any tileset", .gid)]
+    GidOutOfRange { gid: u32 },
+    #[error("{} is {}, but ID {} is already in use", .field, .next, .max)]
+    InvalidNextId { field: String, next: u32, max: u32 },
+    #[error("Invalid world pattern '{}': {}", .pattern, .message)]
+    InvalidWorldPattern { pattern: String, message: String },
+    #[error("Layer '{}' has no '{}'", .layer, .field)]
+    MissingLayerField { layer: String, field: String },
+    #[error("Unknown property type '{}'", .name)]
+    UnknownPropertyType { name: String },
+    #[error("Invalid value of property '{}': {}", .property, .message)]
+    InvalidPropertyValue { property: String, message: String },
+    #[error("Layer '{}' is not a tile layer", .layer)]
+    NotATileLayer { layer: String },
+    #[error("Tile ({}, {}) is outside layer '{}'", .x, .y, .layer)]
+    TileOutOfBounds { layer: String, x: i32, y: i32 },
+    #[error("Invalid layer data: {}", .message)]
+    InvalidLayerData { message: String },
+    #[error("Invalid TMX <{}>: {}", .element, .message)]
+    InvalidTmx { element: String, message: String },
+    #[error("Cannot build {}: {}", .what, .message)]
+    BuildFailed { what: String, message: String },
+    #[error("{}", .0)]
+    Xml(#[from] quick_xml::Error),
+    #[error("{}", .0)]
+    Io(#[from] std::io::Error),
 }
 
 impl From<nanoserde::DeJsonErr> for Error {
     fn from(error: nanoserde::DeJsonErr) -> Error {
-        Error::DeJsonErr {
-            msg: error.msg.clone(),
-            line: error.line,
-            col: error.col,
-        }
+        crate::json_path::to_error(Default::default(), error.msg, error.line + 1, error.col)
     }
 }
 
+fn de_json_message(path: &str, message: &str, line: usize, col: usize) -> String {
+    let mut text = String::new();
+    if !path.is_empty() {
+        text.push_str(&format!("{}: ", path));
+    }
+    text.push_str(message);
+    if line > 0 {
+        text.push_str(&format!(" (line {}, column {})", line, col));
+    }
+    text
+}
+
 // impl std::fmt::Display for Error {
 //     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 //         match self {
@@ -0,0 +1,419 @@
+use std::fmt;
+
+use nanoserde::{DeJson, DeJsonErr, DeJsonErrReason, DeJsonTok, SerJson};
+
+use crate::{Error, Result, tiled::json_value::JsonValue};
+
+/// Stops lenient reading of a file with too many errors to be worth reporting them all.
+const MAX_LENIENT_ERRORS: usize = 100;
+
+/// A step of a [`JsonPath`]: a field of an object, or an element of an array.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// The logical location of a value in a JSON document, such as
+/// `layers[12].layers[2].objects[4].properties[1].value`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct JsonPath(Vec<Segment>);
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, segment) in self.0.iter().enumerate() {
+            match segment {
+                Segment::Key(key) if index == 0 => write!(f, "{}", key)?,
+                Segment::Key(key) => write!(f, ".{}", key)?,
+                Segment::Index(i) => write!(f, "[{}]", i)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads a value from JSON, reporting an error with the path of the value at fault.
+pub(crate) fn from_json<T: DeJson>(data: &str) -> Result<T> {
+    T::deserialize_json(data).map_err(|error| locate(data, error))
+}
+
+/// Reads a value from JSON, skipping the values which can't be read instead of stopping at the
+/// first. Returns the value read and the errors, with their paths and positions in `data`.
+///
+/// The values at fault are removed before reading the JSON again, so the fields they were in
+/// take their default value, or, if they are required, their object is removed in turn. Fails
+/// only if `data` is not valid JSON, or the document as a whole can't be read.
+pub(crate) fn from_json_lenient<T: DeJson>(data: &str) -> Result<(T, Vec<Error>)> {
+    let mut value: JsonValue = from_json(data)?;
+    let original = tokens(data);
+
+    let mut errors = Vec::new();
+    let mut removed: Vec<JsonPath> = Vec::new();
+    loop {
+        let json = value.serialize_json();
+        let error = match T::deserialize_json(&json) {
+            Ok(read) => return Ok((read, errors)),
+            Err(error) => error,
+        };
+
+        let path = path_at(&tokens(&json), &json, &error);
+        if path.0.is_empty() || errors.len() >= MAX_LENIENT_ERRORS || !remove(&mut value, &path) {
+            return Err(errors
+                .into_iter()
+                .next()
+                .unwrap_or_else(|| to_error(path, error.msg, 0, 0)));
+        }
+
+        // Locate the error in `data`, as the paths of later array elements shift on removal
+        let original_path = removed.iter().rev().fold(path.clone(), unshift);
+        let (line, col) = original
+            .iter()
+            .find(|token| token.path == original_path)
+            .map_or((0, 0), |token| (token.line, token.col));
+        errors.push(to_error(original_path, error.msg, line, col));
+        removed.push(path);
+    }
+}
+
+/// Converts a nanoserde error into an error with the path of the value at fault in `data`.
+pub(crate) fn locate(data: &str, error: DeJsonErr) -> Error {
+    let path = path_at(&tokens(data), data, &error);
+    to_error(path, error.msg, error.line + 1, error.col)
+}
+
+/// Converts a nanoserde error reason into an error, with the expected and found types of
+/// unexpected values. `line` and `col` start at 1, or are 0 if unknown.
+pub(crate) fn to_error(path: JsonPath, msg: DeJsonErrReason, line: usize, col: usize) -> Error {
+    let (expected, found) = match &msg {
+        DeJsonErrReason::UnexpectedToken(token, expected) => {
+            (Some(expected.clone()), Some(token_type(token).to_string()))
+        }
+        DeJsonErrReason::NoSuchEnum(value) => (
+            Some("a known value".to_string()),
+            Some(format!("'{}'", value)),
+        ),
+        _ => (None, None),
+    };
+    let message = match (&expected, &found, &msg) {
+        (Some(expected), Some(found), _) => format!("expected {}, found {}", expected, found),
+        (_, _, DeJsonErrReason::MissingKey(key)) => format!("missing field '{}'", key),
+        _ => format!("{:?}", msg).trim().to_string(),
+    };
+
+    Error::DeJsonErr {
+        path: path.to_string(),
+        message,
+        expected,
+        found,
+        line,
+        col,
+    }
+}
+
+fn token_type(token: &DeJsonTok) -> &'static str {
+    match token {
+        DeJsonTok::Str | DeJsonTok::Char(_) => "string",
+        DeJsonTok::U64(_) | DeJsonTok::I64(_) => "integer",
+        DeJsonTok::F64(_) => "number",
+        DeJsonTok::Bool(_) => "boolean",
+        DeJsonTok::Null => "null",
+        DeJsonTok::CurlyOpen => "object",
+        DeJsonTok::BlockOpen => "array",
+        DeJsonTok::Eof => "end of file",
+        _ => "punctuation",
+    }
+}
+
+/// A token of a JSON document, with the path of the value it is part of.
+struct Token {
+    /// Position of the first character, from 1.
+    line: usize,
+    col: usize,
+    /// Index of the character after the token.
+    end: usize,
+    path: JsonPath,
+}
+
+/// Returns the path of the value nanoserde was reading when it failed. The position of an error
+/// is the character following the current token, which is the token after the value at fault
+/// for unknown enum values and missing fields.
+fn path_at(tokens: &[Token], data: &str, error: &DeJsonErr) -> JsonPath {
+    let (line, col) = (error.line, error.col);
+    let skipped = match error.msg {
+        DeJsonErrReason::NoSuchEnum(_) | DeJsonErrReason::MissingKey(_) => 1,
+        _ => 0,
+    };
+    let (mut current_line, mut current_col) = (0, 0);
+    let mut offset = data.chars().count();
+    for (index, c) in data.chars().enumerate() {
+        if c == '\n' {
+            current_line += 1;
+            current_col = 0;
+        } else {
+            current_col += 1;
+        }
+        if (current_line, current_col) == (line, col) {
+            offset = index;
+            break;
+        }
+    }
+
+    let read = tokens
+        .iter()
+        .take_while(|token| token.end <= offset)
+        .count();
+    read.checked_sub(1 + skipped)
+        .map(|index| tokens[index].path.clone())
+        .unwrap_or_default()
+}
+
+/// Splits a JSON document into tokens. Commas, and the ends of objects and arrays, are part of
+/// the value they end.
+fn tokens(data: &str) -> Vec<Token> {
+    enum Frame {
+        Object(Option<String>),
+        Array(usize),
+    }
+    let path_of = |stack: &[Frame]| {
+        JsonPath(
+            stack
+                .iter()
+                .filter_map(|frame| match frame {
+                    Frame::Object(key) => key.clone().map(Segment::Key),
+                    Frame::Array(index) => Some(Segment::Index(*index)),
+                })
+                .collect(),
+        )
+    };
+
+    let chars: Vec<char> = data.chars().collect();
+    let mut tokens = Vec::new();
+    let mut stack = Vec::new();
+    let mut last_value = JsonPath::default();
+    let (mut line, mut col) = (1, 0);
+    let mut index = 0;
+    while index < chars.len() {
+        let c = chars[index];
+        let (start_line, start_col) = if c == '\n' {
+            (line + 1, 0)
+        } else {
+            (line, col + 1)
+        };
+
+        let mut end = index + 1;
+        let path = match c {
+            c if c.is_whitespace() => None,
+            '{' | '[' => {
+                let path = path_of(&stack);
+                stack.push(if c == '{' {
+                    Frame::Object(None)
+                } else {
+                    Frame::Array(0)
+                });
+                Some(path)
+            }
+            '}' | ']' => {
+                stack.pop();
+                last_value = path_of(&stack);
+                Some(last_value.clone())
+            }
+            ':' => Some(path_of(&stack)),
+            ',' => {
+                match stack.last_mut() {
+                    Some(Frame::Object(key)) => *key = None,
+                    Some(Frame::Array(index)) => *index += 1,
+                    None => (),
+                }
+                Some(last_value.clone())
+            }
+            '"' => {
+                while end < chars.len() && chars[end] != '"' {
+                    end += if chars[end] == '\\' { 2 } else { 1 };
+                }
+                end = (end + 1).min(chars.len());
+                if let Some(Frame::Object(key @ None)) = stack.last_mut() {
+                    *key = Some(chars[index + 1..end - 1].iter().collect());
+                    Some(path_of(&stack))
+                } else {
+                    last_value = path_of(&stack);
+                    Some(last_value.clone())
+                }
+            }
+            _ => {
+                while end < chars.len()
+                    && !",:]}".contains(chars[end])
+                    && !chars[end].is_whitespace()
+                {
+                    end += 1;
+                }
+                last_value = path_of(&stack);
+                Some(last_value.clone())
+            }
+        };
+
+        for &c in &chars[index..end] {
+            if c == '\n' {
+                line += 1;
+                col = 0;
+            } else {
+                col += 1;
+            }
+        }
+        if let Some(path) = path {
+            tokens.push(Token {
+                line: start_line,
+                col: start_col,
+                end,
+                path,
+            });
+        }
+        index = end;
+    }
+
+    tokens
+}
+
+/// Removes the value at a path, returning whether it was found.
+fn remove(value: &mut JsonValue, path: &JsonPath) -> bool {
+    let Some((last, parents)) = path.0.split_last() else {
+        return false;
+    };
+
+    let mut parent = value;
+    for segment in parents {
+        let child = match (parent, segment) {
+            (JsonValue::Object(fields), Segment::Key(key)) => {
+                fields.iter_mut().find(|(k, _)| k == key).map(|(_, v)| v)
+            }
+            (JsonValue::Array(items), Segment::Index(index)) => items.get_mut(*index),
+            _ => None,
+        };
+        let Some(child) = child else {
+            return false;
+        };
+        parent = child;
+    }
+
+    match (parent, last) {
+        (JsonValue::Object(fields), Segment::Key(key)) => {
+            let count = fields.len();
+            fields.retain(|(k, _)| k != key);
+            fields.len() < count
+        }
+        (JsonValue::Array(items), Segment::Index(index)) if *index < items.len() => {
+            items.remove(*index);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Maps a path read after removing the value at `removed` to the path before the removal.
+fn unshift(mut path: JsonPath, removed: &JsonPath) -> JsonPath {
+    let Some((Segment::Index(removed_index), array)) = removed.0.split_last() else {
+        return path;
+    };
+    if path.0.len() > array.len() && path.0.starts_with(array) {
+        if let Segment::Index(index) = &mut path.0[array.len()] {
+            if *index >= *removed_index {
+                *index += 1;
+            }
+        }
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tiled::map::Map;
+
+    #[test]
+    fn errors_have_the_path_of_the_value() {
+        let json = r#"{"layers":[{"id":1,"name":"a","type":"group","layers":[
+            {"id":2,"name":"b","type":"objectgroup","objects":[
+                {"id":1,"properties":[{"name":"p","type":"int","value":1}]},
+                {"id":"two"}]}]}]}"#;
+
+        let error = from_json::<Map>(json).unwrap_err();
+
+        let Error::DeJsonErr {
+            path,
+            expected,
+            found,
+            line,
+            ..
+        } = &error
+        else {
+            panic!("{:?}", error);
+        };
+        assert_eq!(path, "layers[0].layers[0].objects[1].id");
+        assert_eq!(found.as_deref(), Some("string"));
+        assert!(expected.is_some());
+        assert_eq!(*line, 4);
+        assert!(
+            error
+                .to_string()
+                .starts_with("layers[0].layers[0].objects[1].id: expected")
+        );
+    }
+
+    #[test]
+    fn unknown_enum_values_are_located() {
+        let json = r#"{"tilewidth":8,"layers":[{"id":1,"type":"tilelayr"}]}"#;
+
+        let error = from_json::<Map>(json).unwrap_err();
+
+        assert!(matches!(
+            error,
+            Error::DeJsonErr { ref path, ref found, .. }
+                if path == "layers[0].type" && found.as_deref() == Some("'tilelayr'")
+        ));
+    }
+
+    #[test]
+    fn lenient_reading_collects_errors() {
+        let json = r#"{"width":"wide","layers":[
+            {"id":"one","name":"a"},
+            {"id":2,"name":"b","visible":3},
+            {"id":3,"name":"c","type":"tilelayr"}],
+            "height":4}"#;
+
+        let (map, errors) = from_json_lenient::<Map>(json).unwrap();
+
+        let paths: Vec<_> = errors
+            .iter()
+            .map(|error| match error {
+                Error::DeJsonErr {
+                    path, line, col, ..
+                } => (path.as_str(), *line, *col),
+                _ => panic!("{:?}", error),
+            })
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                ("width", 1, 2),
+                ("layers[0].id", 2, 14),
+                ("layers[1].visible", 3, 32),
+                ("layers[2].type", 4, 32),
+            ]
+        );
+        assert_eq!((map.width, map.height), (0, 4));
+        assert_eq!(map.layers.len(), 3);
+        assert_eq!(map.layers[1].name, "b");
+    }
+
+    #[test]
+    fn removed_array_elements_keep_the_original_paths() {
+        let json = r#"{"tilesets":[{"firstgid":"x"},{"firstgid":"y"}]}"#;
+
+        let path = unshift(
+            JsonPath(vec![Segment::Key("tilesets".into()), Segment::Index(0)]),
+            &JsonPath(vec![Segment::Key("tilesets".into()), Segment::Index(0)]),
+        );
+
+        assert_eq!(path.to_string(), "tilesets[1]");
+        assert!(from_json_lenient::<Map>(json).is_ok());
+    }
+}
@@ -1,21 +1,601 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
 use nanoserde::{DeJson, SerJson};
-use tiled::map::Map;
+use tiled::{
+    gid::Gid,
+    json_value::{self, JsonValue},
+    layer::LayerType,
+    map::{Map, for_each_layer},
+    object_template::ObjectTemplate,
+    project::Project,
+    tileset::Tileset,
+    world::{World, WorldMap},
+};
 
 // Import and re-export the `error` module
 pub use self::error::{Error, Result};
 mod error;
 
+mod json_path;
 pub mod tiled;
+mod tmx;
 
-// TODO: Differentiate using 'type' when deserializing, so that the types are better defined.
+/// Reads a map from JSON.
+///
+/// The layers are read leniently, as [`tiled::layer::Layer`]s with any combination of fields.
+/// [`Map::typed_layers`] converts them to the type selected by their `type`.
+///
+/// The fields of the map, its layers and its tilesets which are not modelled, such as
+/// `editorsettings`, are kept in their `unknown_fields` and written back by [`serialize_map`].
+///
+/// Errors have the path of the value at fault, such as `layers[12].objects[4].id`.
 pub fn deserialize_map(data: &str) -> Result<Map> {
-    let map: Map = DeJson::deserialize_json(data)?;
+    let map = json_path::from_json(data)?;
+
+    read_map_fields(map, data)
+}
+
+/// Reads a map from JSON as [`deserialize_map`] does, but skips the values which can't be read
+/// instead of failing, returning them as errors along with the map.
+///
+/// Fails only if the data is not valid JSON, or if the layer data can't be decoded.
+pub fn deserialize_map_lenient(data: &str) -> Result<(Map, Vec<Error>)> {
+    let (map, errors) = json_path::from_json_lenient(data)?;
+
+    Ok((read_map_fields(map, data)?, errors))
+}
+
+/// Keeps the unknown fields of a map read from `data`, and decodes its layer data.
+fn read_map_fields(mut map: Map, data: &str) -> Result<Map> {
+    let raw: JsonValue = json_path::from_json(data)?;
+    let known: JsonValue = DeJson::deserialize_json(&SerJson::serialize_json(&map))?;
+    json_value::keep_unknown_fields(&mut map, &raw, &known);
+
+    for layer in &mut map.layers {
+        layer.decode_data()?;
+    }
 
     Ok(map)
 }
 
 pub fn serialize_map(map: &Map) -> Result<String> {
-    let data = SerJson::serialize_json(map);
+    let mut map = map.clone();
+    for layer in &mut map.layers {
+        layer.encode_data(map.compressionlevel)?;
+    }
+    let mut data = SerJson::serialize_json(&map);
+
+    if json_value::has_unknown_fields(&map) {
+        let mut out: JsonValue = DeJson::deserialize_json(&data)?;
+        json_value::add_unknown_fields(&map, &mut out);
+        data = SerJson::serialize_json(&out);
+    }
+
+    Ok(data)
+}
+
+/// Reads a map from TMX (XML).
+pub fn deserialize_map_tmx(data: &str) -> Result<Map> {
+    tmx::read_map(data)
+}
+
+/// Writes a map as TMX (XML), with the layer data encoded as set by each layer.
+pub fn serialize_map_tmx(map: &Map) -> Result<String> {
+    tmx::write_map(map)
+}
+
+/// Reads a tileset from JSON (a .tsj file).
+pub fn deserialize_tileset(data: &str) -> Result<Tileset> {
+    let tileset: Tileset = json_path::from_json(data)?;
+
+    Ok(tileset)
+}
+
+/// Writes a tileset as JSON (a .tsj file). The `firstgid` is left out, as each map referring to
+/// the file sets its own.
+pub fn serialize_tileset(tileset: &Tileset) -> Result<String> {
+    let mut out: JsonValue = DeJson::deserialize_json(&SerJson::serialize_json(tileset))?;
+    out.remove_field("firstgid");
+
+    Ok(SerJson::serialize_json(&out))
+}
+
+/// Reads a tileset from TSX (XML).
+pub fn deserialize_tileset_tsx(data: &str) -> Result<Tileset> {
+    tmx::read_tileset(data)
+}
+
+/// Writes a tileset as TSX (XML).
+pub fn serialize_tileset_tsx(tileset: &Tileset) -> Result<String> {
+    tmx::write_tileset(tileset)
+}
+
+/// Reads an object template from JSON (a .tj file).
+pub fn deserialize_template(data: &str) -> Result<ObjectTemplate> {
+    let template: ObjectTemplate = json_path::from_json(data)?;
+
+    Ok(template)
+}
+
+/// Reads an object template from XML (a .tx file).
+pub fn deserialize_template_tx(data: &str) -> Result<ObjectTemplate> {
+    tmx::read_template(data)
+}
+
+/// Loads a map file (TMX if the extension is .tmx, JSON otherwise), with the external tilesets
+/// and object templates it refers to.
+pub fn load_map(path: &Path) -> Result<Map> {
+    load_map_with(path, deserialize_map)
+}
+
+/// Loads a map file as [`load_map`] does, reading JSON maps leniently (see
+/// [`deserialize_map_lenient`]). Returns the map and the errors of the values skipped.
+pub fn load_map_lenient(path: &Path) -> Result<(Map, Vec<Error>)> {
+    let mut errors = Vec::new();
+    let map = load_map_with(path, |data| {
+        let (map, skipped) = deserialize_map_lenient(data)?;
+        errors = skipped;
+        Ok(map)
+    })?;
+
+    Ok((map, errors))
+}
+
+fn load_map_with(path: &Path, deserialize_json: impl FnOnce(&str) -> Result<Map>) -> Result<Map> {
+    let data = fs::read_to_string(path)?;
+    let mut map = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("tmx") => deserialize_map_tmx(&data)?,
+        _ => deserialize_json(&data)?,
+    };
+
+    let dir = path.parent().unwrap_or(Path::new(""));
+    resolve_tilesets(&mut map, dir)?;
+    resolve_templates(&mut map, dir)?;
+
+    Ok(map)
+}
+
+/// Loads a tileset file (TSX if the extension is .tsx, JSON otherwise).
+pub fn load_tileset(path: &Path) -> Result<Tileset> {
+    let data = fs::read_to_string(path)?;
+
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("tsx") => deserialize_tileset_tsx(&data),
+        _ => deserialize_tileset(&data),
+    }
+}
+
+/// Loads an object template file (XML if the extension is .tx, JSON otherwise).
+pub fn load_template(path: &Path) -> Result<ObjectTemplate> {
+    let data = fs::read_to_string(path)?;
+
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("tx") => deserialize_template_tx(&data),
+        _ => deserialize_template(&data),
+    }
+}
+
+/// Replaces the external tilesets of a map with the tilesets loaded from their `source`, relative
+/// to `dir` (the directory of the map file).
+///
+/// The tilesets keep their `firstgid` and `source`, so they are still written as external
+/// tilesets. Their image paths are made relative to the map, as for an embedded tileset.
+pub fn resolve_tilesets(map: &mut Map, dir: &Path) -> Result<()> {
+    for tileset in &mut map.tilesets {
+        if let Some(source) = tileset.source.clone() {
+            *tileset = load_external_tileset(dir, &source, tileset.firstgid)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Merges the objects of a map which are template instances with the templates they refer to,
+/// relative to `dir` (the directory of the map file). See [`ObjectTemplate::apply`].
+///
+/// The GIDs of tile templates are mapped to the tilesets of the map, which should be resolved
+/// first. The tileset of a template is added to the map if the map does not use it yet.
+pub fn resolve_templates(map: &mut Map, dir: &Path) -> Result<()> {
+    let mut sources = Vec::new();
+    for_each_layer(&mut map.layers, &mut |layer| {
+        for object in layer.objects.iter().flatten() {
+            if let Some(source) = &object.template {
+                if !sources.contains(source) {
+                    sources.push(source.clone());
+                }
+            }
+        }
+    });
+
+    let mut templates = HashMap::new();
+    for source in sources {
+        let template = load_template(&dir.join(&source))?;
+        let gid = template_gid(map, dir, &source, &template)?;
+        templates.insert(source, (template, gid));
+    }
+
+    for_each_layer(&mut map.layers, &mut |layer| {
+        if !matches!(layer.typ, LayerType::ObjectGroup) {
+            return;
+        }
+        for object in layer.objects.iter_mut().flatten() {
+            let Some((template, gid)) = object.template.as_ref().and_then(|s| templates.get(s))
+            else {
+                continue;
+            };
+            template.apply(object);
+            if object.gid.is_none() {
+                object.gid = *gid;
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Returns the GID in a map of a tile template loaded from `source`, adding the tileset of the
+/// template to the map if needed.
+fn template_gid(
+    map: &mut Map,
+    dir: &Path,
+    source: &str,
+    template: &ObjectTemplate,
+) -> Result<Option<Gid>> {
+    let Some(tileset_source) = template.tileset.as_ref().and_then(|t| t.source.as_ref()) else {
+        return Ok(template.map_gid(1));
+    };
+    if template.object.gid.is_none() {
+        return Ok(None);
+    }
+
+    let template_dir = Path::new(source).parent().unwrap_or(Path::new(""));
+    let tileset_source = relative_path(template_dir, tileset_source);
+    let used = map.tilesets.iter().find(|tileset| {
+        tileset
+            .source
+            .as_deref()
+            .is_some_and(|s| relative_path(Path::new(""), s) == tileset_source)
+    });
+
+    let firstgid = match used {
+        Some(tileset) => tileset.firstgid,
+        None => {
+            let firstgid = map
+                .tilesets
+                .iter()
+                .map(|tileset| tileset.firstgid + tileset.tilecount)
+                .max()
+                .unwrap_or(1);
+            map.tilesets
+                .push(load_external_tileset(dir, &tileset_source, firstgid)?);
+            firstgid
+        }
+    };
+
+    Ok(template.map_gid(firstgid))
+}
+
+/// Loads an external tileset from `source`, relative to `dir`, with its image paths made relative
+/// to `dir`.
+fn load_external_tileset(dir: &Path, source: &str, firstgid: u32) -> Result<Tileset> {
+    let mut tileset = load_tileset(&dir.join(source))?;
+    let tileset_dir = Path::new(source).parent().unwrap_or(Path::new(""));
+    if !tileset.image.is_empty() {
+        tileset.image = relative_path(tileset_dir, &tileset.image);
+    }
+    for tile in tileset.tiles.iter_mut().flatten() {
+        if let Some(image) = &tile.image {
+            tile.image = Some(relative_path(tileset_dir, image));
+        }
+    }
+    tileset.firstgid = firstgid;
+    tileset.source = Some(source.to_string());
+
+    Ok(tileset)
+}
+
+/// Joins a path relative to a file to the directory of the file, using '/' as Tiled does, and
+/// removes the `.` components and the `..` components following a directory.
+fn relative_path(dir: &Path, path: &str) -> String {
+    let joined = dir.join(path).to_string_lossy().replace('\\', "/");
+
+    let mut parts: Vec<&str> = Vec::new();
+    for part in joined.split('/') {
+        match part {
+            "." => (),
+            ".." if parts.last().is_some_and(|last| !matches!(*last, ".." | "")) => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+
+    parts.join("/")
+}
+
+pub fn deserialize_world(data: &str) -> Result<World> {
+    let world: World = json_path::from_json(data)?;
+
+    Ok(world)
+}
+
+pub fn serialize_world(world: &World) -> Result<String> {
+    let data = SerJson::serialize_json(world);
 
     Ok(data)
 }
+
+/// A world loaded with its maps.
+pub struct LoadedWorld {
+    pub world: World,
+    pub maps: Vec<LoadedWorldMap>,
+}
+
+/// A map of a loaded world, positioned (and sized, if the world does not set the size) in pixels.
+pub struct LoadedWorldMap {
+    pub entry: WorldMap,
+    pub path: PathBuf,
+    pub map: Map,
+}
+
+/// Loads a world file, and the maps it lists or matches with its patterns.
+pub fn load_world(path: &Path) -> Result<LoadedWorld> {
+    let world = deserialize_world(&fs::read_to_string(path)?)?;
+    // The directory is listed to match the patterns, so it can't be empty
+    let dir = parent_dir(path);
+
+    let mut maps = Vec::new();
+    for mut entry in world.resolve_maps(dir)? {
+        let map_path = dir.join(&entry.file_name);
+        let map = load_map(&map_path)?;
+
+        entry.width.get_or_insert(map.width * map.tilewidth);
+        entry.height.get_or_insert(map.height * map.tileheight);
+
+        maps.push(LoadedWorldMap {
+            entry,
+            path: map_path,
+            map,
+        });
+    }
+
+    Ok(LoadedWorld { world, maps })
+}
+
+/// Reads a project from JSON (a .tiled-project file).
+pub fn deserialize_project(data: &str) -> Result<Project> {
+    let project: Project = json_path::from_json(data)?;
+
+    Ok(project)
+}
+
+/// Writes a project as JSON (a .tiled-project file).
+pub fn serialize_project(project: &Project) -> Result<String> {
+    let data = SerJson::serialize_json(project);
+
+    Ok(data)
+}
+
+/// Loads a project file.
+pub fn load_project(path: &Path) -> Result<Project> {
+    deserialize_project(&fs::read_to_string(path)?)
+}
+
+/// Returns the directory of a file, which is `.` for a bare file name.
+fn parent_dir(path: &Path) -> &Path {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+    use crate::tiled::builder::TilesetBuilder;
+
+    /// A temporary directory unique to a test run, removed when dropped (even if the test fails).
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+            let dir = std::env::temp_dir().join(format!(
+                "open_jsw_tiled_{}_{}_{}",
+                name,
+                std::process::id(),
+                COUNT.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn bare_file_names_are_in_the_current_directory() {
+        assert_eq!(parent_dir(Path::new("jsw.world")), Path::new("."));
+        assert_eq!(parent_dir(Path::new("maps/jsw.world")), Path::new("maps"));
+        assert_eq!(parent_dir(Path::new("/jsw.world")), Path::new("/"));
+    }
+
+    #[test]
+    fn external_tilesets_are_resolved_relative_to_the_map() {
+        let temp_dir = TempDir::new("external_tilesets");
+        let dir = &temp_dir.0;
+        fs::create_dir_all(dir.join("maps")).unwrap();
+
+        let mut tileset = TilesetBuilder::new("cells", 8, 8)
+            .image("gfx/cells.png", 64, 8)
+            .build()
+            .unwrap();
+        tileset.firstgid = 1;
+        let data = serialize_tileset(&tileset).unwrap();
+        assert!(!data.contains("firstgid"));
+        fs::write(dir.join("cells.tsj"), data).unwrap();
+
+        let mut map = Map::default();
+        map.tilesets.push(Tileset::external(5, "../cells.tsj"));
+        let map_path = dir.join("maps").join("map.json");
+        fs::write(&map_path, serialize_map(&map).unwrap()).unwrap();
+
+        let map = load_map(&map_path).unwrap();
+
+        let tileset = &map.tilesets[0];
+        assert_eq!(tileset.firstgid, 5);
+        assert_eq!(tileset.name, "cells");
+        assert_eq!(tileset.tilecount, 8);
+        assert_eq!(tileset.image, "../gfx/cells.png");
+        assert_eq!(tileset.source.as_deref(), Some("../cells.tsj"));
+
+        // Still written as a reference to the file
+        let json = serialize_map(&map).unwrap();
+        assert!(json.contains(r#""tilesets":[{"firstgid":5,"source":"../cells.tsj"}]"#));
+    }
+
+    #[test]
+    fn templates_are_merged_into_their_instances() {
+        let temp_dir = TempDir::new("templates");
+        let dir = &temp_dir.0;
+        fs::create_dir_all(dir.join("templates")).unwrap();
+
+        for (name, tilecount) in [("cells", 8), ("items", 4)] {
+            let tileset = TilesetBuilder::new(name, 8, 8)
+                .image(&format!("{}.png", name), tilecount * 8, 8)
+                .build()
+                .unwrap();
+            let path = dir.join(format!("{}.tsj", name));
+            fs::write(path, serialize_tileset(&tileset).unwrap()).unwrap();
+        }
+        fs::write(
+            dir.join("templates/monk.tj"),
+            r#"{"type":"template","tileset":{"firstgid":1,"source":"../cells.tsj"},
+            "object":{"gid":3,"width":16,"height":16,"id":1,"name":"Monk","type":"guardian",
+            "visible":true,"properties":[{"name":"speed","type":"int","value":2},
+            {"name":"colour","type":"string","value":"yellow"}]}}"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("templates/key.tx"),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<template>
+ <tileset firstgid="1" source="../items.tsj"/>
+ <object name="Key" type="item" gid="2" width="8" height="8"/>
+</template>
+"#,
+        )
+        .unwrap();
+        let map = r#"{"tilesets":[{"firstgid":5,"source":"cells.tsj"}],"layers":[
+            {"id":1,"name":"Objects","type":"objectgroup","objects":[
+            {"id":1,"template":"templates/monk.tj","x":8,"y":16,
+            "properties":[{"name":"speed","type":"int","value":4}]},
+            {"id":2,"template":"templates/key.tx","name":"Gold key","x":0,"y":0}]}]}"#;
+        let map_path = dir.join("map.json");
+        fs::write(&map_path, map).unwrap();
+
+        let map = load_map(&map_path).unwrap();
+
+        let objects = map.layers[0].objects.as_ref().unwrap();
+        let monk = &objects[0];
+        assert_eq!(monk.name, "Monk");
+        assert_eq!(monk.class.as_deref(), Some("guardian"));
+        assert_eq!((monk.x, monk.width), (8.0, 16.0));
+        assert!(monk.visible);
+        assert_eq!(monk.gid, Some(Gid(7)));
+        let properties = monk.properties.as_ref().unwrap();
+        assert_eq!(properties.len(), 2);
+        assert_eq!(
+            properties[0].value,
+            tiled::property::PropertyVal::Integer(4)
+        );
+
+        // The tileset of the key template is added to the map
+        let key = &objects[1];
+        assert_eq!(key.name, "Gold key");
+        assert_eq!(map.tilesets[1].source.as_deref(), Some("items.tsj"));
+        assert_eq!(map.tilesets[1].firstgid, 13);
+        assert_eq!(key.gid, Some(Gid(14)));
+    }
+
+    #[test]
+    fn template_gids_keep_their_flip_flags() {
+        let mut template = ObjectTemplate::default();
+        template.object.gid = Some(Gid::new(3, Gid::FLIPPED_HORIZONTALLY));
+
+        assert_eq!(
+            template.map_gid(5),
+            Some(Gid::new(7, Gid::FLIPPED_HORIZONTALLY))
+        );
+    }
+
+    #[test]
+    fn compressed_chunks_round_trip() {
+        let json = r#"{"infinite":true,"layers":[{"id":1,"name":"Tiles","type":"tilelayer",
+            "encoding":"base64","compression":"zlib","chunks":[{"x":16,"y":0,"width":4,"height":2,
+            "data":"eJxjZGBgYAJiZiBmAWJWIGYDYnYg5gBiAAIAACU="}]}]}"#;
+
+        let map = deserialize_map(json).unwrap();
+        let chunks = map.layers[0].chunks.as_ref().unwrap();
+        assert_eq!(*chunks[0].data, [1, 2, 3, 4, 5, 6, 7, 8]);
+
+        let serialized = serialize_map(&map).unwrap();
+        assert!(!serialized.contains("[1,2,3,4,5,6,7,8]"));
+        let reloaded = deserialize_map(&serialized).unwrap();
+        assert_eq!(
+            reloaded.layers[0].chunks.as_ref().unwrap()[0].data,
+            chunks[0].data
+        );
+    }
+
+    #[test]
+    fn unknown_fields_round_trip() {
+        let json = r#"{"editorsettings":{"export":{"format":"json","target":"out.json"}},
+            "layers":[{"id":1,"name":"Room","type":"group","layers":[
+            {"id":2,"name":"Tiles","type":"tilelayer","width":1,"height":1,"data":[1],
+            "future":[1.5,null]}]}],
+            "tilesets":[{"firstgid":1,"name":"cells","image":"cells.png","tilerendersize":"grid"}]}"#;
+
+        let map = deserialize_map(json).unwrap();
+        assert_eq!(map.unknown_fields.0[0].0, "editorsettings");
+        assert!(map.layers[0].unknown_fields.is_empty());
+
+        let serialized = serialize_map(&map).unwrap();
+        assert!(
+            serialized
+                .contains(r#""editorsettings":{"export":{"format":"json","target":"out.json"}}"#)
+        );
+        assert!(serialized.contains(r#""future":[1.5,null]"#));
+        assert!(serialized.contains(r#""tilerendersize":"grid""#));
+        let reloaded = deserialize_map(&serialized).unwrap();
+        assert_eq!(reloaded.unknown_fields, map.unknown_fields);
+        let tiles = &reloaded.layers[0].layers.as_ref().unwrap()[0];
+        assert_eq!(tiles.unknown_fields.0.len(), 1);
+        assert_eq!(tiles.tile(0, 0), Some(1));
+    }
+
+    #[test]
+    fn checked_in_map_keeps_its_editor_settings() {
+        let map = deserialize_map(include_str!("../../../../tiled/open_jsw.json")).unwrap();
+
+        let serialized = serialize_map(&map).unwrap();
+
+        let editor_settings =
+            r#""editorsettings":{"export":{"format":"json","target":"out.json"}}"#;
+        assert!(serialized.contains(editor_settings));
+    }
+}
@@ -1,17 +1,22 @@
 pub mod axis;
+pub mod builder;
 pub mod chunk;
+pub mod gid;
+pub mod json_value;
 pub mod layer;
 pub mod layer_data;
 pub mod map;
 pub mod object;
 pub mod object_template;
 pub mod point;
+pub mod project;
 pub mod property;
 pub mod text;
 pub mod tileset;
+pub mod typed_layer;
+pub mod validation;
+pub mod world;
 
 pub const TILED_VERSION: &str = "1.11.2";
 pub const MAP_VERSION: &str = "1.10";
 pub const TILE_VERSION: &str = "1.10";
-
-mod validation;
@@ -0,0 +1,665 @@
+use std::collections::HashSet;
+
+use super::{
+    TILE_VERSION, TILED_VERSION,
+    gid::Gid,
+    layer::{Layer, LayerType},
+    map::{Map, MapOrientation},
+    object::Object,
+    point::Point,
+    property::Property,
+    tileset::{Tile, Tileset, WangSet},
+    validation,
+};
+use crate::{Error, Result};
+
+/// Builds a map, assigning the IDs of its layers and objects and the first GIDs of its tilesets.
+///
+/// Layers and objects are created by the map builder, which gives them their ID, and added to
+/// the map once built:
+///
+/// ```
+/// # use open_jsw_tiled::tiled::builder::{LayerBuilder, MapBuilder};
+/// let mut map = MapBuilder::new(32, 16, 8, 8);
+/// let tiles = map.tile_layer("Tiles").class("bg").build()?;
+/// let room = map.group("Room").layer(tiles).build()?;
+/// map.add_layer(room);
+/// let map = map.build()?;
+/// # Ok::<(), open_jsw_tiled::Error>(())
+/// ```
+pub struct MapBuilder {
+    map: Map,
+    next_firstgid: u32,
+}
+
+/// The settings shared by the builders of all the types of layer.
+pub trait LayerBuilder: Sized {
+    fn layer_mut(&mut self) -> &mut Layer;
+
+    fn class(mut self, class: &str) -> Self {
+        self.layer_mut().class = Some(class.to_string());
+        self
+    }
+
+    /// Sets whether the layer is shown (layers are visible by default).
+    fn visible(mut self, visible: bool) -> Self {
+        self.layer_mut().visible = visible;
+        self
+    }
+
+    fn opacity(mut self, opacity: f64) -> Self {
+        self.layer_mut().opacity = opacity;
+        self
+    }
+
+    /// Sets the offset of the layer in pixels.
+    fn offset(mut self, x: f64, y: f64) -> Self {
+        self.layer_mut().offsetx = x;
+        self.layer_mut().offsety = y;
+        self
+    }
+
+    fn property(mut self, property: Property) -> Self {
+        self.layer_mut().properties.push(property);
+        self
+    }
+}
+
+/// Builds a tile layer, sized as the map unless set otherwise.
+pub struct TileLayerBuilder {
+    layer: Layer,
+    error: Option<Error>,
+}
+
+/// Builds a layer of objects.
+pub struct ObjectGroupBuilder {
+    layer: Layer,
+}
+
+/// Builds a group of layers.
+pub struct GroupBuilder {
+    layer: Layer,
+}
+
+/// Builds an object, visible and at (0, 0) unless set otherwise.
+pub struct ObjectBuilder {
+    object: Object,
+}
+
+/// Builds a tileset from an image, computing its `columns` and `tilecount`, or a collection of
+/// images from its tiles.
+pub struct TilesetBuilder {
+    tileset: Tileset,
+}
+
+impl MapBuilder {
+    /// Creates an orthogonal map of `width` x `height` tiles of `tilewidth` x `tileheight`
+    /// pixels.
+    pub fn new(width: u32, height: u32, tilewidth: u32, tileheight: u32) -> Self {
+        Self {
+            map: Map::new(
+                None,
+                MapOrientation::Orthogonal,
+                width,
+                height,
+                tilewidth,
+                tileheight,
+            ),
+            next_firstgid: 1,
+        }
+    }
+
+    pub fn class(&mut self, class: &str) -> &mut Self {
+        self.map.class = Some(class.to_string());
+        self
+    }
+
+    pub fn orientation(&mut self, orientation: MapOrientation) -> &mut Self {
+        self.map.orientation = orientation;
+        self
+    }
+
+    /// Sets whether the map is infinite, which the tile layers created afterwards store as
+    /// chunks.
+    pub fn infinite(&mut self, infinite: bool) -> &mut Self {
+        self.map.infinite = infinite;
+        self
+    }
+
+    /// Sets the size of the map in tiles. Tile layers created afterwards have the new size.
+    pub fn size(&mut self, width: u32, height: u32) -> &mut Self {
+        self.map.width = width;
+        self.map.height = height;
+        self
+    }
+
+    pub fn background_color(&mut self, color: Option<String>) -> &mut Self {
+        self.map.backgroundcolor = color;
+        self
+    }
+
+    pub fn property(&mut self, property: Property) -> &mut Self {
+        self.map
+            .properties
+            .get_or_insert_with(Vec::new)
+            .push(property);
+        self
+    }
+
+    /// Adds a tileset after the tilesets already added, returning its first GID.
+    pub fn add_tileset(&mut self, mut tileset: Tileset) -> u32 {
+        let firstgid = self.next_firstgid;
+        tileset.firstgid = firstgid;
+        self.next_firstgid += tileset.tilecount.max(1);
+        self.map.tilesets.push(tileset);
+        firstgid
+    }
+
+    /// Adds a reference to a tileset stored in `source`, relative to the map, returning its first
+    /// GID. The GIDs of the tiles of `tileset`, as stored in the file, are reserved for it.
+    pub fn add_external_tileset(&mut self, source: &str, tileset: &Tileset) -> u32 {
+        let firstgid = self.add_tileset(Tileset::external(0, source));
+        self.next_firstgid = firstgid + tileset.tilecount.max(1);
+        firstgid
+    }
+
+    pub fn tile_layer(&mut self, name: &str) -> TileLayerBuilder {
+        TileLayerBuilder {
+            layer: self.new_layer(LayerType::TileLayer, name),
+            error: None,
+        }
+    }
+
+    pub fn object_group(&mut self, name: &str) -> ObjectGroupBuilder {
+        ObjectGroupBuilder {
+            layer: self.new_layer(LayerType::ObjectGroup, name),
+        }
+    }
+
+    pub fn group(&mut self, name: &str) -> GroupBuilder {
+        GroupBuilder {
+            layer: self.new_layer(LayerType::Group, name),
+        }
+    }
+
+    pub fn object(&mut self, name: &str) -> ObjectBuilder {
+        let mut object = Object::new(&mut self.map, name.to_string(), 0.0, 0.0, 0.0, 0.0);
+        object.visible = true;
+        ObjectBuilder { object }
+    }
+
+    /// Adds a layer above the layers already added.
+    pub fn add_layer(&mut self, layer: Layer) -> &mut Self {
+        self.map.layers.push(layer);
+        self
+    }
+
+    /// Returns the map, or an error if it is not valid (see [`validation::validate`]). The map
+    /// is taken out of the builder, so the settings can be chained before building it.
+    pub fn build(&mut self) -> Result<Map> {
+        let map = std::mem::take(&mut self.map);
+        let diagnostics = validation::validate(&map);
+        if !diagnostics.is_empty() {
+            let problems: Vec<String> = diagnostics.iter().map(|d| d.to_string()).collect();
+            return Err(build_failed("map", &problems.join("; ")));
+        }
+
+        Ok(map)
+    }
+
+    fn new_layer(&mut self, typ: LayerType, name: &str) -> Layer {
+        let mut layer = Layer::new(&mut self.map, typ, name.to_string());
+        layer.visible = true;
+        layer
+    }
+}
+
+impl LayerBuilder for TileLayerBuilder {
+    fn layer_mut(&mut self) -> &mut Layer {
+        &mut self.layer
+    }
+}
+
+impl LayerBuilder for ObjectGroupBuilder {
+    fn layer_mut(&mut self) -> &mut Layer {
+        &mut self.layer
+    }
+}
+
+impl LayerBuilder for GroupBuilder {
+    fn layer_mut(&mut self) -> &mut Layer {
+        &mut self.layer
+    }
+}
+
+impl TileLayerBuilder {
+    /// Sets the size of a fixed-size layer in tiles, clearing its tiles.
+    pub fn size(mut self, width: u32, height: u32) -> Self {
+        if self.layer.data.is_some() {
+            self.layer.width = Some(width);
+            self.layer.height = Some(height);
+            self.layer.data = Some(vec![0; (width * height) as usize].into());
+        }
+        self
+    }
+
+    /// Sets the GIDs of a fixed-size layer, row by row.
+    pub fn data(mut self, data: Vec<u32>) -> Self {
+        if self.layer.data.is_none() {
+            self.error.get_or_insert(build_failed(
+                &self.layer.name,
+                "an infinite map has no fixed-size data",
+            ));
+        }
+        self.layer.data = Some(data.into());
+        self
+    }
+
+    /// Sets the GID at `(x, y)` in tiles, as [`Layer::set_tile`] does.
+    pub fn tile(mut self, x: i32, y: i32, gid: Gid) -> Self {
+        if let Err(error) = self.layer.set_tile(x, y, gid.0) {
+            self.error.get_or_insert(error);
+        }
+        self
+    }
+
+    /// Returns the layer, or an error if a tile was set outside it, or if its data doesn't match
+    /// its size.
+    pub fn build(self) -> Result<Layer> {
+        if let Some(error) = self.error {
+            return Err(error);
+        }
+
+        if let Some(data) = &self.layer.data {
+            let size = self.layer.width.unwrap_or(0) * self.layer.height.unwrap_or(0);
+            if data.len() != size as usize {
+                let message = format!("{} tiles for an area of {} tiles", data.len(), size);
+                return Err(build_failed(&self.layer.name, &message));
+            }
+        }
+
+        Ok(self.layer)
+    }
+}
+
+impl ObjectGroupBuilder {
+    pub fn object(mut self, object: Object) -> Self {
+        self.layer.objects.get_or_insert_with(Vec::new).push(object);
+        self
+    }
+
+    pub fn objects(mut self, objects: impl IntoIterator<Item = Object>) -> Self {
+        self.layer
+            .objects
+            .get_or_insert_with(Vec::new)
+            .extend(objects);
+        self
+    }
+
+    /// Returns the layer, or an error if two of its objects have the same ID.
+    pub fn build(mut self) -> Result<Layer> {
+        let objects = self.layer.objects.get_or_insert_with(Vec::new);
+        let mut ids = HashSet::new();
+        if let Some(object) = objects.iter().find(|object| !ids.insert(object.id)) {
+            let message = format!("object ID {} is used by another object", object.id);
+            return Err(build_failed(&self.layer.name, &message));
+        }
+
+        Ok(self.layer)
+    }
+}
+
+impl GroupBuilder {
+    /// Adds a layer above the layers already added.
+    pub fn layer(mut self, layer: Layer) -> Self {
+        self.layer.layers.get_or_insert_with(Vec::new).push(layer);
+        self
+    }
+
+    /// Returns the group, or an error if two of its layers have the same name.
+    pub fn build(mut self) -> Result<Layer> {
+        let layers = self.layer.layers.get_or_insert_with(Vec::new);
+        let mut names = HashSet::new();
+        if let Some(layer) = layers.iter().find(|layer| !names.insert(&layer.name)) {
+            return Err(Error::NonUniqueLayerName {
+                layer: layer.name.clone(),
+            });
+        }
+
+        Ok(self.layer)
+    }
+}
+
+impl ObjectBuilder {
+    /// The ID of the object, to refer to it from the properties of other objects.
+    pub fn id(&self) -> u32 {
+        self.object.id
+    }
+
+    pub fn class(mut self, class: &str) -> Self {
+        self.object.class = Some(class.to_string());
+        self
+    }
+
+    /// Sets the position of the object in pixels. Tile objects are positioned by their
+    /// bottom-left corner.
+    pub fn position(mut self, x: f64, y: f64) -> Self {
+        self.object.x = x;
+        self.object.y = y;
+        self
+    }
+
+    pub fn size(mut self, width: f64, height: f64) -> Self {
+        self.object.width = width;
+        self.object.height = height;
+        self
+    }
+
+    /// Makes the object a tile object, drawn with the tile of a GID.
+    pub fn gid(mut self, gid: Gid) -> Self {
+        self.object.gid = Some(gid);
+        self
+    }
+
+    /// Makes the object a polyline, with points relative to its position.
+    pub fn polyline(mut self, points: Vec<Point>) -> Self {
+        self.object.polyline = Some(points);
+        self
+    }
+
+    /// Makes the object a polygon, with points relative to its position.
+    pub fn polygon(mut self, points: Vec<Point>) -> Self {
+        self.object.polygon = Some(points);
+        self
+    }
+
+    pub fn visible(mut self, visible: bool) -> Self {
+        self.object.visible = visible;
+        self
+    }
+
+    pub fn property(mut self, property: Property) -> Self {
+        self.object
+            .properties
+            .get_or_insert_with(Vec::new)
+            .push(property);
+        self
+    }
+
+    /// Returns the object, or an error if its size is negative, if a polyline has fewer than 2
+    /// points or a polygon fewer than 3, or if it is empty tile object.
+    pub fn build(self) -> Result<Object> {
+        let object = &self.object;
+        let what = format!("object {}", object.id);
+        if object.width < 0.0 || object.height < 0.0 {
+            return Err(build_failed(&what, "the size is negative"));
+        }
+        if object
+            .polyline
+            .as_ref()
+            .is_some_and(|points| points.len() < 2)
+        {
+            return Err(build_failed(&what, "a polyline needs at least 2 points"));
+        }
+        if object
+            .polygon
+            .as_ref()
+            .is_some_and(|points| points.len() < 3)
+        {
+            return Err(build_failed(&what, "a polygon needs at least 3 points"));
+        }
+        if object.gid.is_some_and(Gid::is_empty) {
+            return Err(build_failed(&what, "a tile object needs a tile"));
+        }
+
+        Ok(self.object)
+    }
+}
+
+impl TilesetBuilder {
+    /// Creates a tileset of tiles of `tilewidth` x `tileheight` pixels.
+    pub fn new(name: &str, tilewidth: u32, tileheight: u32) -> Self {
+        Self {
+            tileset: Tileset {
+                version: TILE_VERSION.to_string(),
+                tiledversion: TILED_VERSION.to_string(),
+                name: name.to_string(),
+                tilewidth,
+                tileheight,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Sets the image the tiles are cut from, of `width` x `height` pixels.
+    pub fn image(mut self, image: &str, width: u32, height: u32) -> Self {
+        self.tileset.image = image.to_string();
+        self.tileset.imagewidth = width;
+        self.tileset.imageheight = height;
+        self
+    }
+
+    /// Sets the margin around the tiles, and the spacing between them, in pixels.
+    pub fn margin_and_spacing(mut self, margin: u32, spacing: u32) -> Self {
+        self.tileset.margin = margin;
+        self.tileset.spacing = spacing;
+        self
+    }
+
+    pub fn class(mut self, class: &str) -> Self {
+        self.tileset.class = Some(class.to_string());
+        self
+    }
+
+    pub fn property(mut self, property: Property) -> Self {
+        self.tileset
+            .properties
+            .get_or_insert_with(Vec::new)
+            .push(property);
+        self
+    }
+
+    /// Adds the data of a tile, such as its animation, class or image (for a collection of
+    /// images).
+    pub fn tile(mut self, tile: Tile) -> Self {
+        self.tileset.tiles.get_or_insert_with(Vec::new).push(tile);
+        self
+    }
+
+    pub fn tiles(mut self, tiles: impl IntoIterator<Item = Tile>) -> Self {
+        self.tileset
+            .tiles
+            .get_or_insert_with(Vec::new)
+            .extend(tiles);
+        self
+    }
+
+    /// Adds a Wang set, used by the terrain brush of the editor.
+    pub fn wang_set(mut self, wang_set: WangSet) -> Self {
+        self.tileset
+            .wangsets
+            .get_or_insert_with(Vec::new)
+            .push(wang_set);
+        self
+    }
+
+    /// Returns the tileset, with the `columns` and `tilecount` of its image, or the `tilecount`
+    /// of its tiles for a collection of images.
+    ///
+    /// Fails if the tile size is 0, if the image is smaller than a tile, or if the tiles are not
+    /// unique and inside the tileset, or if a Wang set refers to a tile outside the tileset or to a
+    /// color it doesn't have.
+    pub fn build(mut self) -> Result<Tileset> {
+        let tileset = &mut self.tileset;
+        if tileset.tilewidth == 0 || tileset.tileheight == 0 {
+            return Err(build_failed(&tileset.name, "the tile size is 0"));
+        }
+
+        let tiles = tileset.tiles.as_deref().unwrap_or_default();
+        if tileset.image.is_empty() {
+            tileset.columns = 0;
+            tileset.tilecount = tiles.len() as u32;
+        } else {
+            let count = |size: u32, tile_size: u32| {
+                let (margin, spacing) = (tileset.margin, tileset.spacing);
+                (size.saturating_sub(2 * margin) + spacing) / (tile_size + spacing)
+            };
+            let columns = count(tileset.imagewidth, tileset.tilewidth);
+            let rows = count(tileset.imageheight, tileset.tileheight);
+            if columns == 0 || rows == 0 {
+                return Err(build_failed(
+                    &tileset.name,
+                    "the image is smaller than a tile",
+                ));
+            }
+            tileset.columns = columns;
+            tileset.tilecount = columns * rows;
+        }
+
+        let mut ids = HashSet::new();
+        for tile in tiles {
+            if !ids.insert(tile.id) {
+                let message = format!("tile {} is defined twice", tile.id);
+                return Err(build_failed(&tileset.name, &message));
+            }
+            if !tileset.image.is_empty() && tile.id >= tileset.tilecount {
+                let message = format!("tile {} is outside the image", tile.id);
+                return Err(build_failed(&tileset.name, &message));
+            }
+        }
+
+        for wang_set in tileset.wangsets.iter().flatten() {
+            let colors = wang_set.colors.len();
+            for wang_tile in &wang_set.wangtiles {
+                let color = wang_tile.wangid.iter().find(|&&c| c as usize > colors);
+                let message = if wang_tile.tileid >= tileset.tilecount {
+                    format!(
+                        "Wang set '{}' has no tile {}",
+                        wang_set.name, wang_tile.tileid
+                    )
+                } else if let Some(color) = color {
+                    format!("Wang set '{}' has no color {}", wang_set.name, color)
+                } else {
+                    continue;
+                };
+                return Err(build_failed(&tileset.name, &message));
+            }
+        }
+
+        Ok(self.tileset)
+    }
+}
+
+fn build_failed(what: &str, message: &str) -> Error {
+    Error::BuildFailed {
+        what: what.to_string(),
+        message: message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tiled::tileset::WangTile;
+
+    #[test]
+    fn ids_and_first_gids_are_assigned() {
+        let mut map = MapBuilder::new(4, 2, 8, 8);
+        let cells = TilesetBuilder::new("cells", 8, 8)
+            .image("cells.png", 34, 16)
+            .build()
+            .unwrap();
+        assert_eq!((cells.columns, cells.tilecount), (4, 8));
+        let items = TilesetBuilder::new("items", 8, 8)
+            .image("items.png", 19, 19)
+            .margin_and_spacing(1, 1)
+            .build()
+            .unwrap();
+        assert_eq!((items.columns, items.tilecount), (2, 4));
+        assert_eq!(map.add_tileset(cells), 1);
+        assert_eq!(map.add_external_tileset("items.tsj", &items), 9);
+
+        let tiles = map.tile_layer("Tiles").tile(1, 1, Gid(12)).build().unwrap();
+        let patrol = map
+            .object("Patrol")
+            .polyline(vec![Point::default(), Point { x: 8.0, y: 0.0 }])
+            .build()
+            .unwrap();
+        let guardian = map
+            .object("Guardian")
+            .gid(Gid(2))
+            .size(8.0, 8.0)
+            .build()
+            .unwrap();
+        let objects = map
+            .object_group("Objects")
+            .objects([guardian, patrol])
+            .build()
+            .unwrap();
+        let room = map
+            .group("Room")
+            .layer(tiles)
+            .layer(objects)
+            .build()
+            .unwrap();
+        map.add_layer(room);
+        let map = map.build().unwrap();
+
+        let room = &map.layers[0];
+        let layers = room.layers.as_ref().unwrap();
+        assert_eq!((room.id, layers[0].id, layers[1].id), (3, 1, 2));
+        assert_eq!(map.nextlayerid, 4);
+        assert_eq!(map.object(1).unwrap().name, "Patrol");
+        assert_eq!(map.tile("Room/Tiles", 1, 1), Some(12));
+        assert_eq!(map.tilesets[1].source.as_deref(), Some("items.tsj"));
+    }
+
+    #[test]
+    fn map_settings_are_chained() {
+        let map = MapBuilder::new(4, 2, 8, 8)
+            .class("level")
+            .size(8, 4)
+            .background_color(Some("#ff000000".to_string()))
+            .build()
+            .unwrap();
+
+        assert_eq!(map.class.as_deref(), Some("level"));
+        assert_eq!((map.width, map.height), (8, 4));
+        assert_eq!(map.backgroundcolor.as_deref(), Some("#ff000000"));
+    }
+
+    #[test]
+    fn invalid_builds_are_errors() {
+        let mut map = MapBuilder::new(4, 2, 8, 8);
+
+        assert!(map.tile_layer("Tiles").data(vec![0; 7]).build().is_err());
+        assert!(map.tile_layer("Tiles").tile(4, 0, Gid(1)).build().is_err());
+        assert!(map.object("Line").polyline(vec![]).build().is_err());
+        assert!(
+            TilesetBuilder::new("cells", 8, 8)
+                .image("cells.png", 4, 4)
+                .build()
+                .is_err()
+        );
+        let wang_set = WangSet {
+            wangtiles: vec![WangTile {
+                tileid: 0,
+                wangid: [1, 0, 0, 0, 0, 0, 0, 0],
+            }],
+            ..Default::default()
+        };
+        let tileset = TilesetBuilder::new("cells", 8, 8).image("cells.png", 8, 8);
+        assert!(tileset.wang_set(wang_set).build().is_err());
+        let a = map.object_group("A").build().unwrap();
+        let b = map.object_group("A").build().unwrap();
+        assert!(matches!(
+            map.group("Group").layer(a).layer(b).build(),
+            Err(Error::NonUniqueLayerName { .. })
+        ));
+
+        // Tiles outside the tilesets
+        let tiles = map.tile_layer("Tiles").tile(0, 0, Gid(1)).build().unwrap();
+        map.add_layer(tiles);
+        assert!(matches!(map.build(), Err(Error::BuildFailed { .. })));
+    }
+}
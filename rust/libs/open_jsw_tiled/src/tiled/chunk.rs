@@ -1,11 +1,13 @@
 use nanoserde::{DeJson, SerJson};
 
+use super::layer_data::LayerData;
+
 /// Represents a Layer in the map.
 #[derive(Clone, Debug, Default, DeJson, SerJson)]
 #[nserde(default)]
 pub struct Chunk {
     /// Array of unsigned int (GIDs) or base64-encoded data.
-    pub data: Vec<u32>,
+    pub data: LayerData,
 
     /// Height in tiles.
     pub height: u32,
@@ -19,3 +21,41 @@ pub struct Chunk {
     /// Y coordinate in tiles.
     pub y: i32,
 }
+
+/// Width and height in tiles of the chunks Tiled allocates on infinite maps.
+pub const CHUNK_SIZE: u32 = 16;
+
+impl Chunk {
+    /// Creates an empty chunk at `(x, y)` in tiles.
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        Self {
+            data: vec![0; (width * height) as usize].into(),
+            height,
+            width,
+            x,
+            y,
+        }
+    }
+
+    /// Returns the index in `data` of the tile at `(x, y)` in tiles, if it is in the chunk.
+    pub fn index(&self, x: i32, y: i32) -> Option<usize> {
+        let column = x as i64 - self.x as i64;
+        let row = y as i64 - self.y as i64;
+
+        (column >= 0 && row >= 0 && column < self.width as i64 && row < self.height as i64)
+            .then(|| (row * self.width as i64 + column) as usize)
+    }
+
+    /// Returns the GID at `(x, y)` in tiles in a set of chunks, which is empty (0) outside the
+    /// chunks, or `None` if the tile is missing from the data of its chunk.
+    pub fn find_tile(chunks: &[Chunk], x: i32, y: i32) -> Option<u32> {
+        chunks
+            .iter()
+            .find_map(|chunk| {
+                chunk
+                    .index(x, y)
+                    .map(|index| chunk.data.get(index).copied())
+            })
+            .unwrap_or(Some(0))
+    }
+}
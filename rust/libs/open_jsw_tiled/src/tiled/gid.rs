@@ -0,0 +1,161 @@
+use std::fmt::Display;
+
+use nanoserde::{DeJson, SerJson};
+
+/// A global tile ID, with the flags Tiled stores in its highest four bits.
+///
+/// https://doc.mapeditor.org/en/stable/reference/global-tile-ids/
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Gid(pub u32);
+
+impl Gid {
+    pub const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+    pub const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+    pub const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+    pub const ROTATED_HEXAGONAL_120: u32 = 0x1000_0000;
+    pub const FLAGS: u32 = 0xf000_0000;
+
+    /// The GID of an empty tile.
+    pub const EMPTY: Gid = Gid(0);
+
+    /// Creates a GID from a tile ID (without flags) and flags.
+    pub fn new(id: u32, flags: u32) -> Self {
+        Self((id & !Self::FLAGS) | (flags & Self::FLAGS))
+    }
+
+    /// The tile ID, without the flags.
+    pub fn id(self) -> u32 {
+        self.0 & !Self::FLAGS
+    }
+
+    pub fn flags(self) -> u32 {
+        self.0 & Self::FLAGS
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.id() == 0
+    }
+
+    pub fn flipped_horizontally(self) -> bool {
+        self.0 & Self::FLIPPED_HORIZONTALLY != 0
+    }
+
+    pub fn flipped_vertically(self) -> bool {
+        self.0 & Self::FLIPPED_VERTICALLY != 0
+    }
+
+    /// Whether the tile is flipped along its top-left to bottom-right diagonal (anti-diagonally
+    /// on hexagonal maps, where it is a 60 degree rotation).
+    pub fn flipped_diagonally(self) -> bool {
+        self.0 & Self::FLIPPED_DIAGONALLY != 0
+    }
+
+    /// Whether the tile is rotated by 120 degrees (hexagonal maps only).
+    pub fn rotated_hexagonal_120(self) -> bool {
+        self.0 & Self::ROTATED_HEXAGONAL_120 != 0
+    }
+
+    /// Returns the pixel of the tile image shown at `(x, y)` of the drawn tile, for a tile image
+    /// of `width` x `height` pixels.
+    ///
+    /// Tiled flips diagonally first, then horizontally and vertically, so a diagonally flipped
+    /// tile is drawn `height` pixels wide and `width` pixels high.
+    pub fn source_pixel(self, x: u32, y: u32, width: u32, height: u32) -> (u32, u32) {
+        let (drawn_width, drawn_height) = if self.flipped_diagonally() {
+            (height, width)
+        } else {
+            (width, height)
+        };
+
+        let x = if self.flipped_horizontally() {
+            drawn_width - 1 - x
+        } else {
+            x
+        };
+        let y = if self.flipped_vertically() {
+            drawn_height - 1 - y
+        } else {
+            y
+        };
+
+        if self.flipped_diagonally() {
+            (y, x)
+        } else {
+            (x, y)
+        }
+    }
+}
+
+impl From<u32> for Gid {
+    fn from(gid: u32) -> Self {
+        Self(gid)
+    }
+}
+
+impl From<Gid> for u32 {
+    fn from(gid: Gid) -> Self {
+        gid.0
+    }
+}
+
+impl Display for Gid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl DeJson for Gid {
+    fn de_json(
+        s: &mut nanoserde::DeJsonState,
+        i: &mut std::str::Chars,
+    ) -> core::result::Result<Self, nanoserde::DeJsonErr> {
+        Ok(Self(u32::de_json(s, i)?))
+    }
+}
+
+impl SerJson for Gid {
+    fn ser_json(&self, d: usize, s: &mut nanoserde::SerJsonState) {
+        self.0.ser_json(d, s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_are_separated_from_the_id() {
+        let gid = Gid(0xa000_0007);
+
+        assert_eq!(gid.id(), 7);
+        assert!(gid.flipped_horizontally());
+        assert!(!gid.flipped_vertically());
+        assert!(gid.flipped_diagonally());
+        assert_eq!(Gid::new(7, gid.flags()), gid);
+    }
+
+    #[test]
+    fn flipped_tiles_are_sampled_from_the_mirrored_pixel() {
+        let (width, height) = (4, 2);
+
+        assert_eq!(Gid(1).source_pixel(1, 0, width, height), (1, 0));
+        let horizontal = Gid::new(1, Gid::FLIPPED_HORIZONTALLY);
+        assert_eq!(horizontal.source_pixel(0, 0, width, height), (3, 0));
+        let vertical = Gid::new(1, Gid::FLIPPED_VERTICALLY);
+        assert_eq!(vertical.source_pixel(0, 0, width, height), (0, 1));
+        // Drawn 2 pixels wide and 4 high
+        let diagonal = Gid::new(1, Gid::FLIPPED_DIAGONALLY);
+        assert_eq!(diagonal.source_pixel(1, 3, width, height), (3, 1));
+        // Rotated 90 degrees clockwise
+        let rotated = Gid::new(1, Gid::FLIPPED_DIAGONALLY | Gid::FLIPPED_HORIZONTALLY);
+        assert_eq!(rotated.source_pixel(0, 0, width, height), (0, 1));
+    }
+
+    #[test]
+    fn gids_with_flags_round_trip_in_json() {
+        let gid: Gid = DeJson::deserialize_json("2147483655").unwrap();
+
+        assert_eq!(gid, Gid::new(7, Gid::FLIPPED_HORIZONTALLY));
+        assert_eq!(SerJson::serialize_json(&gid), "2147483655");
+    }
+}
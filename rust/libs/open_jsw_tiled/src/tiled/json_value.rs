@@ -0,0 +1,244 @@
+use std::str::Chars;
+
+use nanoserde::{DeJson, DeJsonErr, DeJsonState, DeJsonTok, SerJson, SerJsonState};
+
+use super::{layer::Layer, map::Map, tileset::Tileset};
+
+/// Any JSON value, with the fields of objects in their original order.
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Bool(bool),
+    U64(u64),
+    I64(i64),
+    F64(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+/// The fields of a JSON object which a struct doesn't model, such as the `editorsettings` of a
+/// map, kept to be written back when the struct is serialized.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UnknownFields(pub Vec<(String, JsonValue)>);
+
+impl JsonValue {
+    /// Returns the value of a field of an object.
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn get_mut(&mut self, key: &str) -> Option<&mut JsonValue> {
+        match self {
+            JsonValue::Object(fields) => fields.iter_mut().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn items(&self) -> &[JsonValue] {
+        match self {
+            JsonValue::Array(items) => items,
+            _ => &[],
+        }
+    }
+
+    fn items_mut(&mut self) -> &mut [JsonValue] {
+        match self {
+            JsonValue::Array(items) => items,
+            _ => &mut [],
+        }
+    }
+
+    /// Removes a field of an object, if it has it.
+    pub(crate) fn remove_field(&mut self, key: &str) {
+        if let JsonValue::Object(fields) = self {
+            fields.retain(|(k, _)| k != key);
+        }
+    }
+
+    /// Returns the fields of `self` which are not fields of `known`.
+    fn unknown_fields(&self, known: &JsonValue) -> UnknownFields {
+        let JsonValue::Object(fields) = self else {
+            return UnknownFields::default();
+        };
+        let unknown = fields
+            .iter()
+            .filter(|(key, _)| known.get(key).is_none())
+            .cloned()
+            .collect();
+        UnknownFields(unknown)
+    }
+
+    /// Appends unknown fields to an object, unless the object already has them.
+    fn add_fields(&mut self, unknown: &UnknownFields) {
+        let JsonValue::Object(fields) = self else {
+            return;
+        };
+        for (key, value) in &unknown.0 {
+            if !fields.iter().any(|(k, _)| k == key) {
+                fields.push((key.clone(), value.clone()));
+            }
+        }
+    }
+}
+
+impl UnknownFields {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl DeJson for JsonValue {
+    fn de_json(s: &mut DeJsonState, i: &mut Chars) -> Result<Self, DeJsonErr> {
+        let value = match s.tok {
+            DeJsonTok::Null => JsonValue::Null,
+            DeJsonTok::Bool(value) => JsonValue::Bool(value),
+            DeJsonTok::U64(value) => JsonValue::U64(value),
+            DeJsonTok::I64(value) => JsonValue::I64(value),
+            DeJsonTok::F64(value) => JsonValue::F64(value),
+            DeJsonTok::Str => JsonValue::String(s.as_string()?),
+            DeJsonTok::BlockOpen => return Ok(JsonValue::Array(DeJson::de_json(s, i)?)),
+            DeJsonTok::CurlyOpen => {
+                let mut fields = Vec::new();
+                s.curly_open(i)?;
+                while s.tok != DeJsonTok::CurlyClose {
+                    let key = String::de_json(s, i)?;
+                    s.colon(i)?;
+                    fields.push((key, JsonValue::de_json(s, i)?));
+                    s.eat_comma_curly(i)?;
+                }
+                s.curly_close(i)?;
+                return Ok(JsonValue::Object(fields));
+            }
+            _ => return Err(s.err_token("value")),
+        };
+        s.next_tok(i)?;
+
+        Ok(value)
+    }
+}
+
+impl SerJson for JsonValue {
+    fn ser_json(&self, d: usize, s: &mut SerJsonState) {
+        match self {
+            JsonValue::Null => s.out.push_str("null"),
+            JsonValue::Bool(value) => value.ser_json(d, s),
+            JsonValue::U64(value) => value.ser_json(d, s),
+            JsonValue::I64(value) => value.ser_json(d, s),
+            JsonValue::F64(value) => value.ser_json(d, s),
+            JsonValue::String(value) => value.ser_json(d, s),
+            JsonValue::Array(items) => items.ser_json(d, s),
+            JsonValue::Object(fields) => {
+                s.st_pre();
+                for (index, (key, value)) in fields.iter().enumerate() {
+                    if index > 0 {
+                        s.conl();
+                    }
+                    s.indent(d + 1);
+                    key.ser_json(d + 1, s);
+                    s.out.push(':');
+                    value.ser_json(d + 1, s);
+                }
+                s.st_post(d);
+            }
+        }
+    }
+}
+
+/// Keeps the fields of a map read from `raw` which the map, its layers and its tilesets don't
+/// model. `known` is the map serialized, whose fields are the ones modelled.
+pub(crate) fn keep_unknown_fields(map: &mut Map, raw: &JsonValue, known: &JsonValue) {
+    map.unknown_fields = raw.unknown_fields(known);
+    keep_unknown_layer_fields(&mut map.layers, raw.get("layers"), known.get("layers"));
+    keep_unknown_tileset_fields(
+        &mut map.tilesets,
+        raw.get("tilesets"),
+        known.get("tilesets"),
+    );
+}
+
+fn keep_unknown_layer_fields(
+    layers: &mut [Layer],
+    raw: Option<&JsonValue>,
+    known: Option<&JsonValue>,
+) {
+    let (Some(raw), Some(known)) = (raw, known) else {
+        return;
+    };
+    for ((layer, raw), known) in layers.iter_mut().zip(raw.items()).zip(known.items()) {
+        layer.unknown_fields = raw.unknown_fields(known);
+        if let Some(layers) = layer.layers.as_mut() {
+            keep_unknown_layer_fields(layers, raw.get("layers"), known.get("layers"));
+        }
+    }
+}
+
+fn keep_unknown_tileset_fields(
+    tilesets: &mut [Tileset],
+    raw: Option<&JsonValue>,
+    known: Option<&JsonValue>,
+) {
+    let (Some(raw), Some(known)) = (raw, known) else {
+        return;
+    };
+    for ((tileset, raw), known) in tilesets.iter_mut().zip(raw.items()).zip(known.items()) {
+        tileset.unknown_fields = raw.unknown_fields(known);
+    }
+}
+
+/// Adds the unknown fields of a map, its layers and its tilesets to the map serialized as `out`.
+pub(crate) fn add_unknown_fields(map: &Map, out: &mut JsonValue) {
+    out.add_fields(&map.unknown_fields);
+    if let Some(layers) = out.get_mut("layers") {
+        add_unknown_layer_fields(&map.layers, layers);
+    }
+    if let Some(tilesets) = out.get_mut("tilesets") {
+        for (tileset, out) in map.tilesets.iter().zip(tilesets.items_mut()) {
+            out.add_fields(&tileset.unknown_fields);
+        }
+    }
+}
+
+fn add_unknown_layer_fields(layers: &[Layer], out: &mut JsonValue) {
+    for (layer, out) in layers.iter().zip(out.items_mut()) {
+        out.add_fields(&layer.unknown_fields);
+        if let (Some(layers), Some(out)) = (&layer.layers, out.get_mut("layers")) {
+            add_unknown_layer_fields(layers, out);
+        }
+    }
+}
+
+/// Returns whether a map, its layers or its tilesets have unknown fields.
+pub(crate) fn has_unknown_fields(map: &Map) -> bool {
+    fn in_layers(layers: &[Layer]) -> bool {
+        layers.iter().any(|layer| {
+            !layer.unknown_fields.is_empty()
+                || in_layers(layer.layers.as_deref().unwrap_or_default())
+        })
+    }
+
+    !map.unknown_fields.is_empty()
+        || map
+            .tilesets
+            .iter()
+            .any(|tileset| !tileset.unknown_fields.is_empty())
+        || in_layers(&map.layers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_are_written_as_read() {
+        let json = r#"{"a":[1,-2,0.5,"x\"y",true,null],"b":{},"c":[]}"#;
+
+        let value = JsonValue::deserialize_json(json).unwrap();
+
+        assert_eq!(value.get("a").unwrap().items().len(), 6);
+        assert_eq!(value.serialize_json(), json);
+    }
+}
@@ -1,6 +1,14 @@
 #![allow(clippy::question_mark)]
 
-use super::{chunk::Chunk, layer_data::TileMatrix, map::Map, object::Object, property::Property};
+use super::{
+    chunk::{CHUNK_SIZE, Chunk},
+    json_value::UnknownFields,
+    layer_data::{LayerData, TileMatrix},
+    map::Map,
+    object::Object,
+    property::Property,
+};
+use crate::{Error, Result};
 use nanoserde::{DeJson, SerJson};
 
 /// Represents a Layer in the map.
@@ -17,8 +25,7 @@ pub struct Layer {
     pub compression: Option<Compression>,
 
     /// Array of unsigned int (GIDs) or base64-encoded data (tilelayer only).
-    // pub data: Option<Data>,
-    pub data: Option<Vec<u32>>,
+    pub data: Option<LayerData>,
 
     /// Draw order: "topdown" (default) or "index" (objectgroup only).
     pub draworder: Option<DrawOrder>,
@@ -106,6 +113,10 @@ pub struct Layer {
 
     /// Vertical layer offset in tiles (always 0).
     pub y: i32,
+
+    /// Fields read from JSON which are not modelled, written back as they were read.
+    #[nserde(skip)]
+    pub unknown_fields: UnknownFields,
 }
 
 #[derive(Clone, Debug, Default, DeJson, SerJson)]
@@ -154,12 +165,23 @@ impl Layer {
         let id = map.next_layer_id();
 
         let mut data = None;
+        let mut chunks = None;
         let mut width = None;
         let mut height = None;
+        let mut startx = None;
+        let mut starty = None;
 
         match typ {
+            LayerType::TileLayer if map.infinite => {
+                // Chunks are allocated as tiles are set
+                chunks = Some(Vec::new());
+                width = Some(0);
+                height = Some(0);
+                startx = Some(0);
+                starty = Some(0);
+            }
             LayerType::TileLayer => {
-                data = Some(vec![0; (map.width * map.height) as usize]);
+                data = Some(vec![0; (map.width * map.height) as usize].into());
                 width = Some(map.width);
                 height = Some(map.height);
             }
@@ -179,18 +201,376 @@ impl Layer {
             parallaxy: 1.0,
             height,
             width,
+            startx,
+            starty,
             data,
+            chunks,
             ..Default::default()
         }
     }
 
-    pub fn get_tile_matrix(&mut self) -> TileMatrix<u32> {
+    /// Returns the data of a fixed-size tile layer as a matrix.
+    ///
+    /// Returns `None` if the layer is not a tile layer or is chunked; use [`Layer::tile`] and
+    /// [`Layer::set_tile`] for layers of infinite maps.
+    pub fn get_tile_matrix(&mut self) -> Option<TileMatrix<u32>> {
+        if !matches!(self.typ, LayerType::TileLayer) {
+            return None;
+        }
+
+        let width = self.width? as usize;
+        let data = self.data.as_mut()?;
+        Some(TileMatrix::wrap_vec(&mut **data, width))
+    }
+
+    /// Returns the GID at `(x, y)` in tiles, which may be negative for layers of infinite maps.
+    ///
+    /// Returns `None` if the layer is not a tile layer, if the tile is outside a fixed-size
+    /// layer, or if it is missing from the data of a chunk. The tiles outside the chunks of a
+    /// chunked layer are empty (0).
+    pub fn tile(&self, x: i32, y: i32) -> Option<u32> {
+        if !matches!(self.typ, LayerType::TileLayer) {
+            return None;
+        }
+
+        if let Some(chunks) = &self.chunks {
+            return Chunk::find_tile(chunks, x, y);
+        }
+
+        let index = self.fixed_index(x, y)?;
+        self.data.as_ref()?.get(index).copied()
+    }
+
+    /// Sets the GID at `(x, y)` in tiles.
+    ///
+    /// On a chunked layer a chunk of [`CHUNK_SIZE`] tiles is allocated if there is none at the
+    /// tile, and the bounds of the layer are updated.
+    pub fn set_tile(&mut self, x: i32, y: i32, gid: u32) -> Result<()> {
+        if !matches!(self.typ, LayerType::TileLayer) {
+            return Err(Error::NotATileLayer {
+                layer: self.name.clone(),
+            });
+        }
+
+        if let Some(chunks) = self.chunks.as_mut() {
+            let found = chunks
+                .iter()
+                .enumerate()
+                .find_map(|(n, chunk)| chunk.index(x, y).map(|index| (n, index)));
+
+            match found {
+                Some((n, index)) => {
+                    // The data of a chunk read from a file may be shorter than the chunk
+                    let tile =
+                        chunks[n]
+                            .data
+                            .get_mut(index)
+                            .ok_or_else(|| Error::TileOutOfBounds {
+                                layer: self.name.clone(),
+                                x,
+                                y,
+                            })?;
+                    *tile = gid;
+                }
+                // Tiles outside the chunks are already empty
+                None if gid == 0 => (),
+                None => {
+                    let size = CHUNK_SIZE as i32;
+                    let mut chunk = Chunk::new(
+                        x.div_euclid(size) * size,
+                        y.div_euclid(size) * size,
+                        CHUNK_SIZE,
+                        CHUNK_SIZE,
+                    );
+                    let index = chunk.index(x, y).unwrap();
+                    chunk.data[index] = gid;
+                    chunks.push(chunk);
+                    self.update_chunk_bounds();
+                }
+            }
+
+            return Ok(());
+        }
+
+        let out_of_bounds = || Error::TileOutOfBounds {
+            layer: self.name.clone(),
+            x,
+            y,
+        };
+        let index = self.fixed_index(x, y).ok_or_else(out_of_bounds)?;
+        let tile = self
+            .data
+            .as_mut()
+            .and_then(|data| data.get_mut(index))
+            .ok_or_else(out_of_bounds)?;
+        *tile = gid;
+
+        Ok(())
+    }
+
+    /// Returns the area covered by the data of a tile layer, as `(x, y, width, height)` in tiles,
+    /// or `None` if the layer has no data.
+    pub fn tile_bounds(&self) -> Option<(i32, i32, u32, u32)> {
+        if !matches!(self.typ, LayerType::TileLayer) {
+            return None;
+        }
+
+        let Some(chunks) = &self.chunks else {
+            self.data.as_ref()?;
+            return Some((0, 0, self.width?, self.height?));
+        };
+
+        let left = chunks.iter().map(|chunk| chunk.x).min()?;
+        let top = chunks.iter().map(|chunk| chunk.y).min()?;
+        let right = chunks.iter().map(|c| c.x as i64 + c.width as i64).max()?;
+        let bottom = chunks.iter().map(|c| c.y as i64 + c.height as i64).max()?;
+
+        Some((
+            left,
+            top,
+            (right - left as i64) as u32,
+            (bottom - top as i64) as u32,
+        ))
+    }
+
+    /// Splits the data of a fixed-size tile layer into chunks, leaving out the empty chunks as
+    /// Tiled does.
+    pub fn convert_to_chunks(&mut self, chunk_width: u32, chunk_height: u32) {
+        if !matches!(self.typ, LayerType::TileLayer) || self.chunks.is_some() {
+            return;
+        }
+        let (Some(width), Some(height)) = (self.width, self.height) else {
+            return;
+        };
+        let data = self.data.take().unwrap_or_default();
+
+        let mut chunks = Vec::new();
+        for top in (0..height).step_by(chunk_height.max(1) as usize) {
+            for left in (0..width).step_by(chunk_width.max(1) as usize) {
+                let mut chunk = Chunk::new(left as i32, top as i32, chunk_width, chunk_height);
+                for row in 0..chunk_height.min(height - top) {
+                    for column in 0..chunk_width.min(width - left) {
+                        let index = ((top + row) * width + left + column) as usize;
+                        chunk.data[(row * chunk_width + column) as usize] =
+                            data.get(index).copied().unwrap_or(0);
+                    }
+                }
+                if chunk.data.iter().any(|gid| *gid != 0) {
+                    chunks.push(chunk);
+                }
+            }
+        }
+
+        self.chunks = Some(chunks);
+        self.startx = Some(0);
+        self.starty = Some(0);
+        self.width = Some(0);
+        self.height = Some(0);
+        self.update_chunk_bounds();
+    }
+
+    /// Merges the chunks of a tile layer into fixed-size data covering `width` x `height` tiles
+    /// from `(x, y)`. Tiles outside that area are dropped.
+    pub fn convert_to_fixed(&mut self, x: i32, y: i32, width: u32, height: u32) {
+        if !matches!(self.typ, LayerType::TileLayer) {
+            return;
+        }
+        let Some(chunks) = self.chunks.take() else {
+            return;
+        };
+
+        let mut data = vec![0; (width * height) as usize];
+        for chunk in &chunks {
+            let chunk_width = chunk.width.max(1) as usize;
+            for (index, gid) in chunk.data.iter().enumerate() {
+                let column = chunk.x as i64 + (index % chunk_width) as i64 - x as i64;
+                let row = chunk.y as i64 + (index / chunk_width) as i64 - y as i64;
+                if column >= 0 && row >= 0 && column < width as i64 && row < height as i64 {
+                    data[(row * width as i64 + column) as usize] = *gid;
+                }
+            }
+        }
+
+        self.data = Some(data.into());
+        self.width = Some(width);
+        self.height = Some(height);
+        self.startx = None;
+        self.starty = None;
+    }
+
+    fn fixed_index(&self, x: i32, y: i32) -> Option<usize> {
+        let (width, height) = (self.width? as i64, self.height? as i64);
+        let (x, y) = (x as i64, y as i64);
+
+        (x >= 0 && y >= 0 && x < width && y < height).then(|| (y * width + x) as usize)
+    }
+
+    /// Sets `startx`, `starty`, `width` and `height` to the area covered by the chunks.
+    fn update_chunk_bounds(&mut self) {
+        if let Some((x, y, width, height)) = self.tile_bounds() {
+            self.startx = Some(x);
+            self.starty = Some(y);
+            self.width = Some(width);
+            self.height = Some(height);
+        }
+    }
+
+    /// Sets how the data of the layer, or of the tile layers in a group, is written.
+    pub fn set_data_encoding(
+        &mut self,
+        encoding: Option<LayerEncoding>,
+        compression: Option<Compression>,
+    ) {
         match self.typ {
             LayerType::TileLayer => {
-                let data = self.data.as_mut().unwrap();
-                TileMatrix::wrap_vec(data, self.width.unwrap() as usize)
+                self.encoding = encoding;
+                self.compression = compression;
             }
-            _ => panic!("Layer is not a tile layer"),
+            LayerType::Group => {
+                for layer in self.layers.iter_mut().flatten() {
+                    layer.set_data_encoding(encoding.clone(), compression.clone());
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Decodes the data of the layer, its chunks and its sub-layers read as strings.
+    pub fn decode_data(&mut self) -> Result<()> {
+        let encoding = self.encoding.as_ref();
+        let compression = self.compression.as_ref();
+
+        if let Some(data) = self.data.as_mut() {
+            data.decode(encoding, compression)?;
+        }
+        for chunk in self.chunks.iter_mut().flatten() {
+            chunk.data.decode(encoding, compression)?;
+        }
+        for layer in self.layers.iter_mut().flatten() {
+            layer.decode_data()?;
         }
+
+        Ok(())
+    }
+
+    /// Encodes the data of the layer, its chunks and its sub-layers as set by `encoding` and
+    /// `compression`, at the compression level of the map.
+    pub fn encode_data(&mut self, compression_level: i32) -> Result<()> {
+        let encoding = self.encoding.as_ref();
+        let compression = self.compression.as_ref();
+
+        if let Some(data) = self.data.as_mut() {
+            data.encode(encoding, compression, compression_level)?;
+        }
+        for chunk in self.chunks.iter_mut().flatten() {
+            chunk
+                .data
+                .encode(encoding, compression, compression_level)?;
+        }
+        for layer in self.layers.iter_mut().flatten() {
+            layer.encode_data(compression_level)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tiled::map::MapOrientation;
+
+    fn infinite_map() -> Map {
+        let mut map = Map::new(None, MapOrientation::Orthogonal, 0, 0, 8, 8);
+        map.infinite = true;
+        map
+    }
+
+    #[test]
+    fn setting_tiles_allocates_chunks() {
+        let mut map = infinite_map();
+        let mut layer = Layer::new(&mut map, LayerType::TileLayer, "Tiles".to_string());
+
+        layer.set_tile(-1, 2, 5).unwrap();
+        layer.set_tile(20, -3, 7).unwrap();
+        layer.set_tile(100, 100, 0).unwrap();
+
+        let chunks = layer.chunks.as_ref().unwrap();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!((chunks[0].x, chunks[0].y), (-16, 0));
+        assert_eq!((chunks[1].x, chunks[1].y), (16, -16));
+        assert_eq!(layer.tile(-1, 2), Some(5));
+        assert_eq!(layer.tile(20, -3), Some(7));
+        assert_eq!(layer.tile(100, 100), Some(0));
+        assert_eq!((layer.startx, layer.starty), (Some(-16), Some(-16)));
+        assert_eq!((layer.width, layer.height), (Some(48), Some(32)));
+    }
+
+    #[test]
+    fn short_chunk_data_is_not_indexed() {
+        let mut map = infinite_map();
+        let mut layer = Layer::new(&mut map, LayerType::TileLayer, "Tiles".to_string());
+        let mut chunk = Chunk::new(0, 0, 4, 4);
+        chunk.data.truncate(2);
+        layer.chunks = Some(vec![chunk]);
+
+        assert_eq!(layer.tile(1, 0), Some(0));
+        assert_eq!(layer.tile(3, 3), None);
+        assert!(matches!(
+            layer.set_tile(3, 3, 1),
+            Err(Error::TileOutOfBounds { x: 3, y: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn tile_matrices_are_only_for_fixed_tile_layers() {
+        let mut map = Map::new(None, MapOrientation::Orthogonal, 4, 2, 8, 8);
+        let mut tiles = Layer::new(&mut map, LayerType::TileLayer, "Tiles".to_string());
+        let mut objects = Layer::new(&mut map, LayerType::ObjectGroup, "Objects".to_string());
+        let mut map = infinite_map();
+        let mut chunked = Layer::new(&mut map, LayerType::TileLayer, "Chunks".to_string());
+
+        assert!(tiles.get_tile_matrix().is_some());
+        assert!(objects.get_tile_matrix().is_none());
+        assert!(chunked.get_tile_matrix().is_none());
+    }
+
+    #[test]
+    fn fixed_layers_are_bounded() {
+        let mut map = Map::new(None, MapOrientation::Orthogonal, 4, 2, 8, 8);
+        let mut layer = Layer::new(&mut map, LayerType::TileLayer, "Tiles".to_string());
+
+        layer.set_tile(3, 1, 9).unwrap();
+
+        assert_eq!(layer.tile(3, 1), Some(9));
+        assert_eq!(**layer.data.as_ref().unwrap(), [0, 0, 0, 0, 0, 0, 0, 9]);
+        assert_eq!(layer.tile(4, 0), None);
+        assert!(matches!(
+            layer.set_tile(-1, 0, 1),
+            Err(Error::TileOutOfBounds { x: -1, y: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn chunked_and_fixed_conversion() {
+        let mut map = Map::new(None, MapOrientation::Orthogonal, 20, 3, 8, 8);
+        let mut layer = Layer::new(&mut map, LayerType::TileLayer, "Tiles".to_string());
+        layer.set_tile(0, 0, 1).unwrap();
+        layer.set_tile(19, 2, 2).unwrap();
+
+        layer.convert_to_chunks(8, 8);
+
+        assert!(layer.data.is_none());
+        // The empty chunk in the middle is left out
+        assert_eq!(layer.chunks.as_ref().unwrap().len(), 2);
+        assert_eq!(layer.tile(19, 2), Some(2));
+        assert_eq!(layer.tile_bounds(), Some((0, 0, 24, 8)));
+
+        layer.convert_to_fixed(0, 0, 20, 3);
+
+        assert!(layer.chunks.is_none());
+        assert_eq!(layer.data.as_ref().unwrap().len(), 60);
+        assert_eq!(layer.tile(0, 0), Some(1));
+        assert_eq!(layer.tile(19, 2), Some(2));
     }
 }
@@ -1,4 +1,207 @@
-use std::ops::{Index, IndexMut};
+#![allow(clippy::question_mark)]
+
+use std::{
+    io::{Read, Write},
+    ops::{Deref, DerefMut, Index, IndexMut},
+};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use flate2::{
+    read::{GzDecoder, ZlibDecoder},
+    write::{GzEncoder, ZlibEncoder},
+};
+use nanoserde::{DeJson, SerJson};
+
+use super::layer::{Compression, LayerEncoding};
+use crate::{Error, Result};
+
+/// Tile GIDs of a tile layer or chunk.
+///
+/// In JSON the data is an array of GIDs, or a string when the layer is base64 encoded. As the
+/// encoding is a separate field of the layer, a string is kept as read until it is decoded (see
+/// [`LayerData::decode`]), and is only written once the tiles are encoded (see
+/// [`LayerData::encode`]).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LayerData {
+    tiles: Vec<u32>,
+    encoded: Option<String>,
+}
+
+impl LayerData {
+    pub fn new(tiles: Vec<u32>) -> Self {
+        Self {
+            tiles,
+            encoded: None,
+        }
+    }
+
+    /// Decodes the data read as a string, using the encoding and compression of its layer.
+    pub fn decode(
+        &mut self,
+        encoding: Option<&LayerEncoding>,
+        compression: Option<&Compression>,
+    ) -> Result<()> {
+        let Some(encoded) = self.encoded.take() else {
+            return Ok(());
+        };
+
+        match encoding {
+            Some(LayerEncoding::Base64) => {
+                self.tiles = decode_base64(&encoded, compression)?;
+                Ok(())
+            }
+            _ => Err(Error::InvalidLayerData {
+                message: "string data is only supported with base64 encoding".to_string(),
+            }),
+        }
+    }
+
+    /// Encodes the tiles to be written as a string, if the layer is base64 encoded.
+    ///
+    /// `compression_level` is the level set by the map, where -1 is the default of the algorithm.
+    pub fn encode(
+        &mut self,
+        encoding: Option<&LayerEncoding>,
+        compression: Option<&Compression>,
+        compression_level: i32,
+    ) -> Result<()> {
+        self.encoded = match encoding {
+            Some(LayerEncoding::Base64) => {
+                Some(encode_base64(&self.tiles, compression, compression_level)?)
+            }
+            _ => None,
+        };
+
+        Ok(())
+    }
+}
+
+impl From<Vec<u32>> for LayerData {
+    fn from(tiles: Vec<u32>) -> Self {
+        Self::new(tiles)
+    }
+}
+
+impl Deref for LayerData {
+    type Target = Vec<u32>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tiles
+    }
+}
+
+impl DerefMut for LayerData {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.tiles
+    }
+}
+
+impl DeJson for LayerData {
+    fn de_json(
+        s: &mut nanoserde::DeJsonState,
+        i: &mut std::str::Chars,
+    ) -> core::result::Result<Self, nanoserde::DeJsonErr> {
+        if matches!(s.tok, nanoserde::DeJsonTok::Str) {
+            let encoded = core::mem::take(&mut s.strbuf);
+            s.next_tok(i)?;
+
+            return Ok(Self {
+                tiles: Vec::new(),
+                encoded: Some(encoded),
+            });
+        }
+
+        Ok(Self::new(Vec::de_json(s, i)?))
+    }
+}
+
+impl SerJson for LayerData {
+    fn ser_json(&self, d: usize, s: &mut nanoserde::SerJsonState) {
+        match &self.encoded {
+            Some(encoded) => encoded.ser_json(d, s),
+            None => self.tiles.ser_json(d, s),
+        }
+    }
+}
+
+/// Decodes base64 tile data (little-endian GIDs, optionally compressed), ignoring any whitespace
+/// around or within it.
+pub fn decode_base64(data: &str, compression: Option<&Compression>) -> Result<Vec<u32>> {
+    let data: String = data.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut bytes = STANDARD.decode(data).map_err(invalid_layer_data)?;
+
+    if let Some(compression) = compression {
+        bytes = decompress(&bytes, compression).map_err(invalid_layer_data)?;
+    }
+
+    if bytes.len() % 4 != 0 {
+        return Err(Error::InvalidLayerData {
+            message: format!("{} bytes is not a whole number of tiles", bytes.len()),
+        });
+    }
+
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|gid| u32::from_le_bytes([gid[0], gid[1], gid[2], gid[3]]))
+        .collect())
+}
+
+/// Encodes tile data as base64 (little-endian GIDs, optionally compressed).
+///
+/// `compression_level` is the level set by the map, where -1 is the default of the algorithm.
+pub fn encode_base64(
+    tiles: &[u32],
+    compression: Option<&Compression>,
+    compression_level: i32,
+) -> Result<String> {
+    let mut bytes: Vec<u8> = tiles.iter().flat_map(|gid| gid.to_le_bytes()).collect();
+
+    if let Some(compression) = compression {
+        bytes = compress(&bytes, compression, compression_level).map_err(invalid_layer_data)?;
+    }
+
+    Ok(STANDARD.encode(bytes))
+}
+
+fn decompress(bytes: &[u8], compression: &Compression) -> std::io::Result<Vec<u8>> {
+    let mut decompressed = Vec::new();
+
+    match compression {
+        Compression::Zlib => ZlibDecoder::new(bytes).read_to_end(&mut decompressed)?,
+        Compression::Gzip => GzDecoder::new(bytes).read_to_end(&mut decompressed)?,
+        Compression::Zstd => return zstd::decode_all(bytes),
+    };
+
+    Ok(decompressed)
+}
+
+fn compress(bytes: &[u8], compression: &Compression, level: i32) -> std::io::Result<Vec<u8>> {
+    // zlib and gzip levels are 0 to 9, and zstd uses its default level for 0
+    let flate_level = match level {
+        0..=9 => flate2::Compression::new(level as u32),
+        _ => flate2::Compression::default(),
+    };
+
+    match compression {
+        Compression::Zlib => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), flate_level);
+            encoder.write_all(bytes)?;
+            encoder.finish()
+        }
+        Compression::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), flate_level);
+            encoder.write_all(bytes)?;
+            encoder.finish()
+        }
+        Compression::Zstd => zstd::encode_all(bytes, level.max(0)),
+    }
+}
+
+fn invalid_layer_data(error: impl std::fmt::Display) -> Error {
+    Error::InvalidLayerData {
+        message: error.to_string(),
+    }
+}
 
 pub struct TileMatrix<'a, T> {
     data: &'a mut Vec<T>,
@@ -36,3 +239,76 @@ impl<'a, T> IndexMut<usize> for TileMatrix<'a, T> {
         &mut self.data[start..start + self.cols]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_round_trip() {
+        let tiles = vec![0, 1, 2, 0x8000_0003];
+
+        let encoded = encode_base64(&tiles, None, -1).unwrap();
+
+        assert_eq!(encoded, "AAAAAAEAAAACAAAAAwAAgA==");
+        assert_eq!(
+            decode_base64(&format!("\n   {}\n", encoded), None).unwrap(),
+            tiles
+        );
+    }
+
+    #[test]
+    fn json_data_is_an_array_or_a_string() {
+        let mut data: LayerData = DeJson::deserialize_json(r#""AQAAAAIAAAA=""#).unwrap();
+        data.decode(Some(&LayerEncoding::Base64), None).unwrap();
+        assert_eq!(*data, vec![1, 2]);
+        assert_eq!(SerJson::serialize_json(&data), "[1,2]");
+
+        data.encode(Some(&LayerEncoding::Base64), None, -1).unwrap();
+        assert_eq!(SerJson::serialize_json(&data), r#""AQAAAAIAAAA=""#);
+
+        let data: LayerData = DeJson::deserialize_json("[3,4]").unwrap();
+        assert_eq!(*data, vec![3, 4]);
+    }
+
+    #[test]
+    fn compressed_round_trip() {
+        let tiles: Vec<u32> = (0..256).map(|gid| gid % 7).collect();
+
+        for compression in [Compression::Zlib, Compression::Gzip, Compression::Zstd] {
+            for level in [-1, 0, 9] {
+                let encoded = encode_base64(&tiles, Some(&compression), level).unwrap();
+                let decoded = decode_base64(&encoded, Some(&compression)).unwrap();
+
+                assert_eq!(decoded, tiles, "{:?} level {}", compression, level);
+            }
+        }
+    }
+
+    #[test]
+    fn compressed_data_is_decoded() {
+        // A 4x2 layer, compressed by another encoder
+        let zlib = "eJxjZGBgYAJiZiBmAWJWIGYDYnYg5gBiAAIAACU=";
+        let gzip = "H4sIAAAAAAACA2NkYGBgAmJmIGYBYlYgZgNidiDmAGIATLFdpyAAAAA=";
+
+        let tiles = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        assert_eq!(
+            decode_base64(zlib, Some(&Compression::Zlib)).unwrap(),
+            tiles
+        );
+        assert_eq!(
+            decode_base64(gzip, Some(&Compression::Gzip)).unwrap(),
+            tiles
+        );
+    }
+
+    #[test]
+    fn invalid_compressed_data_is_an_error() {
+        let encoded = encode_base64(&[1, 2], None, -1).unwrap();
+
+        assert!(matches!(
+            decode_base64(&encoded, Some(&Compression::Zstd)),
+            Err(Error::InvalidLayerData { .. })
+        ));
+    }
+}
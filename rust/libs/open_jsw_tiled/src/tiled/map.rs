@@ -2,7 +2,18 @@
 
 use nanoserde::{DeJson, SerJson};
 
-use super::{MAP_VERSION, TILED_VERSION, layer::Layer, property::Property, tileset::Tileset};
+use super::{
+    MAP_VERSION, TILED_VERSION,
+    chunk::CHUNK_SIZE,
+    gid::Gid,
+    json_value::UnknownFields,
+    layer::{Layer, LayerType},
+    object::Object,
+    property::Property,
+    tileset::{MapTilesets, Tileset},
+    typed_layer::TypedLayer,
+};
+use crate::Result;
 
 const DEFAULT_MINUS_ONE_I32: i32 = -1;
 
@@ -67,7 +78,8 @@ pub struct Map {
     /// Map grid tile height.
     pub tileheight: u32,
 
-    /// Array of tilesets.
+    /// Array of tilesets. External tilesets are written as their `firstgid` and `source` only.
+    #[nserde(proxy = "MapTilesets")]
     pub tilesets: Vec<Tileset>,
 
     /// Map grid tile width.
@@ -82,6 +94,10 @@ pub struct Map {
 
     /// Number of tile columns.
     pub width: u32,
+
+    /// Fields read from JSON which are not modelled, written back as they were read.
+    #[nserde(skip)]
+    pub unknown_fields: UnknownFields,
 }
 
 #[derive(Clone, Debug, Default, DeJson, SerJson)]
@@ -173,4 +189,398 @@ impl Map {
         self.nextobjectid += 1;
         id
     }
+
+    /// Returns the layers of the map typed by their `type`, or an error if a layer is missing a
+    /// field required by its type.
+    pub fn typed_layers(&self) -> Result<Vec<TypedLayer>> {
+        self.layers
+            .iter()
+            .cloned()
+            .map(TypedLayer::try_from)
+            .collect()
+    }
+
+    /// Returns the layer at a path of layer names separated by slashes, such as
+    /// `"The Bathroom/Foreground 1"` for a layer in a group. If sibling layers share a name, the
+    /// first one is returned.
+    pub fn layer(&self, path: &str) -> Option<&Layer> {
+        find_layer_path(&self.layers, path)?.pop()
+    }
+
+    /// Returns the layer at a path of layer names, as [`Map::layer`] does.
+    pub fn layer_mut(&mut self, path: &str) -> Option<&mut Layer> {
+        let (layers, name) = match path.rsplit_once('/') {
+            Some((parent, name)) => (self.layer_mut(parent)?.layers.as_mut()?, name),
+            None => (&mut self.layers, path),
+        };
+        layers.iter_mut().find(|layer| layer.name == name)
+    }
+
+    /// Returns all the layers of the map, with each group followed by its layers.
+    pub fn all_layers(&self) -> Vec<&Layer> {
+        fn collect<'a>(layers: &'a [Layer], all: &mut Vec<&'a Layer>) {
+            for layer in layers {
+                all.push(layer);
+                collect(layer.layers.as_deref().unwrap_or_default(), all);
+            }
+        }
+
+        let mut all = Vec::new();
+        collect(&self.layers, &mut all);
+        all
+    }
+
+    /// Returns the GID at `(x, y)` in tiles of the tile layer at `path`, as [`Layer::tile`]
+    /// does.
+    pub fn tile(&self, path: &str, x: i32, y: i32) -> Option<u32> {
+        self.layer(path)?.tile(x, y)
+    }
+
+    /// Returns the GID at `(x, y)` in pixels of the tile layer at `path`, taking the offsets of
+    /// the layer and of its groups into account. Only orthogonal maps are supported.
+    pub fn tile_at_pixel(&self, path: &str, x: f64, y: f64) -> Option<u32> {
+        let layers = find_layer_path(&self.layers, path)?;
+        let (offsetx, offsety) = layers.iter().fold((0.0, 0.0), |(x, y), layer| {
+            (x + layer.offsetx, y + layer.offsety)
+        });
+
+        let tile_x = ((x - offsetx) / self.tilewidth as f64).floor() as i32;
+        let tile_y = ((y - offsety) / self.tileheight as f64).floor() as i32;
+        layers.last()?.tile(tile_x, tile_y)
+    }
+
+    /// Returns all the objects of the object layers of the map, including those in groups.
+    pub fn objects(&self) -> impl Iterator<Item = &Object> {
+        self.all_layers()
+            .into_iter()
+            .flat_map(|layer| layer.objects.iter().flatten())
+    }
+
+    /// Returns the objects whose bounds overlap the rectangle at `(x, y)` of `width` x `height`
+    /// pixels. Points, and the lines of objects with no width or height, overlap the rectangle
+    /// if they are inside it. See [`Map::object_bounds`].
+    pub fn objects_in_rect(
+        &self,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    ) -> impl Iterator<Item = &Object> {
+        self.offset_objects()
+            .into_iter()
+            .filter(move |(object, offset)| {
+                let (object_x, object_y, object_width, object_height) =
+                    offset_bounds(object, *offset);
+                overlaps(object_x, object_width, x, width)
+                    && overlaps(object_y, object_height, y, height)
+            })
+            .map(|(object, _)| object)
+    }
+
+    /// Returns the bounds of the object with an ID in pixels, as [`Object::bounds`] does, taking
+    /// the offsets of its layer and of its groups into account.
+    pub fn object_bounds(&self, id: u32) -> Option<(f64, f64, f64, f64)> {
+        self.offset_objects()
+            .into_iter()
+            .find(|(object, _)| object.id == id)
+            .map(|(object, offset)| offset_bounds(object, offset))
+    }
+
+    /// Returns all the objects of the map, with the sum of the offsets of their layer and groups.
+    fn offset_objects(&self) -> Vec<(&Object, (f64, f64))> {
+        fn collect<'a>(
+            layers: &'a [Layer],
+            (x, y): (f64, f64),
+            all: &mut Vec<(&'a Object, (f64, f64))>,
+        ) {
+            for layer in layers {
+                let offset = (x + layer.offsetx, y + layer.offsety);
+                all.extend(
+                    layer
+                        .objects
+                        .iter()
+                        .flatten()
+                        .map(|object| (object, offset)),
+                );
+                collect(layer.layers.as_deref().unwrap_or_default(), offset, all);
+            }
+        }
+
+        let mut all = Vec::new();
+        collect(&self.layers, (0.0, 0.0), &mut all);
+        all
+    }
+
+    /// Returns the object with an ID, in any object layer.
+    pub fn object(&self, id: u32) -> Option<&Object> {
+        self.objects().find(|object| object.id == id)
+    }
+
+    /// Returns the object with an ID, in any object layer.
+    pub fn object_mut(&mut self, id: u32) -> Option<&mut Object> {
+        find_object_mut(&mut self.layers, id)
+    }
+
+    /// Returns the tileset of a tile, and the local ID of the tile in it, ignoring the flags of
+    /// the GID. Returns `None` for an empty tile, or a tile outside the tilesets.
+    pub fn tileset_for_gid(&self, gid: Gid) -> Option<(&Tileset, u32)> {
+        if gid.is_empty() {
+            return None;
+        }
+
+        let tileset = self
+            .tilesets
+            .iter()
+            .filter(|tileset| tileset.firstgid <= gid.id())
+            .max_by_key(|tileset| tileset.firstgid)?;
+        let id = gid.id() - tileset.firstgid;
+
+        // The tiles of a collection of images may have gaps in their IDs
+        let in_range = tileset.image.is_empty() || id < tileset.tilecount;
+        in_range.then_some((tileset, id))
+    }
+
+    /// Converts the map between infinite, with chunked tile layers, and fixed-size.
+    ///
+    /// A fixed-size map is sized to cover the tiles of all its layers, or keeps its size if there
+    /// are none. If some tiles were at negative coordinates, the objects are moved along with the
+    /// tiles.
+    pub fn set_infinite(&mut self, infinite: bool) {
+        if infinite == self.infinite {
+            return;
+        }
+        self.infinite = infinite;
+
+        if infinite {
+            for_each_layer(&mut self.layers, &mut |layer| {
+                layer.convert_to_chunks(CHUNK_SIZE, CHUNK_SIZE)
+            });
+            return;
+        }
+
+        let mut bounds: Option<(i64, i64, i64, i64)> = None;
+        for_each_layer(&mut self.layers, &mut |layer| {
+            if let Some((x, y, width, height)) = layer.tile_bounds() {
+                let (x, y) = (x as i64, y as i64);
+                let (right, bottom) = (x + width as i64, y + height as i64);
+                bounds = Some(match bounds {
+                    Some((l, t, r, b)) => (l.min(x), t.min(y), r.max(right), b.max(bottom)),
+                    None => (x, y, right, bottom),
+                });
+            }
+        });
+        let (left, top, right, bottom) =
+            bounds.unwrap_or((0, 0, self.width as i64, self.height as i64));
+        let (x, y) = (left as i32, top as i32);
+        let (width, height) = ((right - left) as u32, (bottom - top) as u32);
+
+        let (dx, dy) = (
+            -(x as f64) * self.tilewidth as f64,
+            -(y as f64) * self.tileheight as f64,
+        );
+        for_each_layer(&mut self.layers, &mut |layer| {
+            layer.convert_to_fixed(x, y, width, height);
+            if matches!(layer.typ, LayerType::ObjectGroup) {
+                for object in layer.objects.iter_mut().flatten() {
+                    object.x += dx;
+                    object.y += dy;
+                }
+            }
+        });
+        self.width = width;
+        self.height = height;
+    }
+}
+
+/// Calls `f` for each layer, including the layers in groups.
+pub(crate) fn for_each_layer(layers: &mut [Layer], f: &mut impl FnMut(&mut Layer)) {
+    for layer in layers {
+        f(layer);
+        if let Some(layers) = layer.layers.as_mut() {
+            for_each_layer(layers, f);
+        }
+    }
+}
+
+/// Returns the layers along a path of layer names, from the top-level layer to the layer at the
+/// path.
+fn find_layer_path<'a>(layers: &'a [Layer], path: &str) -> Option<Vec<&'a Layer>> {
+    let mut found = Vec::new();
+    let mut layers = layers;
+    for name in path.split('/') {
+        let layer = layers.iter().find(|layer| layer.name == name)?;
+        layers = layer.layers.as_deref().unwrap_or_default();
+        found.push(layer);
+    }
+    Some(found)
+}
+
+fn find_object_mut(layers: &mut [Layer], id: u32) -> Option<&mut Object> {
+    for layer in layers {
+        if let Some(object) = layer
+            .objects
+            .iter_mut()
+            .flatten()
+            .find(|object| object.id == id)
+        {
+            return Some(object);
+        }
+        if let Some(object) = find_object_mut(layer.layers.as_deref_mut().unwrap_or_default(), id) {
+            return Some(object);
+        }
+    }
+    None
+}
+
+/// Returns the bounds of an object moved by an offset.
+fn offset_bounds(object: &Object, (offsetx, offsety): (f64, f64)) -> (f64, f64, f64, f64) {
+    let (x, y, width, height) = object.bounds();
+    (x + offsetx, y + offsety, width, height)
+}
+
+/// Whether the span at `start` of `length` overlaps the span at `other` of `other_length`. A
+/// span of no length overlaps the other span if it starts inside it.
+fn overlaps(start: f64, length: f64, other: f64, other_length: f64) -> bool {
+    if length == 0.0 {
+        other <= start && start < other + other_length
+    } else {
+        start < other + other_length && other < start + length
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tiled::{builder::TilesetBuilder, object::Object};
+
+    #[test]
+    fn gids_are_looked_up_in_their_tileset() {
+        let mut map = Map::new(None, MapOrientation::Orthogonal, 4, 4, 8, 8);
+        let mut cells = TilesetBuilder::new("cells", 8, 8)
+            .image("cells.png", 42, 22)
+            .margin_and_spacing(1, 2)
+            .build()
+            .unwrap();
+        cells.firstgid = 1;
+        let mut items = TilesetBuilder::new("items", 8, 8)
+            .image("items.png", 16, 8)
+            .build()
+            .unwrap();
+        items.firstgid = 9;
+        map.tilesets = vec![cells, items];
+
+        let flipped = Gid::new(6, Gid::FLIPPED_HORIZONTALLY | Gid::FLIPPED_VERTICALLY);
+        let (tileset, id) = map.tileset_for_gid(flipped).unwrap();
+        assert_eq!((tileset.name.as_str(), id), ("cells", 5));
+        assert_eq!(tileset.tile_position(id), Some((11, 11)));
+
+        let (tileset, id) = map.tileset_for_gid(Gid(10)).unwrap();
+        assert_eq!((tileset.name.as_str(), id), ("items", 1));
+        assert!(map.tileset_for_gid(Gid(11)).is_none());
+        assert!(map.tileset_for_gid(Gid::EMPTY).is_none());
+    }
+
+    #[test]
+    fn infinite_maps_are_fixed_to_their_tiles() {
+        let mut map = Map::new(None, MapOrientation::Orthogonal, 4, 4, 8, 8);
+        map.set_infinite(true);
+        let mut tiles = Layer::new(&mut map, LayerType::TileLayer, "Tiles".to_string());
+        tiles.set_tile(-1, -1, 3).unwrap();
+        let mut objects = Layer::new(&mut map, LayerType::ObjectGroup, "Objects".to_string());
+        objects.objects = Some(vec![Object::new(
+            &mut map,
+            "Item".to_string(),
+            0.0,
+            0.0,
+            8.0,
+            8.0,
+        )]);
+        map.layers = vec![tiles, objects];
+
+        map.set_infinite(false);
+
+        assert_eq!((map.width, map.height), (16, 16));
+        assert_eq!(map.layers[0].tile(15, 15), Some(3));
+        let object = &map.layers[1].objects.as_ref().unwrap()[0];
+        assert_eq!((object.x, object.y), (128.0, 128.0));
+    }
+
+    fn room_map() -> Map {
+        crate::deserialize_map(
+            r#"{"tilewidth":8,"tileheight":8,"layers":[
+            {"id":1,"name":"The Bathroom","type":"group","offsetx":4,"layers":[
+            {"id":2,"name":"Foreground 1","type":"tilelayer","width":2,"height":2,
+            "offsety":8,"data":[1,2,3,4]},
+            {"id":3,"name":"Dynamic 1","type":"objectgroup","objects":[
+            {"id":1,"x":0,"y":0,"width":8,"height":8},
+            {"id":2,"x":16,"y":16,"gid":5,"width":8,"height":8},
+            {"id":3,"x":40,"y":0,"point":true},
+            {"id":4,"x":32,"y":0,"polyline":[{"x":0,"y":0},{"x":0,"y":8}]}]}]},
+            {"id":4,"name":"Foreground 1","type":"tilelayer","width":1,"height":1,"data":[9]}]}"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn layers_are_found_by_path() {
+        let mut map = room_map();
+
+        assert_eq!(map.layer("The Bathroom/Foreground 1").unwrap().id, 2);
+        assert_eq!(map.layer("Foreground 1").unwrap().id, 4);
+        assert!(map.layer("The Bathroom/Missing").is_none());
+        assert!(map.layer("Foreground 1/Dynamic 1").is_none());
+        assert_eq!(map.all_layers().len(), 4);
+
+        map.layer_mut("The Bathroom/Dynamic 1").unwrap().name = "Objects".to_string();
+        assert_eq!(map.layer("The Bathroom/Objects").unwrap().id, 3);
+    }
+
+    #[test]
+    fn tiles_are_found_by_tile_and_pixel() {
+        let map = room_map();
+
+        assert_eq!(map.tile("The Bathroom/Foreground 1", 1, 0), Some(2));
+        assert_eq!(map.tile("The Bathroom/Dynamic 1", 0, 0), None);
+        // Offset by the group horizontally and by the layer vertically
+        assert_eq!(
+            map.tile_at_pixel("The Bathroom/Foreground 1", 12.0, 8.0),
+            Some(2)
+        );
+        assert_eq!(
+            map.tile_at_pixel("The Bathroom/Foreground 1", 3.0, 8.0),
+            None
+        );
+        assert_eq!(
+            map.tile_at_pixel("The Bathroom/Foreground 1", 4.0, 23.9),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn objects_are_found_by_area_and_id() {
+        let mut map = room_map();
+
+        let ids = |x, y, width, height| {
+            map.objects_in_rect(x, y, width, height)
+                .map(|object| object.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(0.0, 0.0, 16.0, 16.0), [1]);
+        // Offset by the group horizontally
+        assert_eq!(ids(0.0, 0.0, 4.0, 16.0), []);
+        assert_eq!(ids(44.0, 0.0, 1.0, 1.0), [3]);
+        assert_eq!(ids(40.0, 0.0, 4.0, 1.0), []);
+        // Tile objects are aligned to their bottom-left corner
+        assert_eq!(ids(16.0, 8.0, 8.0, 8.0), [2]);
+        assert_eq!(ids(16.0, 16.0, 8.0, 8.0), []);
+        assert_eq!(ids(32.0, 4.0, 16.0, 16.0), [4]);
+        assert_eq!(ids(32.0, 0.0, 16.0, 16.0), [3, 4]);
+        assert_eq!(map.object_bounds(2), Some((20.0, 8.0, 8.0, 8.0)));
+        assert_eq!(map.object(2).unwrap().bounds(), (16.0, 8.0, 8.0, 8.0));
+
+        assert_eq!(map.object(2).unwrap().gid, Some(Gid(5)));
+        map.object_mut(4).unwrap().name = "Rope".to_string();
+        assert_eq!(map.object(4).unwrap().name, "Rope");
+        assert!(map.object(5).is_none());
+    }
 }
@@ -2,7 +2,7 @@
 
 use nanoserde::{DeJson, SerJson};
 
-use super::{map::Map, point::Point, property::Property, text::Text};
+use super::{gid::Gid, map::Map, point::Point, property::Property, text::Text};
 
 /// Represents a Layer in the map.
 #[derive(Clone, Debug, Default, DeJson, SerJson)]
@@ -12,10 +12,10 @@ pub struct Object {
     pub ellipse: Option<bool>,
 
     /// Global tile ID, only if the object represents a tile.
-    pub gid: Option<i32>,
+    pub gid: Option<Gid>,
 
     /// Height in pixels.
-    pub height: u32,
+    pub height: f64,
 
     /// Incremental ID, unique across all objects.
     pub id: u32,
@@ -49,11 +49,13 @@ pub struct Object {
     #[nserde(rename = "class")]
     pub class: Option<String>,
 
-    /// Whether the object is shown in the editor.
+    /// Whether the object is shown in the editor (not written for template instances which do
+    /// not hide it).
+    #[nserde(default = "true")]
     pub visible: bool,
 
     /// Width in pixels.
-    pub width: u32,
+    pub width: f64,
 
     /// X coordinate in pixels.
     pub x: f64,
@@ -63,7 +65,7 @@ pub struct Object {
 }
 
 impl Object {
-    pub fn new(map: &mut Map, name: String, x: f64, y: f64, width: u32, height: u32) -> Self {
+    pub fn new(map: &mut Map, name: String, x: f64, y: f64, width: f64, height: f64) -> Self {
         let id = map.next_object_id();
 
         Self {
@@ -76,4 +78,33 @@ impl Object {
             ..Default::default()
         }
     }
+
+    /// Returns the bounding box of the object as `(x, y, width, height)` in pixels, relative to
+    /// its layer and ignoring its rotation. [`super::map::Map::object_bounds`] adds the offsets
+    /// of the layer and its groups.
+    ///
+    /// Tile objects are aligned to their bottom-left corner, and the points of polygons and
+    /// polylines are relative to the position of the object. Points have a size of 0.
+    pub fn bounds(&self) -> (f64, f64, f64, f64) {
+        if self.gid.is_some() {
+            return (self.x, self.y - self.height, self.width, self.height);
+        }
+
+        if let Some(points) = self.polygon.as_ref().or(self.polyline.as_ref()) {
+            let (mut left, mut top) = (f64::INFINITY, f64::INFINITY);
+            let (mut right, mut bottom) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+            for point in points {
+                left = left.min(point.x);
+                top = top.min(point.y);
+                right = right.max(point.x);
+                bottom = bottom.max(point.y);
+            }
+            if points.is_empty() {
+                return (self.x, self.y, 0.0, 0.0);
+            }
+            return (self.x + left, self.y + top, right - left, bottom - top);
+        }
+
+        (self.x, self.y, self.width, self.height)
+    }
 }
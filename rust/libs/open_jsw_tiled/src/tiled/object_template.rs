@@ -2,7 +2,7 @@
 
 use nanoserde::{DeJson, SerJson};
 
-use super::{object::Object, tileset::Tileset};
+use super::{gid::Gid, object::Object, tileset::Tileset};
 
 /// Represents a Template in Tiled.
 #[derive(Clone, Debug, Default, DeJson, SerJson)]
@@ -18,3 +18,65 @@ pub struct ObjectTemplate {
     /// The object instantiated by this template.
     pub object: Object,
 }
+
+impl ObjectTemplate {
+    /// Merges the template into an object instantiating it, as Tiled does: the object keeps what
+    /// it overrides and takes everything else from the template.
+    ///
+    /// Tiled only writes the overridden fields of an instance, so a field counts as overridden
+    /// when it is set (a name, a size, a rotation or a shape). Properties are merged by name, the
+    /// values of the instance replacing those of the template. The GID is left to
+    /// [`ObjectTemplate::map_gid`], as it depends on the tilesets of the map.
+    pub fn apply(&self, object: &mut Object) {
+        let template = &self.object;
+
+        if object.name.is_empty() {
+            object.name = template.name.clone();
+        }
+        if object.class.is_none() {
+            object.class = template.class.clone();
+        }
+        if object.width == 0.0 && object.height == 0.0 {
+            object.width = template.width;
+            object.height = template.height;
+        }
+        if object.rotation == 0.0 {
+            object.rotation = template.rotation;
+        }
+        object.visible &= template.visible;
+
+        let has_shape = object.ellipse.is_some()
+            || object.point.is_some()
+            || object.polygon.is_some()
+            || object.polyline.is_some()
+            || object.text.is_some();
+        if !has_shape {
+            object.ellipse = template.ellipse;
+            object.point = template.point;
+            object.polygon = template.polygon.clone();
+            object.polyline = template.polyline.clone();
+            object.text = template.text.clone();
+        }
+
+        if let Some(template_properties) = &template.properties {
+            let mut properties = template_properties.clone();
+            for property in object.properties.take().into_iter().flatten() {
+                match properties.iter_mut().find(|p| p.name == property.name) {
+                    Some(existing) => *existing = property,
+                    None => properties.push(property),
+                }
+            }
+            object.properties = Some(properties);
+        }
+    }
+
+    /// Returns the GID of a tile template in a map where the tileset of the template starts at
+    /// `firstgid`, keeping the flip flags, or `None` if the template is not a tile.
+    pub fn map_gid(&self, firstgid: u32) -> Option<Gid> {
+        let gid = self.object.gid?;
+        let template_firstgid = self.tileset.as_ref().map_or(1, |tileset| tileset.firstgid);
+        let id = gid.id().checked_sub(template_firstgid)?;
+
+        Some(Gid::new(firstgid + id, gid.flags()))
+    }
+}
@@ -5,8 +5,8 @@ use nanoserde::{DeJson, SerJson};
 #[nserde(default)]
 pub struct Point {
     /// X coordinate in pixels.
-    pub x: i32,
+    pub x: f64,
 
     /// Y coordinate in pixels.
-    pub y: i32,
+    pub y: f64,
 }
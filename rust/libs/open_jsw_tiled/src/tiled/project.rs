@@ -0,0 +1,401 @@
+#![allow(clippy::question_mark)]
+
+use std::collections::BTreeMap;
+
+use nanoserde::{DeJson, SerJson};
+
+use super::{
+    layer::Layer,
+    map::Map,
+    property::{Property, PropertyVal, property_type},
+};
+use crate::{Error, Result};
+
+/// The version of Tiled whose features a project is limited to (1.10).
+const COMPATIBILITY_VERSION: u32 = 1100;
+
+/// https://doc.mapeditor.org/en/stable/manual/projects/
+/// Represents a Tiled project (a .tiled-project file), with the custom property types used by
+/// its maps.
+#[derive(Clone, Debug, DeJson, SerJson)]
+#[nserde(default)]
+pub struct Project {
+    /// Path to the automapping rules file, relative to the project.
+    #[nserde(rename = "automappingRulesFile")]
+    pub automapping_rules_file: String,
+
+    /// Array of commands which can be run from the editor.
+    pub commands: Vec<Command>,
+
+    /// The version of Tiled whose features the project is limited to (since 1.9).
+    #[nserde(rename = "compatibilityVersion")]
+    pub compatibility_version: u32,
+
+    /// Path to the directory of the scripted extensions, relative to the project.
+    #[nserde(rename = "extensionsPath")]
+    pub extensions_path: String,
+
+    /// Array of the directories of the project, relative to the project.
+    pub folders: Vec<String>,
+
+    /// Array of the properties of the project (since 1.10).
+    pub properties: Vec<Property>,
+
+    /// Array of the custom property types of the project (since 1.8).
+    #[nserde(rename = "propertyTypes")]
+    pub property_types: Vec<PropertyType>,
+}
+
+/// A command which can be run from the editor.
+#[derive(Clone, Debug, Default, DeJson, SerJson)]
+#[nserde(default)]
+pub struct Command {
+    pub arguments: String,
+    pub command: String,
+    pub enabled: bool,
+    pub executable: String,
+    pub name: String,
+    #[nserde(rename = "saveBeforeExecution")]
+    pub save_before_execution: bool,
+    pub shortcut: String,
+    #[nserde(rename = "showOutput")]
+    pub show_output: bool,
+    #[nserde(rename = "workingDirectory")]
+    pub working_directory: String,
+}
+
+/// https://doc.mapeditor.org/en/stable/manual/custom-properties/#custom-types
+/// A custom property type: an enum, or a class with members.
+#[derive(Clone, Debug, Default, DeJson, SerJson)]
+#[nserde(default)]
+pub struct PropertyType {
+    /// Unique ID of the type in the project.
+    pub id: u32,
+
+    /// Name of the type, which properties refer to as their `propertytype`.
+    pub name: String,
+
+    /// "enum" or "class".
+    #[nserde(rename = "type")]
+    pub typ: PropertyTypeKind,
+
+    /// How the values of an enum are stored: "string" or "int" (enum only).
+    #[nserde(rename = "storageType")]
+    pub storage_type: Option<String>,
+
+    /// The values of an enum (enum only).
+    pub values: Option<Vec<String>>,
+
+    /// Whether several values of an enum can be set, as bit flags when stored as "int" (enum
+    /// only).
+    #[nserde(rename = "valuesAsFlags")]
+    pub values_as_flags: Option<bool>,
+
+    /// Hex-formatted color of objects of the class (#RRGGBB or #AARRGGBB, class only).
+    pub color: Option<String>,
+
+    /// Whether objects of the class are drawn filled (class only).
+    #[nserde(rename = "drawFill")]
+    pub draw_fill: Option<bool>,
+
+    /// The members of a class, with their default values (class only).
+    pub members: Option<Vec<Property>>,
+
+    /// What a class can be used as: "property", "map", "layer", "object", "tile", "tileset",
+    /// "wangcolor", "wangset" or "project" (class only).
+    #[nserde(rename = "useAs")]
+    pub use_as: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, DeJson, SerJson)]
+pub enum PropertyTypeKind {
+    #[default]
+    #[nserde(rename = "class")]
+    Class,
+    #[nserde(rename = "enum")]
+    Enum,
+}
+
+impl Default for Project {
+    fn default() -> Self {
+        Self {
+            automapping_rules_file: String::new(),
+            commands: Vec::new(),
+            compatibility_version: COMPATIBILITY_VERSION,
+            extensions_path: "extensions".to_string(),
+            folders: vec![".".to_string()],
+            properties: Vec::new(),
+            property_types: Vec::new(),
+        }
+    }
+}
+
+impl Project {
+    /// Returns the custom property type with a name.
+    pub fn property_type(&self, name: &str) -> Option<&PropertyType> {
+        self.property_types.iter().find(|typ| typ.name == name)
+    }
+
+    /// Adds an enum of values stored as strings.
+    pub fn add_enum(&mut self, name: &str, values: &[&str]) {
+        let id = self.next_property_type_id();
+        self.property_types.push(PropertyType {
+            id,
+            name: name.to_string(),
+            typ: PropertyTypeKind::Enum,
+            storage_type: Some(property_type::STRING.to_string()),
+            values: Some(values.iter().map(|value| value.to_string()).collect()),
+            values_as_flags: Some(false),
+            ..Default::default()
+        });
+    }
+
+    /// Adds a class, used as `use_as` (e.g. "object"), with the default values of its members.
+    pub fn add_class(&mut self, name: &str, use_as: &[&str], members: Vec<Property>) {
+        let id = self.next_property_type_id();
+        self.property_types.push(PropertyType {
+            id,
+            name: name.to_string(),
+            typ: PropertyTypeKind::Class,
+            color: Some("#ffa0a0a4".to_string()),
+            draw_fill: Some(true),
+            members: Some(members),
+            use_as: Some(use_as.iter().map(|use_as| use_as.to_string()).collect()),
+            ..Default::default()
+        });
+    }
+
+    fn next_property_type_id(&self) -> u32 {
+        self.property_types
+            .iter()
+            .map(|typ| typ.id)
+            .max()
+            .unwrap_or(0)
+            + 1
+    }
+
+    /// Checks the properties of a map, its layers, objects, tilesets and tiles which refer to a
+    /// custom property type, and fills the members missing from class values with their
+    /// defaults.
+    ///
+    /// Returns an error for a property of an unknown type, an enum value which is not one of
+    /// the values of the enum, or a class property whose value is not a class.
+    pub fn apply_property_types(&self, map: &mut Map) -> Result<()> {
+        self.apply_to(map.properties.iter_mut().flatten())?;
+        for layer in &mut map.layers {
+            self.apply_to_layer(layer)?;
+        }
+        for tileset in &mut map.tilesets {
+            self.apply_to(tileset.properties.iter_mut().flatten())?;
+            for tile in tileset.tiles.iter_mut().flatten() {
+                self.apply_to(tile.properties.iter_mut().flatten())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply_to_layer(&self, layer: &mut Layer) -> Result<()> {
+        self.apply_to(layer.properties.iter_mut())?;
+        for object in layer.objects.iter_mut().flatten() {
+            self.apply_to(object.properties.iter_mut().flatten())?;
+        }
+        for layer in layer.layers.iter_mut().flatten() {
+            self.apply_to_layer(layer)?;
+        }
+
+        Ok(())
+    }
+
+    fn apply_to<'a>(&self, properties: impl Iterator<Item = &'a mut Property>) -> Result<()> {
+        for property in properties {
+            if let Some(name) = property.propertytype.clone() {
+                self.apply_type(&property.name, &name, &mut property.value)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks a value of a custom type, and fills in the missing members of a class value.
+    fn apply_type(&self, property: &str, type_name: &str, value: &mut PropertyVal) -> Result<()> {
+        let invalid = |message: String| Error::InvalidPropertyValue {
+            property: property.to_string(),
+            message,
+        };
+        let typ = self
+            .property_type(type_name)
+            .ok_or_else(|| Error::UnknownPropertyType {
+                name: type_name.to_string(),
+            })?;
+
+        match typ.typ {
+            PropertyTypeKind::Enum => {
+                let values = typ.values.as_deref().unwrap_or_default();
+                let valid = match value {
+                    PropertyVal::String(value) if typ.values_as_flags == Some(true) => value
+                        .split(',')
+                        .filter(|value| !value.is_empty())
+                        .all(|value| values.iter().any(|v| v == value)),
+                    PropertyVal::String(value) => values.iter().any(|v| v == value),
+                    PropertyVal::Integer(value) if typ.values_as_flags == Some(true) => {
+                        *value >= 0 && (*value as u64) < 1u64 << values.len().min(63)
+                    }
+                    PropertyVal::Integer(value) => *value >= 0 && (*value as usize) < values.len(),
+                    _ => false,
+                };
+                if !valid {
+                    return Err(invalid(format!(
+                        "{:?} is not a value of {}",
+                        value, typ.name
+                    )));
+                }
+            }
+            PropertyTypeKind::Class => {
+                let PropertyVal::Class(value) = value else {
+                    return Err(invalid(format!("{:?} is not a {}", value, typ.name)));
+                };
+                for member in typ.members.iter().flatten() {
+                    self.apply_member(value, member)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fills a member of a class value with its default value if it is missing, and converts it
+    /// to the type of the member.
+    fn apply_member(
+        &self,
+        value: &mut BTreeMap<String, PropertyVal>,
+        member: &Property,
+    ) -> Result<()> {
+        let member_value = value
+            .entry(member.name.clone())
+            .or_insert_with(|| member.value.clone());
+        *member_value = std::mem::take(member_value).into_type(&member.typ);
+
+        if let Some(type_name) = &member.propertytype {
+            self.apply_type(&member.name, type_name, member_value)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{deserialize_map, deserialize_project, serialize_project};
+
+    fn project() -> Project {
+        let mut project = Project::default();
+        project.add_enum("direction", &["left", "right"]);
+        project.add_class(
+            "bounds",
+            &["property"],
+            vec![
+                Property::new("min", PropertyVal::Integer(0)),
+                Property::new("max", PropertyVal::Integer(248)),
+            ],
+        );
+        project.add_class(
+            "guardian",
+            &["property", "object"],
+            vec![
+                Property::new("speed", PropertyVal::Integer(2)),
+                Property::new("direction", PropertyVal::String("right".into()))
+                    .with_property_type("direction"),
+                Property::new("bounds", PropertyVal::Class(BTreeMap::new()))
+                    .with_property_type("bounds"),
+            ],
+        );
+        project
+    }
+
+    #[test]
+    fn the_repository_project_is_read() {
+        let json = include_str!("../../../../../tiled/open-jsw.tiled-project");
+
+        let project = deserialize_project(json).unwrap();
+
+        assert_eq!(project.folders, ["."]);
+        assert_eq!(project.compatibility_version, 1100);
+        assert!(project.property_types.is_empty());
+    }
+
+    #[test]
+    fn property_types_round_trip() {
+        let json = serialize_project(&project()).unwrap();
+        let project = deserialize_project(&json).unwrap();
+
+        let direction = project.property_type("direction").unwrap();
+        assert_eq!(direction.typ, PropertyTypeKind::Enum);
+        assert_eq!(direction.values.as_deref().unwrap(), ["left", "right"]);
+        let guardian = project.property_type("guardian").unwrap();
+        assert_eq!(guardian.id, 3);
+        assert_eq!(
+            guardian.members.as_ref().unwrap()[0].value,
+            PropertyVal::Integer(2)
+        );
+        assert_eq!(
+            guardian.members.as_ref().unwrap()[1]
+                .propertytype
+                .as_deref(),
+            Some("direction")
+        );
+    }
+
+    #[test]
+    fn class_members_default_from_the_project() {
+        let mut map = deserialize_map(
+            r#"{"layers":[{"id":1,"name":"Objects","type":"objectgroup","objects":[
+            {"id":1,"properties":[{"name":"monk","type":"class","propertytype":"guardian",
+            "value":{"speed":4,"bounds":{"max":64}}}]}]}]}"#,
+        )
+        .unwrap();
+
+        project().apply_property_types(&mut map).unwrap();
+
+        let objects = map.layers[0].objects.as_ref().unwrap();
+        let PropertyVal::Class(monk) = &objects[0].properties.as_ref().unwrap()[0].value else {
+            panic!("Expected class value");
+        };
+        assert_eq!(monk["speed"], PropertyVal::Integer(4));
+        assert_eq!(monk["direction"], PropertyVal::String("right".into()));
+        let PropertyVal::Class(bounds) = &monk["bounds"] else {
+            panic!("Expected nested class value");
+        };
+        assert_eq!(bounds["min"], PropertyVal::Integer(0));
+        assert_eq!(bounds["max"], PropertyVal::Integer(64));
+    }
+
+    #[test]
+    fn invalid_values_are_errors() {
+        let map = |property: &str| {
+            deserialize_map(&format!(
+                r#"{{"layers":[{{"id":1,"name":"Room","type":"group","properties":[{}]}}]}}"#,
+                property
+            ))
+            .unwrap()
+        };
+
+        let unknown = r#"{"name":"a","type":"class","propertytype":"monk","value":{}}"#;
+        assert!(matches!(
+            project().apply_property_types(&mut map(unknown)),
+            Err(Error::UnknownPropertyType { .. })
+        ));
+        let bad_enum = r#"{"name":"d","type":"string","propertytype":"direction","value":"up"}"#;
+        assert!(matches!(
+            project().apply_property_types(&mut map(bad_enum)),
+            Err(Error::InvalidPropertyValue { .. })
+        ));
+        let nested = r#"{"name":"g","type":"class","propertytype":"guardian",
+            "value":{"direction":"down"}}"#;
+        assert!(matches!(
+            project().apply_property_types(&mut map(nested)),
+            Err(Error::InvalidPropertyValue { ref property, .. }) if property == "direction"
+        ));
+    }
+}
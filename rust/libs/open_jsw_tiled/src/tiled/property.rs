@@ -1,3 +1,7 @@
+#![allow(clippy::question_mark)]
+
+use std::collections::BTreeMap;
+
 use nanoserde::{DeJson, SerJson};
 
 pub mod property_type {
@@ -8,24 +12,152 @@ pub mod property_type {
     pub const COLOR: &str = "color";
     pub const FILE: &str = "file";
     pub const OBJECT: &str = "object";
+    pub const CLASS: &str = "class";
     pub const ARRAY: &str = "array";
 }
 
+/// https://doc.mapeditor.org/en/stable/reference/json-map-format/#property
+/// Represents a custom Property in Tiled.
+///
+/// The value is converted to the variant matching `type` when deserialized, so the type is kept
+/// through a deserialize / serialize round-trip (e.g. a float of `1` stays a float).
 #[derive(Clone, Debug, Default, DeJson, SerJson)]
+#[nserde(proxy = "RawProperty")]
 pub struct Property {
+    /// Name of the property.
     pub name: String,
+
+    /// Value of the property.
     pub value: PropertyVal,
-    #[nserde(rename = "type")]
+
+    /// Type of the property: "string" (default), "int", "float", "bool", "color", "file",
+    /// "object" or "class" (since 0.16, with "color" in 0.17, "file" in 1.0, "object" in 1.4 and
+    /// "class" in 1.8).
     pub typ: String,
+
+    /// Name of the custom property type, when applicable (since 1.8).
+    pub propertytype: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+/// The JSON representation of a [`Property`], before the value is converted to its type.
+#[derive(Clone, Debug, Default, DeJson, SerJson)]
+#[nserde(default)]
+struct RawProperty {
+    name: String,
+    #[nserde(rename = "type")]
+    typ: Option<String>,
+    propertytype: Option<String>,
+    value: PropertyVal,
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum PropertyVal {
     String(String),
     UInt(u64),
     Integer(i64),
     Float(f64),
     Boolean(bool),
+    /// Hex-formatted color (#RRGGBB or #AARRGGBB).
+    Color(String),
+    /// Path to a file, relative to the file containing the property.
+    File(String),
+    /// ID of the referenced object, or 0 for no object.
+    Object(u32),
+    /// Member values of a class property, which may contain nested classes.
+    Class(BTreeMap<String, PropertyVal>),
+}
+
+impl Property {
+    /// Creates a property, with the Tiled type derived from the value.
+    pub fn new(name: &str, value: PropertyVal) -> Self {
+        Self {
+            name: name.to_string(),
+            typ: value.property_type().to_string(),
+            value,
+            propertytype: None,
+        }
+    }
+
+    /// Sets the name of the custom property type (required for class and enum properties).
+    pub fn with_property_type(mut self, propertytype: &str) -> Self {
+        self.propertytype = Some(propertytype.to_string());
+        self
+    }
+}
+
+impl From<&RawProperty> for Property {
+    fn from(raw: &RawProperty) -> Self {
+        let typ = raw
+            .typ
+            .clone()
+            .unwrap_or_else(|| property_type::STRING.to_string());
+
+        Self {
+            name: raw.name.clone(),
+            value: raw.value.clone().into_type(&typ),
+            typ,
+            propertytype: raw.propertytype.clone(),
+        }
+    }
+}
+
+impl From<&Property> for RawProperty {
+    fn from(property: &Property) -> Self {
+        Self {
+            name: property.name.clone(),
+            typ: Some(property.typ.clone()),
+            propertytype: property.propertytype.clone(),
+            value: property.value.clone(),
+        }
+    }
+}
+
+impl PropertyVal {
+    /// Returns the Tiled property type of the value.
+    pub fn property_type(&self) -> &'static str {
+        match self {
+            PropertyVal::String(_) => property_type::STRING,
+            PropertyVal::UInt(_) | PropertyVal::Integer(_) => property_type::INT,
+            PropertyVal::Float(_) => property_type::FLOAT,
+            PropertyVal::Boolean(_) => property_type::BOOL,
+            PropertyVal::Color(_) => property_type::COLOR,
+            PropertyVal::File(_) => property_type::FILE,
+            PropertyVal::Object(_) => property_type::OBJECT,
+            PropertyVal::Class(_) => property_type::CLASS,
+        }
+    }
+
+    /// Converts a value, as read from JSON, to the variant for a Tiled property type.
+    ///
+    /// JSON does not distinguish colors and files from strings, objects from integers, or
+    /// integral floats from integers. Values which cannot be converted are returned unchanged.
+    pub fn into_type(self, typ: &str) -> Self {
+        match (typ, self) {
+            (property_type::INT, PropertyVal::UInt(x)) => match i64::try_from(x) {
+                Ok(x) => PropertyVal::Integer(x),
+                Err(_) => PropertyVal::UInt(x),
+            },
+            (property_type::INT, PropertyVal::Float(x)) if x.fract() == 0.0 => {
+                PropertyVal::Integer(x as i64)
+            }
+            (property_type::FLOAT, PropertyVal::UInt(x)) => PropertyVal::Float(x as f64),
+            (property_type::FLOAT, PropertyVal::Integer(x)) => PropertyVal::Float(x as f64),
+            (property_type::COLOR, PropertyVal::String(x)) => PropertyVal::Color(x),
+            (property_type::FILE, PropertyVal::String(x)) => PropertyVal::File(x),
+            (property_type::OBJECT, PropertyVal::UInt(x)) => match u32::try_from(x) {
+                Ok(x) => PropertyVal::Object(x),
+                Err(_) => PropertyVal::UInt(x),
+            },
+            (property_type::OBJECT, PropertyVal::Integer(x)) => match u32::try_from(x) {
+                Ok(x) => PropertyVal::Object(x),
+                Err(_) => PropertyVal::Integer(x),
+            },
+            (property_type::STRING, PropertyVal::Color(x) | PropertyVal::File(x)) => {
+                PropertyVal::String(x)
+            }
+            (_, value) => value,
+        }
+    }
 }
 
 impl Default for PropertyVal {
@@ -42,6 +174,19 @@ impl std::fmt::Display for PropertyVal {
             PropertyVal::Integer(x) => write!(f, "{}", x),
             PropertyVal::Float(x) => write!(f, "{}", x),
             PropertyVal::Boolean(x) => write!(f, "{}", x),
+            PropertyVal::Color(x) => write!(f, "{}", x),
+            PropertyVal::File(x) => write!(f, "{}", x),
+            PropertyVal::Object(x) => write!(f, "{}", x),
+            PropertyVal::Class(members) => {
+                write!(f, "{{")?;
+                for (i, (name, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", name, value)?;
+                }
+                write!(f, "}}")
+            }
         }
     }
 }
@@ -58,11 +203,24 @@ impl DeJson for PropertyVal {
             DeJsonTok::U64(x) => PropertyVal::UInt(x),
             DeJsonTok::I64(x) => PropertyVal::Integer(x),
             DeJsonTok::F64(x) => PropertyVal::Float(x),
-            #[allow(clippy::mem_replace_with_default)]
-            DeJsonTok::Str => PropertyVal::String(core::mem::replace(&mut s.strbuf, String::new())),
+            DeJsonTok::Str => PropertyVal::String(core::mem::take(&mut s.strbuf)),
+            DeJsonTok::CurlyOpen => {
+                // Class members, which are untyped in the map file
+                let mut members = BTreeMap::new();
+                s.curly_open(i)?;
+                while s.next_str().is_some() {
+                    let name = core::mem::take(&mut s.strbuf);
+                    s.next_colon(i)?;
+                    members.insert(name, PropertyVal::de_json(s, i)?);
+                    s.eat_comma_curly(i)?;
+                }
+                s.curly_close(i)?;
+
+                return Ok(PropertyVal::Class(members));
+            }
             _ => {
                 return Err(s.err_token(
-                    "Incorrect property value. Must be either string, number or boolean",
+                    "Incorrect property value. Must be either string, number, boolean or object",
                 ));
             }
         };
@@ -73,29 +231,132 @@ impl DeJson for PropertyVal {
     }
 }
 
-// &self, d: usize, s: &mut SerJsonState
-
 impl SerJson for PropertyVal {
-    fn ser_json(&self, _d: usize, _s: &mut nanoserde::SerJsonState) {
-        // TODO: Implement this
-        // use nanoserde::DeJsonTok;
-
-        // let v = match s.tok {
-        //     DeJsonTok::Bool(b) => PropertyVal::Boolean(b),
-        //     DeJsonTok::U64(x) => PropertyVal::UInt(x),
-        //     DeJsonTok::I64(x) => PropertyVal::Integer(x),
-        //     DeJsonTok::F64(x) => PropertyVal::Float(x),
-        //     #[allow(clippy::mem_replace_with_default)]
-        //     DeJsonTok::Str => PropertyVal::String(core::mem::replace(&mut s.strbuf, String::new())),
-        //     _ => {
-        //         return Err(s.err_token(
-        //             "Incorrect property value. Must be either string, number or boolean",
-        //         ));
-        //     }
-        // };
-
-        // s.next_tok(i)?;
-
-        // Ok(v)
+    fn ser_json(&self, d: usize, s: &mut nanoserde::SerJsonState) {
+        match self {
+            PropertyVal::String(x) | PropertyVal::Color(x) | PropertyVal::File(x) => {
+                x.ser_json(d, s)
+            }
+            PropertyVal::UInt(x) => x.ser_json(d, s),
+            PropertyVal::Integer(x) => x.ser_json(d, s),
+            PropertyVal::Float(x) => x.ser_json(d, s),
+            PropertyVal::Boolean(x) => x.ser_json(d, s),
+            PropertyVal::Object(x) => x.ser_json(d, s),
+            PropertyVal::Class(members) => {
+                s.st_pre();
+                for (i, (name, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        s.conl();
+                    }
+                    // Member names are user-defined, so they are escaped like any other string
+                    s.indent(d + 1);
+                    name.ser_json(d + 1, s);
+                    s.out.push(':');
+                    value.ser_json(d + 1, s);
+                }
+                s.st_post(d);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(json: &str) -> Property {
+        let property: Property = DeJson::deserialize_json(json).unwrap();
+        let serialized = SerJson::serialize_json(&property);
+        let reloaded: Property = DeJson::deserialize_json(&serialized).unwrap();
+        assert_eq!(property.value, reloaded.value);
+        assert_eq!(property.typ, reloaded.typ);
+        assert_eq!(property.propertytype, reloaded.propertytype);
+        reloaded
+    }
+
+    #[test]
+    fn values_keep_their_type() {
+        let cases = [
+            (
+                r#"{"name":"s","type":"string","value":"text"}"#,
+                PropertyVal::String("text".into()),
+            ),
+            (
+                r#"{"name":"i","type":"int","value":-3}"#,
+                PropertyVal::Integer(-3),
+            ),
+            (
+                r#"{"name":"u","type":"int","value":3}"#,
+                PropertyVal::Integer(3),
+            ),
+            (
+                r#"{"name":"f","type":"float","value":1}"#,
+                PropertyVal::Float(1.0),
+            ),
+            (
+                r#"{"name":"b","type":"bool","value":true}"#,
+                PropertyVal::Boolean(true),
+            ),
+            (
+                r##"{"name":"c","type":"color","value":"#ff00ff00"}"##,
+                PropertyVal::Color("#ff00ff00".into()),
+            ),
+            (
+                r#"{"name":"p","type":"file","value":"../gfx/a.png"}"#,
+                PropertyVal::File("../gfx/a.png".into()),
+            ),
+            (
+                r#"{"name":"o","type":"object","value":12}"#,
+                PropertyVal::Object(12),
+            ),
+        ];
+
+        for (json, value) in cases {
+            assert_eq!(round_trip(json).value, value, "{}", json);
+        }
+    }
+
+    #[test]
+    fn class_members_are_nested() {
+        let json = r#"{"name":"guardian","type":"class","propertytype":"Guardian",
+            "value":{"speed":2,"bounds":{"min":8,"max":64.5},"name":"Monk"}}"#;
+
+        let property = round_trip(json);
+
+        assert_eq!(property.typ, property_type::CLASS);
+        assert_eq!(property.propertytype.as_deref(), Some("Guardian"));
+        let PropertyVal::Class(members) = property.value else {
+            panic!("Expected class value");
+        };
+        assert_eq!(members["speed"], PropertyVal::UInt(2));
+        assert_eq!(members["name"], PropertyVal::String("Monk".into()));
+        let PropertyVal::Class(bounds) = &members["bounds"] else {
+            panic!("Expected nested class value");
+        };
+        assert_eq!(bounds["max"], PropertyVal::Float(64.5));
+    }
+
+    #[test]
+    fn missing_type_defaults_to_string() {
+        let property: Property = DeJson::deserialize_json(r#"{"name":"n","value":"v"}"#).unwrap();
+
+        assert_eq!(property.typ, property_type::STRING);
+    }
+
+    #[test]
+    fn class_member_names_are_escaped() {
+        let name = "say \"hi\"\\now";
+        let property = Property::new(
+            "greeting",
+            PropertyVal::Class([(name.to_string(), PropertyVal::Boolean(true))].into()),
+        );
+
+        let serialized = SerJson::serialize_json(&property);
+        let reloaded: Property = DeJson::deserialize_json(&serialized).unwrap();
+
+        let PropertyVal::Class(members) = reloaded.value else {
+            panic!("Expected class value");
+        };
+        assert_eq!(members[name], PropertyVal::Boolean(true));
     }
 }
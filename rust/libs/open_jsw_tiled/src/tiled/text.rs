@@ -1,16 +1,18 @@
 use nanoserde::{DeJson, SerJson};
 
 /// Represents a Text object in Tiled.
-#[derive(Clone, Debug, Default, DeJson, SerJson)]
+#[derive(Clone, Debug, DeJson, SerJson)]
 #[nserde(default)]
 pub struct Text {
     /// Whether to use a bold font (default: false).
     pub bold: bool,
 
     /// Hex-formatted color (#RRGGBB or #AARRGGBB) (default: #000000).
+    #[nserde(default = "#000000")]
     pub color: String,
 
     /// Font family (default: sans-serif).
+    #[nserde(default = "sans-serif")]
     pub fontfamily: String,
 
     /// Horizontal alignment: "center", "right", "justify", or "left" (default).
@@ -20,9 +22,11 @@ pub struct Text {
     pub italic: bool,
 
     /// Whether to use kerning when placing characters (default: true).
+    #[nserde(default = "true")]
     pub kerning: bool,
 
     /// Pixel size of the font (default: 16).
+    #[nserde(default = "16")]
     pub pixelsize: i32,
 
     /// Whether to strike out the text (default: false).
@@ -41,7 +45,26 @@ pub struct Text {
     pub wrap: bool,
 }
 
-#[derive(Clone, Debug, Default, DeJson, SerJson)]
+impl Default for Text {
+    fn default() -> Self {
+        Self {
+            bold: false,
+            color: "#000000".to_string(),
+            fontfamily: "sans-serif".to_string(),
+            halign: HorizontalAlignment::Left,
+            italic: false,
+            kerning: true,
+            pixelsize: 16,
+            strikeout: false,
+            text: String::new(),
+            underline: false,
+            valign: VerticalAlignment::Top,
+            wrap: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, DeJson, SerJson)]
 pub enum HorizontalAlignment {
     #[nserde(rename = "center")]
     Center,
@@ -54,7 +77,7 @@ pub enum HorizontalAlignment {
     Left,
 }
 
-#[derive(Clone, Debug, Default, DeJson, SerJson)]
+#[derive(Clone, Debug, Default, PartialEq, DeJson, SerJson)]
 pub enum VerticalAlignment {
     #[nserde(rename = "center")]
     Center,
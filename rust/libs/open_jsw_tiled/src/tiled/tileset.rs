@@ -2,7 +2,7 @@
 
 use nanoserde::{DeJson, SerJson};
 
-use super::{TILE_VERSION, layer::Layer, property::Property};
+use super::{builder::TilesetBuilder, json_value::UnknownFields, layer::Layer, property::Property};
 
 /// https://doc.mapeditor.org/en/stable/reference/tmx-map-format/#tmx-tileset
 /// Represents a Tileset in the map.
@@ -95,6 +95,10 @@ pub struct Tileset {
 
     /// Array of Wang sets (since 1.1.5).
     pub wangsets: Option<Vec<WangSet>>,
+
+    /// Fields read from JSON which are not modelled, written back as they were read.
+    #[nserde(skip)]
+    pub unknown_fields: UnknownFields,
 }
 
 /// Represents grid information in the tileset.
@@ -223,8 +227,8 @@ pub struct WangSet {
     /// Array of properties (optional).
     pub properties: Option<Vec<Property>>,
 
-    /// Local ID of the tile representing the Wang set.
-    pub tile: u32,
+    /// Local ID of the tile representing the Wang set (-1 for none).
+    pub tile: i32,
 
     /// Type of Wang set: "corner", "edge", or "mixed" (since 1.5).
     #[nserde(rename = "type")]
@@ -252,8 +256,8 @@ pub struct WangColor {
     /// Array of properties (optional, since 1.5).
     pub properties: Option<Vec<Property>>,
 
-    /// Local ID of the tile representing the Wang color.
-    pub tile: u32,
+    /// Local ID of the tile representing the Wang color (-1 for none).
+    pub tile: i32,
 }
 
 /// Represents a Wang Tile in a Wang Set.
@@ -337,6 +341,12 @@ pub enum WangSetType {
 }
 
 impl Tileset {
+    /// Creates a tileset from an image, with a first GID of at least 1.
+    ///
+    /// # Panics
+    ///
+    /// If the tile size is 0, or the image is smaller than a tile.
+    #[deprecated(note = "use `TilesetBuilder`, which returns an error for an invalid tileset")]
     pub fn new(
         name: String,
         image: String,
@@ -346,27 +356,90 @@ impl Tileset {
         tileheight: u32,
         firstgid: u32,
     ) -> Self {
-        let columns = imagewidth / tilewidth;
-        let rows = imageheight / tileheight;
-        let tilecount = columns * rows;
-
-        let mut firstgid = firstgid;
-        if firstgid < 1 {
-            firstgid = 1;
-        }
+        let mut tileset = TilesetBuilder::new(&name, tilewidth, tileheight)
+            .image(&image, imagewidth, imageheight)
+            .build()
+            .expect("invalid tileset");
+        tileset.firstgid = firstgid.max(1);
+        tileset
+    }
 
+    /// Creates a reference to a tileset stored in a separate file, relative to the map.
+    pub fn external(firstgid: u32, source: &str) -> Self {
         Self {
-            version: TILE_VERSION.to_string(),
-            name,
-            image,
-            imagewidth,
-            imageheight,
-            columns,
-            tilewidth,
-            tileheight,
-            tilecount,
             firstgid,
+            source: Some(source.to_string()),
             ..Default::default()
         }
     }
+
+    /// Returns the top-left pixel of a tile in the image of the tileset, from the local ID of
+    /// the tile, or `None` if the tileset is a collection of images.
+    pub fn tile_position(&self, id: u32) -> Option<(u32, u32)> {
+        if self.image.is_empty() || self.columns == 0 {
+            return None;
+        }
+
+        let (column, row) = (id % self.columns, id / self.columns);
+
+        Some((
+            self.margin + column * (self.tilewidth + self.spacing),
+            self.margin + row * (self.tileheight + self.spacing),
+        ))
+    }
+}
+
+/// The tilesets of a map as written in JSON, where an external tileset is only a reference to
+/// its file.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct MapTilesets(Vec<Tileset>);
+
+/// https://doc.mapeditor.org/en/stable/reference/json-map-format/#tileset
+/// The `firstgid` and `source` of an external tileset.
+#[derive(SerJson)]
+struct TilesetReference {
+    firstgid: u32,
+    source: String,
+}
+
+impl From<&Vec<Tileset>> for MapTilesets {
+    fn from(tilesets: &Vec<Tileset>) -> Self {
+        Self(tilesets.clone())
+    }
+}
+
+impl From<&MapTilesets> for Vec<Tileset> {
+    fn from(tilesets: &MapTilesets) -> Self {
+        tilesets.0.clone()
+    }
+}
+
+impl DeJson for MapTilesets {
+    fn de_json(
+        s: &mut nanoserde::DeJsonState,
+        i: &mut std::str::Chars,
+    ) -> Result<Self, nanoserde::DeJsonErr> {
+        Ok(Self(DeJson::de_json(s, i)?))
+    }
+}
+
+impl SerJson for MapTilesets {
+    fn ser_json(&self, d: usize, s: &mut nanoserde::SerJsonState) {
+        s.out.push('[');
+        for (index, tileset) in self.0.iter().enumerate() {
+            if index > 0 {
+                s.out.push(',');
+            }
+            s.indent(d + 1);
+            match &tileset.source {
+                Some(source) => TilesetReference {
+                    firstgid: tileset.firstgid,
+                    source: source.clone(),
+                }
+                .ser_json(d + 1, s),
+                None => tileset.ser_json(d + 1, s),
+            }
+        }
+        s.out.push(']');
+    }
 }
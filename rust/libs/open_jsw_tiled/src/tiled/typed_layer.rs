@@ -0,0 +1,361 @@
+use super::{
+    chunk::Chunk,
+    layer::{Compression, DrawOrder, Layer, LayerEncoding, LayerType},
+    layer_data::{LayerData, TileMatrix},
+    object::Object,
+    property::Property,
+};
+use crate::{Error, Result};
+
+/// A layer with only the fields of its type, selected by the `type` of a [`Layer`].
+///
+/// [`Layer`] accepts any combination of fields, so that maps are read leniently. Converting it
+/// to a `TypedLayer` checks that the fields required by its type are present.
+#[derive(Clone, Debug)]
+pub enum TypedLayer {
+    TileLayer(TileLayer),
+    ObjectGroup(ObjectGroup),
+    ImageLayer(ImageLayer),
+    Group(GroupLayer),
+}
+
+/// The fields shared by all the types of layer.
+#[derive(Clone, Debug, Default)]
+pub struct LayerCommon {
+    pub id: u32,
+    pub name: String,
+    pub class: Option<String>,
+    pub locked: bool,
+    pub visible: bool,
+    pub opacity: f64,
+    pub offsetx: f64,
+    pub offsety: f64,
+    pub parallaxx: f64,
+    pub parallaxy: f64,
+    pub tintcolor: Option<String>,
+    pub properties: Vec<Property>,
+}
+
+/// https://doc.mapeditor.org/en/stable/reference/json-map-format/#layer
+/// A layer of tiles, stored as fixed-size data or as chunks on infinite maps.
+#[derive(Clone, Debug)]
+pub struct TileLayer {
+    pub common: LayerCommon,
+    /// Column count, or the width of the area covered by the chunks.
+    pub width: u32,
+    /// Row count, or the height of the area covered by the chunks.
+    pub height: u32,
+    /// Top-left corner of the area covered by the chunks, in tiles.
+    pub start: (i32, i32),
+    pub encoding: LayerEncoding,
+    pub compression: Option<Compression>,
+    pub tiles: TileData,
+}
+
+/// The tiles of a [`TileLayer`].
+#[derive(Clone, Debug)]
+pub enum TileData {
+    Fixed(LayerData),
+    Chunked(Vec<Chunk>),
+}
+
+/// A layer of objects.
+#[derive(Clone, Debug)]
+pub struct ObjectGroup {
+    pub common: LayerCommon,
+    pub draworder: DrawOrder,
+    pub objects: Vec<Object>,
+}
+
+/// A layer drawing a single image.
+#[derive(Clone, Debug)]
+pub struct ImageLayer {
+    pub common: LayerCommon,
+    pub image: String,
+    pub imagewidth: Option<u32>,
+    pub imageheight: Option<u32>,
+    pub repeatx: bool,
+    pub repeaty: bool,
+    pub transparentcolor: Option<String>,
+}
+
+/// A group of layers.
+#[derive(Clone, Debug)]
+pub struct GroupLayer {
+    pub common: LayerCommon,
+    pub layers: Vec<TypedLayer>,
+}
+
+impl TypedLayer {
+    /// Returns the fields shared by all the types of layer.
+    pub fn common(&self) -> &LayerCommon {
+        match self {
+            TypedLayer::TileLayer(layer) => &layer.common,
+            TypedLayer::ObjectGroup(layer) => &layer.common,
+            TypedLayer::ImageLayer(layer) => &layer.common,
+            TypedLayer::Group(layer) => &layer.common,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.common().name
+    }
+
+    pub fn as_tile_layer(&self) -> Option<&TileLayer> {
+        match self {
+            TypedLayer::TileLayer(layer) => Some(layer),
+            _ => None,
+        }
+    }
+
+    pub fn as_object_group(&self) -> Option<&ObjectGroup> {
+        match self {
+            TypedLayer::ObjectGroup(layer) => Some(layer),
+            _ => None,
+        }
+    }
+
+    pub fn as_image_layer(&self) -> Option<&ImageLayer> {
+        match self {
+            TypedLayer::ImageLayer(layer) => Some(layer),
+            _ => None,
+        }
+    }
+
+    pub fn as_group(&self) -> Option<&GroupLayer> {
+        match self {
+            TypedLayer::Group(layer) => Some(layer),
+            _ => None,
+        }
+    }
+}
+
+impl TileLayer {
+    /// Returns the GID at `(x, y)` in tiles, as [`Layer::tile`] does.
+    pub fn tile(&self, x: i32, y: i32) -> Option<u32> {
+        match &self.tiles {
+            TileData::Chunked(chunks) => Chunk::find_tile(chunks, x, y),
+            TileData::Fixed(data) => {
+                let (width, height) = (self.width as i64, self.height as i64);
+                let (x, y) = (x as i64, y as i64);
+                if x < 0 || y < 0 || x >= width || y >= height {
+                    return None;
+                }
+                data.get((y * width + x) as usize).copied()
+            }
+        }
+    }
+
+    /// Returns the tiles as a matrix, or `None` if the layer is chunked.
+    pub fn tile_matrix(&mut self) -> Option<TileMatrix<u32>> {
+        match &mut self.tiles {
+            TileData::Fixed(data) => Some(TileMatrix::wrap_vec(&mut **data, self.width as usize)),
+            TileData::Chunked(_) => None,
+        }
+    }
+}
+
+impl TryFrom<Layer> for TypedLayer {
+    type Error = Error;
+
+    fn try_from(layer: Layer) -> Result<Self> {
+        let missing = |field: &str| Error::MissingLayerField {
+            layer: layer.name.clone(),
+            field: field.to_string(),
+        };
+
+        let typed = match layer.typ {
+            LayerType::TileLayer => {
+                let tiles = match (&layer.chunks, &layer.data) {
+                    (Some(chunks), _) => TileData::Chunked(chunks.clone()),
+                    (None, Some(data)) => TileData::Fixed(data.clone()),
+                    (None, None) => return Err(missing("data")),
+                };
+                TypedLayer::TileLayer(TileLayer {
+                    width: layer.width.ok_or_else(|| missing("width"))?,
+                    height: layer.height.ok_or_else(|| missing("height"))?,
+                    start: (layer.startx.unwrap_or(0), layer.starty.unwrap_or(0)),
+                    encoding: layer.encoding.clone().unwrap_or_default(),
+                    compression: layer.compression.clone(),
+                    tiles,
+                    common: LayerCommon::from(&layer),
+                })
+            }
+            LayerType::ObjectGroup => TypedLayer::ObjectGroup(ObjectGroup {
+                draworder: layer.draworder.clone().unwrap_or_default(),
+                objects: layer.objects.clone().unwrap_or_default(),
+                common: LayerCommon::from(&layer),
+            }),
+            LayerType::ImageLayer => TypedLayer::ImageLayer(ImageLayer {
+                image: layer.image.clone().ok_or_else(|| missing("image"))?,
+                imagewidth: layer.imagewidth,
+                imageheight: layer.imageheight,
+                repeatx: layer.repeatx.unwrap_or(false),
+                repeaty: layer.repeaty.unwrap_or(false),
+                transparentcolor: layer.transparentcolor.clone(),
+                common: LayerCommon::from(&layer),
+            }),
+            LayerType::Group => TypedLayer::Group(GroupLayer {
+                common: LayerCommon::from(&layer),
+                layers: layer
+                    .layers
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(TypedLayer::try_from)
+                    .collect::<Result<_>>()?,
+            }),
+        };
+
+        Ok(typed)
+    }
+}
+
+impl From<&Layer> for LayerCommon {
+    fn from(layer: &Layer) -> Self {
+        Self {
+            id: layer.id,
+            name: layer.name.clone(),
+            class: layer.class.clone(),
+            locked: layer.locked,
+            visible: layer.visible,
+            opacity: layer.opacity,
+            offsetx: layer.offsetx,
+            offsety: layer.offsety,
+            parallaxx: layer.parallaxx,
+            parallaxy: layer.parallaxy,
+            tintcolor: layer.tintcolor.clone(),
+            properties: layer.properties.clone(),
+        }
+    }
+}
+
+impl From<TypedLayer> for Layer {
+    fn from(typed: TypedLayer) -> Self {
+        let (common, mut layer) = match typed {
+            TypedLayer::TileLayer(tile_layer) => {
+                let (data, chunks, startx, starty) = match tile_layer.tiles {
+                    TileData::Fixed(data) => (Some(data), None, None, None),
+                    TileData::Chunked(chunks) => (
+                        None,
+                        Some(chunks),
+                        Some(tile_layer.start.0),
+                        Some(tile_layer.start.1),
+                    ),
+                };
+                let layer = Layer {
+                    typ: LayerType::TileLayer,
+                    width: Some(tile_layer.width),
+                    height: Some(tile_layer.height),
+                    startx,
+                    starty,
+                    encoding: Some(tile_layer.encoding),
+                    compression: tile_layer.compression,
+                    data,
+                    chunks,
+                    ..Default::default()
+                };
+                (tile_layer.common, layer)
+            }
+            TypedLayer::ObjectGroup(group) => {
+                let layer = Layer {
+                    typ: LayerType::ObjectGroup,
+                    draworder: Some(group.draworder),
+                    objects: Some(group.objects),
+                    ..Default::default()
+                };
+                (group.common, layer)
+            }
+            TypedLayer::ImageLayer(image_layer) => {
+                let layer = Layer {
+                    typ: LayerType::ImageLayer,
+                    image: Some(image_layer.image),
+                    imagewidth: image_layer.imagewidth,
+                    imageheight: image_layer.imageheight,
+                    repeatx: Some(image_layer.repeatx),
+                    repeaty: Some(image_layer.repeaty),
+                    transparentcolor: image_layer.transparentcolor,
+                    ..Default::default()
+                };
+                (image_layer.common, layer)
+            }
+            TypedLayer::Group(group) => {
+                let layer = Layer {
+                    typ: LayerType::Group,
+                    layers: Some(group.layers.into_iter().map(Layer::from).collect()),
+                    ..Default::default()
+                };
+                (group.common, layer)
+            }
+        };
+
+        layer.id = common.id;
+        layer.name = common.name;
+        layer.class = common.class;
+        layer.locked = common.locked;
+        layer.visible = common.visible;
+        layer.opacity = common.opacity;
+        layer.offsetx = common.offsetx;
+        layer.offsety = common.offsety;
+        layer.parallaxx = common.parallaxx;
+        layer.parallaxy = common.parallaxy;
+        layer.tintcolor = common.tintcolor;
+        layer.properties = common.properties;
+        layer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deserialize_map;
+
+    #[test]
+    fn layers_are_typed_by_their_type() {
+        let json = r#"{"layers":[
+            {"id":1,"name":"Tiles","type":"tilelayer","width":2,"height":1,"data":[3,4]},
+            {"id":2,"name":"Group","type":"group","layers":[
+                {"id":3,"name":"Objects","type":"objectgroup","objects":[{"id":1,"x":8,"y":8}]},
+                {"id":4,"name":"Sky","type":"imagelayer","image":"sky.png","repeatx":true}]}]}"#;
+
+        let layers = deserialize_map(json).unwrap().typed_layers().unwrap();
+
+        let tiles = layers[0].as_tile_layer().unwrap();
+        assert_eq!(tiles.tile(1, 0), Some(4));
+        assert_eq!(tiles.tile(2, 0), None);
+        let group = layers[1].as_group().unwrap();
+        assert_eq!(group.layers[0].as_object_group().unwrap().objects.len(), 1);
+        let sky = group.layers[1].as_image_layer().unwrap();
+        assert_eq!(sky.image, "sky.png");
+        assert!(sky.repeatx && !sky.repeaty);
+        assert!(layers[0].as_group().is_none());
+    }
+
+    #[test]
+    fn missing_fields_are_errors() {
+        let json =
+            r#"{"layers":[{"id":1,"name":"Tiles","type":"tilelayer","width":2,"height":1}]}"#;
+
+        let result = deserialize_map(json).unwrap().typed_layers();
+
+        assert!(matches!(
+            result,
+            Err(Error::MissingLayerField { ref layer, ref field }) if layer == "Tiles" && field == "data"
+        ));
+    }
+
+    #[test]
+    fn typed_layers_convert_back() {
+        let json = r#"{"infinite":true,"layers":[{"id":1,"name":"Tiles","type":"tilelayer",
+            "width":16,"height":16,"startx":-16,"starty":0,"opacity":0.5,
+            "chunks":[{"x":-16,"y":0,"width":16,"height":16,"data":[]}]}]}"#;
+        let map = deserialize_map(json).unwrap();
+
+        let layer = Layer::from(map.typed_layers().unwrap().remove(0));
+
+        assert!(matches!(layer.typ, LayerType::TileLayer));
+        assert_eq!((layer.startx, layer.starty), (Some(-16), Some(0)));
+        assert_eq!(layer.opacity, 0.5);
+        assert_eq!(layer.chunks.unwrap().len(), 1);
+        assert!(layer.data.is_none());
+    }
+}
@@ -1,3 +1,312 @@
-// fn default_true() -> bool {
-//     true
-// }
+use std::{collections::HashSet, fmt, path::Path};
+
+use super::{
+    gid::Gid,
+    layer::{Layer, LayerType},
+    map::Map,
+};
+use crate::Error;
+
+/// The layers an OpenJSW room group contains, by class.
+const ROOM_LAYER_CLASSES: [&str; 3] = ["bg", "dynamic", "fg"];
+
+/// A problem found in a map, with where it was found: the slash-separated path of a layer (e.g.
+/// `The Bathroom/Foreground 1`), an object in a layer (`Room/Dynamic 1#12`), a tileset
+/// (`tilesets[0]`) or the map itself (`map`).
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub location: String,
+    pub error: Error,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.error)
+    }
+}
+
+/// Checks the structure of a map, returning every problem found:
+///
+/// - layer IDs unique in the map, and layer names unique in their group
+/// - tile layer data matching the width x height of the layer (or of each chunk)
+/// - GIDs of tiles and tile objects inside the ranges of the tilesets
+/// - `nextlayerid` and `nextobjectid` above the IDs in use
+/// - OpenJSW room groups (class "room") containing a bg, dynamic and fg layer
+pub fn validate(map: &Map) -> Vec<Diagnostic> {
+    let mut validator = Validator {
+        map,
+        diagnostics: Vec::new(),
+        layer_ids: HashSet::new(),
+        max_object_id: 0,
+    };
+    validator.validate_layers(&map.layers, "");
+
+    let max_layer_id = validator.layer_ids.iter().copied().max().unwrap_or(0);
+    let max_object_id = validator.max_object_id;
+    for (field, next, max) in [
+        ("nextlayerid", map.nextlayerid, max_layer_id),
+        ("nextobjectid", map.nextobjectid, max_object_id),
+    ] {
+        if next <= max {
+            validator.report(
+                "map",
+                Error::InvalidNextId {
+                    field: field.to_string(),
+                    next,
+                    max,
+                },
+            );
+        }
+    }
+
+    validator.diagnostics
+}
+
+/// Checks that the images of the tilesets, and of the tiles of image collections, exist
+/// relative to `dir` (the directory of the map file).
+pub fn validate_images(map: &Map, dir: &Path) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    for (index, tileset) in map.tilesets.iter().enumerate() {
+        let images = std::iter::once(&tileset.image)
+            .filter(|image| !image.is_empty())
+            .chain(tileset.tiles.iter().flatten().flat_map(|tile| &tile.image));
+        for image in images {
+            if !dir.join(image).is_file() {
+                diagnostics.push(Diagnostic {
+                    location: format!("tilesets[{}]", index),
+                    error: Error::TextureNotFound {
+                        texture: image.clone(),
+                    },
+                });
+            }
+        }
+    }
+
+    diagnostics
+}
+
+struct Validator<'a> {
+    map: &'a Map,
+    diagnostics: Vec<Diagnostic>,
+    layer_ids: HashSet<u32>,
+    max_object_id: u32,
+}
+
+impl Validator<'_> {
+    fn report(&mut self, location: &str, error: Error) {
+        self.diagnostics.push(Diagnostic {
+            location: location.to_string(),
+            error,
+        });
+    }
+
+    /// Checks a list of sibling layers, under the path of their group.
+    fn validate_layers(&mut self, layers: &[Layer], parent: &str) {
+        let mut names = HashSet::new();
+
+        for layer in layers {
+            let path = if parent.is_empty() {
+                layer.name.clone()
+            } else {
+                format!("{}/{}", parent, layer.name)
+            };
+
+            if !names.insert(layer.name.as_str()) {
+                self.report(
+                    &path,
+                    Error::NonUniqueLayerName {
+                        layer: layer.name.clone(),
+                    },
+                );
+            }
+            if !self.layer_ids.insert(layer.id) {
+                self.report(&path, Error::NonUniqueLayerId { id: layer.id });
+            }
+
+            match layer.typ {
+                LayerType::TileLayer => self.validate_tiles(layer, &path),
+                LayerType::ObjectGroup => self.validate_objects(layer, &path),
+                LayerType::Group => {
+                    if layer.class.as_deref() == Some("room") {
+                        self.validate_room(layer, &path);
+                    }
+                    self.validate_layers(layer.layers.as_deref().unwrap_or_default(), &path);
+                }
+                LayerType::ImageLayer => (),
+            }
+        }
+    }
+
+    fn validate_tiles(&mut self, layer: &Layer, path: &str) {
+        let mut data = Vec::new();
+        if let Some(chunks) = &layer.chunks {
+            for chunk in chunks {
+                data.push((&chunk.data, chunk.width * chunk.height));
+            }
+        } else if let Some(layer_data) = &layer.data {
+            let size = layer.width.unwrap_or(0) * layer.height.unwrap_or(0);
+            data.push((layer_data, size));
+        }
+
+        for (tiles, size) in data {
+            if tiles.len() != size as usize {
+                self.report(
+                    path,
+                    Error::InvalidLayerData {
+                        message: format!(
+                            "{} tiles for an area of {} tiles (width x height)",
+                            tiles.len(),
+                            size
+                        ),
+                    },
+                );
+            }
+            for gid in tiles.iter().map(|gid| Gid::from(*gid)) {
+                self.validate_gid(gid, path);
+            }
+        }
+    }
+
+    fn validate_objects(&mut self, layer: &Layer, path: &str) {
+        for object in layer.objects.iter().flatten() {
+            self.max_object_id = self.max_object_id.max(object.id);
+            if let Some(gid) = object.gid {
+                self.validate_gid(gid, &format!("{}#{}", path, object.id));
+            }
+        }
+    }
+
+    /// Checks a GID is empty or in a tileset, as found by [`Map::tileset_for_gid`].
+    fn validate_gid(&mut self, gid: Gid, location: &str) {
+        if !gid.is_empty() && self.map.tileset_for_gid(gid).is_none() {
+            self.report(location, Error::GidOutOfRange { gid: gid.id() });
+        }
+    }
+
+    fn validate_room(&mut self, room: &Layer, path: &str) {
+        for class in ROOM_LAYER_CLASSES {
+            let found = room
+                .layers
+                .iter()
+                .flatten()
+                .any(|layer| layer.class.as_deref() == Some(class));
+            if !found {
+                self.report(
+                    path,
+                    Error::LayerTypeNotFound {
+                        layer_type: class.to_string(),
+                    },
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deserialize_map;
+
+    fn messages(diagnostics: &[Diagnostic]) -> Vec<String> {
+        diagnostics.iter().map(|d| d.to_string()).collect()
+    }
+
+    #[test]
+    fn valid_maps_have_no_diagnostics() {
+        let map = deserialize_map(
+            r#"{"nextlayerid":5,"nextobjectid":2,
+            "tilesets":[{"firstgid":1,"name":"cells","tilecount":4,"image":"cells.png"}],
+            "layers":[{"id":1,"name":"Room","type":"group","class":"room","layers":[
+            {"id":2,"name":"Background 1","type":"tilelayer","class":"bg","width":2,"height":1,
+            "data":[1,4]},
+            {"id":3,"name":"Dynamic 1","type":"objectgroup","class":"dynamic",
+            "objects":[{"id":1,"gid":2}]},
+            {"id":4,"name":"Foreground 1","type":"tilelayer","class":"fg","width":2,"height":1,
+            "data":[0,0]}]}]}"#,
+        )
+        .unwrap();
+
+        assert!(messages(&validate(&map)).is_empty());
+    }
+
+    #[test]
+    fn problems_are_located() {
+        let map = deserialize_map(
+            r#"{"nextlayerid":3,"nextobjectid":7,
+            "tilesets":[{"firstgid":1,"name":"cells","tilecount":4,"image":"cells.png"}],
+            "layers":[{"id":1,"name":"Room","type":"group","class":"room","layers":[
+            {"id":2,"name":"Tiles","type":"tilelayer","class":"bg","width":2,"height":2,
+            "data":[1,5,0]},
+            {"id":3,"name":"Tiles","type":"objectgroup","class":"dynamic",
+            "objects":[{"id":7,"gid":9}]},
+            {"id":3,"name":"Foreground 1","type":"tilelayer","width":1,"height":1,
+            "data":[0]}]}]}"#,
+        )
+        .unwrap();
+
+        let diagnostics = validate(&map);
+
+        let messages = messages(&diagnostics);
+        assert!(
+            messages
+                .iter()
+                .any(|m| m.starts_with("Room: ") && m.contains("'fg'"))
+        );
+        assert!(
+            messages
+                .iter()
+                .any(|m| m.starts_with("Room/Tiles: ") && m.contains("3 tiles"))
+        );
+        assert!(
+            messages
+                .iter()
+                .any(|m| m.starts_with("Room/Tiles: ") && m.contains("GID 5"))
+        );
+        assert!(
+            messages
+                .iter()
+                .any(|m| m.starts_with("Room/Tiles#7: ") && m.contains("GID 9"))
+        );
+        assert!(matches!(
+            diagnostics
+                .iter()
+                .find(|d| matches!(d.error, Error::NonUniqueLayerName { .. }))
+                .map(|d| d.location.as_str()),
+            Some("Room/Tiles")
+        ));
+        assert!(
+            messages
+                .iter()
+                .any(|m| m.starts_with("Room/Foreground 1: ") && m.contains("Layer ID 3"))
+        );
+        assert!(
+            messages
+                .iter()
+                .any(|m| m.starts_with("map: ") && m.contains("nextlayerid"))
+        );
+        assert!(
+            messages
+                .iter()
+                .any(|m| m.starts_with("map: ") && m.contains("nextobjectid"))
+        );
+        assert_eq!(diagnostics.len(), 8);
+    }
+
+    #[test]
+    fn missing_images_are_reported() {
+        let map = deserialize_map(
+            r#"{"tilesets":[{"firstgid":1,"name":"cells","image":"missing.png"}]}"#,
+        )
+        .unwrap();
+
+        let diagnostics = validate_images(&map, Path::new(env!("CARGO_MANIFEST_DIR")));
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].location, "tilesets[0]");
+        assert!(matches!(
+            &diagnostics[0].error,
+            Error::TextureNotFound { texture } if texture == "missing.png"
+        ));
+    }
+}
@@ -0,0 +1,221 @@
+#![allow(clippy::question_mark)]
+
+use std::{fs, path::Path};
+
+use nanoserde::{DeJson, SerJson};
+use regex::Regex;
+
+use crate::{Error, Result};
+
+/// https://doc.mapeditor.org/en/stable/manual/worlds/
+/// Represents a World in Tiled, which places maps next to each other.
+#[derive(Clone, Debug, Default, DeJson, SerJson)]
+#[nserde(default)]
+pub struct World {
+    /// Array of maps, with their positions in the world.
+    pub maps: Vec<WorldMap>,
+
+    /// Array of patterns, which place the maps found next to the world file by their file names
+    /// (optional).
+    pub patterns: Option<Vec<WorldPattern>>,
+
+    /// Whether only the maps next to the current map are shown in the editor.
+    #[nserde(rename = "onlyShowAdjacentMaps")]
+    pub only_show_adjacent_maps: bool,
+
+    /// Type of the file (always "world").
+    #[nserde(rename = "type")]
+    pub typ: WorldType,
+}
+
+/// A map in a World.
+#[derive(Clone, Debug, Default, PartialEq, DeJson, SerJson)]
+#[nserde(default)]
+pub struct WorldMap {
+    /// Path to the map file, relative to the world file.
+    #[nserde(rename = "fileName")]
+    pub file_name: String,
+
+    /// X coordinate in pixels.
+    pub x: i32,
+
+    /// Y coordinate in pixels.
+    pub y: i32,
+
+    /// Width in pixels (optional, the size of the map is used if not set).
+    pub width: Option<u32>,
+
+    /// Height in pixels (optional, the size of the map is used if not set).
+    pub height: Option<u32>,
+}
+
+/// A pattern placing the maps whose file names match a regular expression.
+///
+/// The first two captures of the expression are the column and row of the map, which are
+/// multiplied by `multiplierX` and `multiplierY`, then offset by `offsetX` and `offsetY` to get the
+/// position of the map in pixels.
+#[derive(Clone, Debug, Default, DeJson, SerJson)]
+#[nserde(default)]
+pub struct WorldPattern {
+    /// Regular expression matched against the file names of the maps.
+    pub regexp: String,
+
+    /// Horizontal distance between the maps in pixels.
+    #[nserde(rename = "multiplierX")]
+    pub multiplier_x: i32,
+
+    /// Vertical distance between the maps in pixels.
+    #[nserde(rename = "multiplierY")]
+    pub multiplier_y: i32,
+
+    /// X offset of the maps in pixels.
+    #[nserde(rename = "offsetX")]
+    pub offset_x: i32,
+
+    /// Y offset of the maps in pixels.
+    #[nserde(rename = "offsetY")]
+    pub offset_y: i32,
+
+    /// Width of the maps in pixels (optional, since 1.9).
+    #[nserde(rename = "mapWidth")]
+    pub map_width: Option<u32>,
+
+    /// Height of the maps in pixels (optional, since 1.9).
+    #[nserde(rename = "mapHeight")]
+    pub map_height: Option<u32>,
+}
+
+#[derive(Clone, Debug, Default, DeJson, SerJson)]
+pub enum WorldType {
+    #[default]
+    #[nserde(rename = "world")]
+    World,
+}
+
+impl World {
+    pub fn new(maps: Vec<WorldMap>) -> Self {
+        Self {
+            maps,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the maps of the world, including the files in `dir` (the directory of the world
+    /// file) which match a pattern.
+    ///
+    /// Maps listed in the world take precedence over maps matched by a pattern, and a file matched
+    /// by several patterns is placed by the first.
+    pub fn resolve_maps(&self, dir: &Path) -> Result<Vec<WorldMap>> {
+        let mut maps = self.maps.clone();
+
+        let Some(patterns) = self.patterns.as_ref().filter(|p| !p.is_empty()) else {
+            return Ok(maps);
+        };
+
+        let mut file_names: Vec<String> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_file())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect();
+        file_names.sort();
+
+        for pattern in patterns {
+            let regex = pattern.regex()?;
+
+            for file_name in &file_names {
+                if maps.iter().any(|map| map.file_name == *file_name) {
+                    continue;
+                }
+                if let Some(map) = pattern.match_file_name(&regex, file_name) {
+                    maps.push(map);
+                }
+            }
+        }
+
+        Ok(maps)
+    }
+}
+
+impl WorldPattern {
+    /// Compiles the regular expression of the pattern.
+    pub fn regex(&self) -> Result<Regex> {
+        Regex::new(&self.regexp).map_err(|error| Error::InvalidWorldPattern {
+            pattern: self.regexp.clone(),
+            message: error.to_string(),
+        })
+    }
+
+    /// Returns the map for a file name, if it matches the pattern.
+    fn match_file_name(&self, regex: &Regex, file_name: &str) -> Option<WorldMap> {
+        let captures = regex.captures(file_name)?;
+        let col: i32 = captures.get(1)?.as_str().parse().ok()?;
+        let row: i32 = captures.get(2)?.as_str().parse().ok()?;
+
+        Some(WorldMap {
+            file_name: file_name.to_string(),
+            x: col * self.multiplier_x + self.offset_x,
+            y: row * self.multiplier_y + self.offset_y,
+            width: self.map_width,
+            height: self.map_height,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn world_round_trip() {
+        let json = r#"{
+            "maps": [
+                { "fileName": "rooms/00_the_off_licence.json", "height": 128, "width": 256, "x": 256, "y": 0 }
+            ],
+            "patterns": [
+                { "regexp": "room_(\\d+)_(\\d+)\\.json", "multiplierX": 256, "multiplierY": 128, "offsetX": 0, "offsetY": -128 }
+            ],
+            "onlyShowAdjacentMaps": false,
+            "type": "world"
+        }"#;
+
+        let world: World = DeJson::deserialize_json(json).unwrap();
+        let reloaded: World = DeJson::deserialize_json(&SerJson::serialize_json(&world)).unwrap();
+
+        assert_eq!(reloaded.maps, world.maps);
+        assert_eq!(reloaded.maps[0].file_name, "rooms/00_the_off_licence.json");
+        assert_eq!(reloaded.maps[0].width, Some(256));
+        let pattern = &reloaded.patterns.unwrap()[0];
+        assert_eq!(pattern.multiplier_y, 128);
+        assert_eq!(pattern.offset_y, -128);
+    }
+
+    #[test]
+    fn patterns_place_maps_by_file_name() {
+        let pattern = WorldPattern {
+            regexp: r"room_(\d+)_(\d+)\.json".to_string(),
+            multiplier_x: 256,
+            multiplier_y: 128,
+            offset_x: 8,
+            ..Default::default()
+        };
+        let regex = pattern.regex().unwrap();
+
+        let map = pattern.match_file_name(&regex, "room_3_2.json").unwrap();
+
+        assert_eq!((map.x, map.y), (3 * 256 + 8, 2 * 128));
+        assert!(pattern.match_file_name(&regex, "cells.tsj").is_none());
+    }
+
+    #[test]
+    fn invalid_patterns_are_errors() {
+        let pattern = WorldPattern {
+            regexp: "room_(".to_string(),
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            pattern.regex(),
+            Err(Error::InvalidWorldPattern { .. })
+        ));
+    }
+}
@@ -0,0 +1,179 @@
+//! https://doc.mapeditor.org/en/stable/reference/tmx-map-format/
+//! Reads and writes the XML flavour of the Tiled formats (TMX maps and TSX tilesets), using the
+//! same model as JSON.
+
+use self::element::Element;
+use crate::{
+    Result,
+    tiled::{map::Map, object_template::ObjectTemplate, tileset::Tileset},
+};
+
+mod element;
+mod read;
+mod write;
+
+pub(crate) fn read_map(data: &str) -> Result<Map> {
+    read::map(&Element::read(data)?)
+}
+
+pub(crate) fn write_map(map: &Map) -> Result<String> {
+    write::map(map)?.write()
+}
+
+pub(crate) fn read_tileset(data: &str) -> Result<Tileset> {
+    read::tileset(&Element::read(data)?)
+}
+
+pub(crate) fn write_tileset(tileset: &Tileset) -> Result<String> {
+    write::tileset(tileset, false)?.write()
+}
+
+pub(crate) fn read_template(data: &str) -> Result<ObjectTemplate> {
+    read::template(&Element::read(data)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        deserialize_map, serialize_map,
+        tiled::{layer::LayerEncoding, property::PropertyVal},
+    };
+
+    const MAP: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" tiledversion="1.11.2" class="room" orientation="orthogonal" renderorder="right-down" width="4" height="2" tilewidth="8" tileheight="8" backgroundcolor="#ff000000" infinite="0" nextlayerid="6" nextobjectid="5">
+ <properties>
+  <property name="border" type="color" value="#ff0000ff"/>
+  <property name="guardian" type="class" propertytype="Guardian">
+   <properties>
+    <property name="name" value="Monk"/>
+    <property name="speed" type="int" value="2"/>
+   </properties>
+  </property>
+  <property name="notes">First line
+second line</property>
+  <property name="patrol" type="object" value="3"/>
+  <property name="room" type="int" value="33"/>
+ </properties>
+ <tileset firstgid="1" name="cells" tilewidth="8" tileheight="8" tilecount="16" columns="4">
+  <image source="cells.png" trans="ff00ff" width="32" height="32"/>
+  <tile id="2" type="earth" probability="0.5">
+   <properties>
+    <property name="solid" type="bool" value="true"/>
+   </properties>
+   <objectgroup id="2" name="" draworder="index">
+    <object id="1" x="0" y="0" width="8" height="8"/>
+   </objectgroup>
+   <animation>
+    <frame tileid="2" duration="100"/>
+    <frame tileid="3" duration="100"/>
+   </animation>
+  </tile>
+ </tileset>
+ <tileset firstgid="17" source="sprites.tsx"/>
+ <layer id="1" name="Background" width="4" height="2">
+  <data encoding="csv">
+1,2,3,4,
+5,6,7,8
+</data>
+ </layer>
+ <group id="2" name="Room" offsetx="256" offsety="128">
+  <layer id="3" name="Foreground" width="4" height="2" visible="0" opacity="0.5">
+   <data encoding="base64">
+AQAAAAAAAAAAAAAAEQAAAAAAAAAAAAAAAAAAAAIAAAA=
+</data>
+  </layer>
+  <objectgroup id="4" name="Dynamic 1">
+   <object id="1" name="Player start" type="player" x="160" y="104" width="16" height="16"/>
+   <object id="2" name="Item" type="item" gid="17" x="8" y="16" width="8" height="8"/>
+   <object id="3" name="Guardian patrol" type="patrol" x="10.5" y="20" rotation="90">
+    <polyline points="0,0 32.5,0"/>
+   </object>
+   <object id="4" name="Label" x="0" y="0" width="64" height="16">
+    <text pixelsize="8" wrap="1" color="#ffffffff" halign="center">Jet Set Willy</text>
+   </object>
+  </objectgroup>
+ </group>
+ <imagelayer id="5" name="Sky" repeatx="1">
+  <image source="sky.png" width="256" height="128"/>
+ </imagelayer>
+</map>
+"##;
+
+    #[test]
+    fn tmx_round_trip() {
+        let map = read_map(MAP).unwrap();
+
+        assert_eq!(write_map(&map).unwrap(), MAP);
+    }
+
+    #[test]
+    fn tmx_json_tmx_round_trip() {
+        let map = read_map(MAP).unwrap();
+
+        let json = serialize_map(&map).unwrap();
+        let reloaded = deserialize_map(&json).unwrap();
+
+        assert_eq!(write_map(&reloaded).unwrap(), MAP);
+    }
+
+    #[test]
+    fn tmx_is_read_into_the_model() {
+        let map = read_map(MAP).unwrap();
+
+        let properties = map.properties.as_ref().unwrap();
+        assert_eq!(
+            properties[0].value,
+            PropertyVal::Color("#ff0000ff".to_string())
+        );
+        assert_eq!(
+            properties[2].value,
+            PropertyVal::String("First line\nsecond line".to_string())
+        );
+        assert_eq!(map.tilesets[0].transparentcolor.as_deref(), Some("#ff00ff"));
+        assert_eq!(map.tilesets[1].source.as_deref(), Some("sprites.tsx"));
+
+        assert_eq!(
+            **map.layers[0].data.as_ref().unwrap(),
+            [1, 2, 3, 4, 5, 6, 7, 8]
+        );
+        let foreground = &map.layers[1].layers.as_ref().unwrap()[0];
+        assert!(matches!(foreground.encoding, Some(LayerEncoding::Base64)));
+        assert_eq!(
+            **foreground.data.as_ref().unwrap(),
+            [1, 0, 0, 17, 0, 0, 0, 2]
+        );
+        assert!(!foreground.visible);
+
+        let objects = map.layers[1].layers.as_ref().unwrap()[1]
+            .objects
+            .as_ref()
+            .unwrap();
+        assert_eq!(objects[0].class.as_deref(), Some("player"));
+        assert_eq!(objects[2].polyline.as_ref().unwrap()[1].x, 32.5);
+        assert_eq!(objects[3].text.as_ref().unwrap().pixelsize, 8);
+        assert!(objects[3].text.as_ref().unwrap().kerning);
+    }
+
+    #[test]
+    fn tsx_round_trip() {
+        let tsx = r##"<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.10" tiledversion="1.11.2" name="cells" tilewidth="8" tileheight="8" spacing="1" tilecount="2" columns="2">
+ <tileoffset x="0" y="4"/>
+ <image source="cells.png" width="17" height="8"/>
+ <wangsets>
+  <wangset name="Terrain" type="corner" tile="-1">
+   <wangcolor name="Earth" color="#ff0000" tile="0" probability="1"/>
+   <wangtile tileid="0" wangid="0,1,0,1,0,1,0,1"/>
+  </wangset>
+ </wangsets>
+</tileset>
+"##;
+
+        let tileset = read_tileset(tsx).unwrap();
+
+        assert_eq!(tileset.spacing, 1);
+        assert_eq!(tileset.wangsets.as_ref().unwrap()[0].tile, -1);
+        assert_eq!(write_tileset(&tileset).unwrap(), tsx);
+    }
+}
@@ -0,0 +1,238 @@
+use std::{fmt::Display, str::FromStr};
+
+use nanoserde::{DeJson, SerJson};
+use quick_xml::{
+    Reader, Writer,
+    events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event},
+};
+
+use crate::{Error, Result};
+
+/// An XML element, with its attributes in document order.
+///
+/// TMX files are small enough to be read into a tree before being mapped to the Tiled model, which
+/// keeps the mapping independent of the order of the elements.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Element>,
+    pub text: String,
+}
+
+impl Element {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Parses an XML document, returning its root element.
+    pub fn read(xml: &str) -> Result<Element> {
+        let mut reader = Reader::from_str(xml);
+        let mut stack = vec![Element::new("")];
+
+        loop {
+            match reader.read_event()? {
+                Event::Start(start) => stack.push(Self::from_start(&start)?),
+                Event::Empty(start) => {
+                    let element = Self::from_start(&start)?;
+                    stack.last_mut().unwrap().children.push(element);
+                }
+                Event::End(_) => {
+                    let mut element = stack.pop().unwrap();
+                    // Drop the indentation between child elements
+                    if !element.children.is_empty() && element.text.trim().is_empty() {
+                        element.text.clear();
+                    }
+                    stack.last_mut().unwrap().children.push(element);
+                }
+                Event::Text(text) => stack.last_mut().unwrap().text.push_str(&text.unescape()?),
+                Event::CData(data) => stack
+                    .last_mut()
+                    .unwrap()
+                    .text
+                    .push_str(&String::from_utf8_lossy(&data.into_inner())),
+                Event::Eof => break,
+                _ => (),
+            }
+        }
+
+        if stack.len() > 1 {
+            return Err(Error::InvalidTmx {
+                element: stack.pop().unwrap().name,
+                message: "element is not closed".to_string(),
+            });
+        }
+
+        let document = stack.pop().unwrap();
+        document
+            .children
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::InvalidTmx {
+                element: "document".to_string(),
+                message: "no root element".to_string(),
+            })
+    }
+
+    /// Writes the element as an XML document, indented by one space as Tiled does.
+    pub fn write(&self) -> Result<String> {
+        let mut writer = Writer::new_with_indent(Vec::new(), b' ', 1);
+        writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+        self.write_to(&mut writer)?;
+
+        let mut xml = String::from_utf8_lossy(&writer.into_inner()).into_owned();
+        xml.push('\n');
+
+        Ok(xml)
+    }
+
+    fn from_start(start: &BytesStart) -> Result<Element> {
+        let mut element = Element::new(&String::from_utf8_lossy(start.name().as_ref()));
+
+        for attribute in start.attributes() {
+            let attribute = attribute.map_err(quick_xml::Error::from)?;
+            element.attributes.push((
+                String::from_utf8_lossy(attribute.key.as_ref()).into_owned(),
+                attribute.unescape_value()?.into_owned(),
+            ));
+        }
+
+        Ok(element)
+    }
+
+    fn write_to(&self, writer: &mut Writer<Vec<u8>>) -> std::io::Result<()> {
+        let mut start = BytesStart::new(self.name.as_str());
+        for (name, value) in &self.attributes {
+            start.push_attribute((name.as_str(), value.as_str()));
+        }
+
+        if self.children.is_empty() && self.text.is_empty() {
+            return writer.write_event(Event::Empty(start));
+        }
+
+        writer.write_event(Event::Start(start))?;
+        if !self.text.is_empty() {
+            writer.write_event(Event::Text(BytesText::new(&self.text)))?;
+        }
+        for child in &self.children {
+            child.write_to(writer)?;
+        }
+        writer.write_event(Event::End(BytesEnd::new(self.name.as_str())))
+    }
+
+    /// Returns the first child element with a name.
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    /// Returns the child elements with a name.
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    pub fn push(&mut self, child: Element) {
+        self.children.push(child);
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn string(&self, name: &str) -> Option<String> {
+        self.attribute(name).map(str::to_string)
+    }
+
+    /// Parses an attribute, if present.
+    pub fn parse<T: FromStr>(&self, name: &str) -> Result<Option<T>> {
+        self.attribute(name)
+            .map(|value| value.parse().map_err(|_| self.invalid(name, value)))
+            .transpose()
+    }
+
+    /// Parses an attribute, or returns the default value of the attribute if it is not present.
+    pub fn parse_or<T: FromStr>(&self, name: &str, default: T) -> Result<T> {
+        Ok(self.parse(name)?.unwrap_or(default))
+    }
+
+    /// Parses a boolean attribute, written as 1 or 0 (or true or false).
+    pub fn flag(&self, name: &str) -> Result<Option<bool>> {
+        self.attribute(name)
+            .map(|value| match value {
+                "1" | "true" => Ok(true),
+                "0" | "false" => Ok(false),
+                _ => Err(self.invalid(name, value)),
+            })
+            .transpose()
+    }
+
+    /// Parses an attribute naming a variant of an enum, using the same names as JSON.
+    pub fn enumeration<T: DeJson>(&self, name: &str) -> Result<Option<T>> {
+        self.attribute(name)
+            .map(|value| {
+                DeJson::deserialize_json(&format!("\"{}\"", value))
+                    .map_err(|_| self.invalid(name, value))
+            })
+            .transpose()
+    }
+
+    pub fn set(&mut self, name: &str, value: impl Display) {
+        self.attributes.push((name.to_string(), value.to_string()));
+    }
+
+    pub fn set_opt(&mut self, name: &str, value: Option<impl Display>) {
+        if let Some(value) = value {
+            self.set(name, value);
+        }
+    }
+
+    /// Sets a boolean attribute, as 1 or 0.
+    pub fn set_flag(&mut self, name: &str, value: bool) {
+        self.set(name, if value { 1 } else { 0 });
+    }
+
+    /// Sets an attribute naming a variant of an enum, using the same names as JSON.
+    pub fn set_enumeration(&mut self, name: &str, value: &impl SerJson) {
+        self.set(name, SerJson::serialize_json(value).trim_matches('"'));
+    }
+
+    /// Returns an error for an invalid attribute value.
+    pub fn invalid(&self, name: &str, value: &str) -> Error {
+        Error::InvalidTmx {
+            element: self.name.clone(),
+            message: format!("invalid {} '{}'", name, value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_write() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map width="32">
+ <data encoding="csv">1,&amp;2</data>
+ <tile/>
+</map>
+"#;
+
+        let element = Element::read(xml).unwrap();
+
+        assert_eq!(element.attribute("width"), Some("32"));
+        assert_eq!(element.child("data").unwrap().text, "1,&2");
+        assert_eq!(element.children_named("tile").count(), 1);
+        assert_eq!(element.write().unwrap(), xml);
+    }
+
+    #[test]
+    fn unclosed_elements_are_errors() {
+        assert!(Element::read("<map><layer>").is_err());
+    }
+}
@@ -0,0 +1,540 @@
+use std::collections::BTreeMap;
+
+use super::element::Element;
+use crate::{
+    Error, Result,
+    tiled::{
+        chunk::Chunk,
+        gid::Gid,
+        json_value::UnknownFields,
+        layer::{Compression, Layer, LayerEncoding, LayerType},
+        layer_data::decode_base64,
+        map::{Map, MapType},
+        object::Object,
+        object_template::ObjectTemplate,
+        point::Point,
+        property::{Property, PropertyVal, property_type},
+        text::Text,
+        tileset::{
+            Frame, Grid, Terrain, Tile, TileOffset, Tileset, TilesetType, Transformations,
+            WangColor, WangSet, WangTile,
+        },
+    },
+};
+
+/// https://doc.mapeditor.org/en/stable/reference/tmx-map-format/#map
+pub(crate) fn map(element: &Element) -> Result<Map> {
+    expect_name(element, "map")?;
+
+    Ok(Map {
+        backgroundcolor: element.string("backgroundcolor"),
+        class: element.string("class"),
+        compressionlevel: element.parse_or("compressionlevel", -1)?,
+        height: element.parse_or("height", 0)?,
+        hexsidelength: element.parse("hexsidelength")?,
+        infinite: element.flag("infinite")?.unwrap_or(false),
+        layers: layers(element)?,
+        nextlayerid: element.parse_or("nextlayerid", 0)?,
+        nextobjectid: element.parse_or("nextobjectid", 0)?,
+        orientation: element.enumeration("orientation")?.unwrap_or_default(),
+        parallaxoriginx: element.parse_or("parallaxoriginx", 0.0)?,
+        parallaxoriginy: element.parse_or("parallaxoriginy", 0.0)?,
+        properties: properties(element)?,
+        renderorder: element.enumeration("renderorder")?.unwrap_or_default(),
+        staggeraxis: element.enumeration("staggeraxis")?,
+        staggerindex: element.enumeration("staggerindex")?,
+        tiledversion: element.string("tiledversion").unwrap_or_default(),
+        tileheight: element.parse_or("tileheight", 0)?,
+        tilesets: element
+            .children_named("tileset")
+            .map(tileset)
+            .collect::<Result<_>>()?,
+        tilewidth: element.parse_or("tilewidth", 0)?,
+        typ: MapType::Map,
+        version: element.string("version").unwrap_or_default(),
+        width: element.parse_or("width", 0)?,
+        unknown_fields: UnknownFields::default(),
+    })
+}
+
+/// https://doc.mapeditor.org/en/stable/reference/tmx-map-format/#tileset
+///
+/// Reads both tilesets embedded in a map and the root element of a TSX file.
+pub(crate) fn tileset(element: &Element) -> Result<Tileset> {
+    expect_name(element, "tileset")?;
+    let image = Image::read(element.child("image"))?;
+
+    Ok(Tileset {
+        backgroundcolor: element.string("backgroundcolor"),
+        class: element.string("class"),
+        columns: element.parse_or("columns", 0)?,
+        fillmode: element.enumeration("fillmode")?.unwrap_or_default(),
+        firstgid: element.parse_or("firstgid", 0)?,
+        grid: element
+            .child("grid")
+            .map(|grid| -> Result<Grid> {
+                Ok(Grid {
+                    height: grid.parse_or("height", 0)?,
+                    orientation: grid.enumeration("orientation")?.unwrap_or_default(),
+                    width: grid.parse_or("width", 0)?,
+                })
+            })
+            .transpose()?,
+        image: image.source.unwrap_or_default(),
+        imageheight: image.height.unwrap_or_default(),
+        imagewidth: image.width.unwrap_or_default(),
+        margin: element.parse_or("margin", 0)?,
+        name: element.string("name").unwrap_or_default(),
+        objectalignment: element.enumeration("objectalignment")?,
+        properties: properties(element)?,
+        source: element.string("source"),
+        spacing: element.parse_or("spacing", 0)?,
+        terrains: element
+            .child("terraintypes")
+            .map(|terrains| {
+                terrains
+                    .children_named("terrain")
+                    .map(|terrain| -> Result<Terrain> {
+                        Ok(Terrain {
+                            name: terrain.string("name").unwrap_or_default(),
+                            properties: properties(terrain)?,
+                            tile: terrain.parse_or("tile", 0)?,
+                        })
+                    })
+                    .collect()
+            })
+            .transpose()?,
+        tilecount: element.parse_or("tilecount", 0)?,
+        tiledversion: element.string("tiledversion").unwrap_or_default(),
+        tileheight: element.parse_or("tileheight", 0)?,
+        tileoffset: element
+            .child("tileoffset")
+            .map(|offset| -> Result<TileOffset> {
+                Ok(TileOffset {
+                    x: offset.parse_or("x", 0)?,
+                    y: offset.parse_or("y", 0)?,
+                })
+            })
+            .transpose()?,
+        tilerendersize: element.enumeration("tilerendersize")?,
+        tiles: optional_list(element.children_named("tile").map(tile).collect())?,
+        tilewidth: element.parse_or("tilewidth", 0)?,
+        transformations: element
+            .child("transformations")
+            .map(|transformations| -> Result<Transformations> {
+                Ok(Transformations {
+                    hflip: transformations.flag("hflip")?.unwrap_or(false),
+                    vflip: transformations.flag("vflip")?.unwrap_or(false),
+                    rotate: transformations.flag("rotate")?.unwrap_or(false),
+                    preferuntransformed: transformations
+                        .flag("preferuntransformed")?
+                        .unwrap_or(false),
+                })
+            })
+            .transpose()?,
+        transparentcolor: image.transparentcolor,
+        typ: TilesetType::Tileset,
+        version: element.string("version").unwrap_or_default(),
+        wangsets: element
+            .child("wangsets")
+            .map(|wangsets| wangsets.children_named("wangset").map(wang_set).collect())
+            .transpose()?,
+        unknown_fields: UnknownFields::default(),
+    })
+}
+
+/// Reads the layers which are children of a map or group, in order.
+fn layers(element: &Element) -> Result<Vec<Layer>> {
+    element
+        .children
+        .iter()
+        .filter_map(|child| {
+            let typ = match child.name.as_str() {
+                "layer" => LayerType::TileLayer,
+                "objectgroup" => LayerType::ObjectGroup,
+                "imagelayer" => LayerType::ImageLayer,
+                "group" => LayerType::Group,
+                _ => return None,
+            };
+            Some(layer(child, typ))
+        })
+        .collect()
+}
+
+/// https://doc.mapeditor.org/en/stable/reference/tmx-map-format/#layer
+fn layer(element: &Element, typ: LayerType) -> Result<Layer> {
+    let mut layer = Layer {
+        class: element.string("class"),
+        id: element.parse_or("id", 0)?,
+        locked: element.flag("locked")?.unwrap_or(false),
+        name: element.string("name").unwrap_or_default(),
+        offsetx: element.parse_or("offsetx", 0.0)?,
+        offsety: element.parse_or("offsety", 0.0)?,
+        opacity: element.parse_or("opacity", 1.0)?,
+        parallaxx: element.parse_or("parallaxx", 1.0)?,
+        parallaxy: element.parse_or("parallaxy", 1.0)?,
+        properties: properties(element)?.unwrap_or_default(),
+        tintcolor: element.string("tintcolor"),
+        typ,
+        visible: element.flag("visible")?.unwrap_or(true),
+        x: element.parse_or("x", 0)?,
+        y: element.parse_or("y", 0)?,
+        ..Default::default()
+    };
+
+    match layer.typ {
+        LayerType::TileLayer => {
+            layer.width = element.parse("width")?;
+            layer.height = element.parse("height")?;
+            if let Some(data) = element.child("data") {
+                tile_data(&mut layer, data)?;
+            }
+        }
+        LayerType::ObjectGroup => {
+            layer.draworder = element.enumeration("draworder")?;
+            layer.objects = Some(
+                element
+                    .children_named("object")
+                    .map(object)
+                    .collect::<Result<_>>()?,
+            );
+        }
+        LayerType::ImageLayer => {
+            let image = Image::read(element.child("image"))?;
+            layer.image = image.source;
+            layer.imagewidth = image.width;
+            layer.imageheight = image.height;
+            layer.transparentcolor = image.transparentcolor;
+            layer.repeatx = element.flag("repeatx")?;
+            layer.repeaty = element.flag("repeaty")?;
+        }
+        LayerType::Group => layer.layers = Some(layers(element)?),
+    }
+
+    Ok(layer)
+}
+
+/// https://doc.mapeditor.org/en/stable/reference/tmx-map-format/#data
+fn tile_data(layer: &mut Layer, element: &Element) -> Result<()> {
+    layer.encoding = element.enumeration("encoding")?;
+    layer.compression = element.enumeration("compression")?;
+    let encoding = layer.encoding.as_ref();
+    let compression = layer.compression.as_ref();
+
+    if element.child("chunk").is_none() {
+        layer.data = Some(tiles(element, encoding, compression)?.into());
+        return Ok(());
+    }
+
+    let chunks = element
+        .children_named("chunk")
+        .map(|chunk| -> Result<Chunk> {
+            Ok(Chunk {
+                data: tiles(chunk, encoding, compression)?.into(),
+                height: chunk.parse_or("height", 0)?,
+                width: chunk.parse_or("width", 0)?,
+                x: chunk.parse_or("x", 0)?,
+                y: chunk.parse_or("y", 0)?,
+            })
+        })
+        .collect::<Result<_>>()?;
+    layer.chunks = Some(chunks);
+
+    Ok(())
+}
+
+/// Reads the GIDs of a `<data>` or `<chunk>` element, which are either encoded in its text or
+/// listed as `<tile>` elements.
+fn tiles(
+    element: &Element,
+    encoding: Option<&LayerEncoding>,
+    compression: Option<&Compression>,
+) -> Result<Vec<u32>> {
+    match encoding {
+        Some(LayerEncoding::Csv) => element
+            .text
+            .split(',')
+            .map(str::trim)
+            .filter(|gid| !gid.is_empty())
+            .map(|gid| {
+                gid.parse().map_err(|_| Error::InvalidLayerData {
+                    message: format!("invalid GID '{}'", gid),
+                })
+            })
+            .collect(),
+        Some(LayerEncoding::Base64) => decode_base64(&element.text, compression),
+        None => element
+            .children_named("tile")
+            .map(|tile| tile.parse_or("gid", 0))
+            .collect(),
+    }
+}
+
+/// https://doc.mapeditor.org/en/stable/reference/tmx-map-format/#template-files
+pub(crate) fn template(element: &Element) -> Result<ObjectTemplate> {
+    expect_name(element, "template")?;
+    let object = element.child("object").ok_or_else(|| Error::InvalidTmx {
+        element: element.name.clone(),
+        message: "no <object>".to_string(),
+    })?;
+
+    Ok(ObjectTemplate {
+        typ: "template".to_string(),
+        tileset: element.child("tileset").map(tileset).transpose()?,
+        object: self::object(object)?,
+    })
+}
+
+/// https://doc.mapeditor.org/en/stable/reference/tmx-map-format/#object
+fn object(element: &Element) -> Result<Object> {
+    Ok(Object {
+        ellipse: element.child("ellipse").map(|_| true),
+        gid: element.parse("gid")?.map(Gid),
+        height: element.parse_or("height", 0.0)?,
+        id: element.parse_or("id", 0)?,
+        name: element.string("name").unwrap_or_default(),
+        point: element.child("point").map(|_| true),
+        polygon: element.child("polygon").map(points).transpose()?,
+        polyline: element.child("polyline").map(points).transpose()?,
+        properties: properties(element)?,
+        rotation: element.parse_or("rotation", 0.0)?,
+        template: element.string("template"),
+        text: element.child("text").map(text).transpose()?,
+        class: element.string("type").or_else(|| element.string("class")),
+        visible: element.flag("visible")?.unwrap_or(true),
+        width: element.parse_or("width", 0.0)?,
+        x: element.parse_or("x", 0.0)?,
+        y: element.parse_or("y", 0.0)?,
+    })
+}
+
+/// Reads the `points` of a `<polygon>` or `<polyline>`, written as "x,y x,y ...".
+fn points(element: &Element) -> Result<Vec<Point>> {
+    let points = element.attribute("points").unwrap_or_default();
+
+    points
+        .split_whitespace()
+        .map(|point| {
+            let (x, y) = point
+                .split_once(',')
+                .ok_or_else(|| element.invalid("points", points))?;
+            Ok(Point {
+                x: x.parse().map_err(|_| element.invalid("points", points))?,
+                y: y.parse().map_err(|_| element.invalid("points", points))?,
+            })
+        })
+        .collect()
+}
+
+/// https://doc.mapeditor.org/en/stable/reference/tmx-map-format/#text
+fn text(element: &Element) -> Result<Text> {
+    let default = Text::default();
+
+    Ok(Text {
+        bold: element.flag("bold")?.unwrap_or(default.bold),
+        color: element.string("color").unwrap_or(default.color),
+        fontfamily: element.string("fontfamily").unwrap_or(default.fontfamily),
+        halign: element.enumeration("halign")?.unwrap_or(default.halign),
+        italic: element.flag("italic")?.unwrap_or(default.italic),
+        kerning: element.flag("kerning")?.unwrap_or(default.kerning),
+        pixelsize: element.parse_or("pixelsize", default.pixelsize)?,
+        strikeout: element.flag("strikeout")?.unwrap_or(default.strikeout),
+        text: element.text.clone(),
+        underline: element.flag("underline")?.unwrap_or(default.underline),
+        valign: element.enumeration("valign")?.unwrap_or(default.valign),
+        wrap: element.flag("wrap")?.unwrap_or(default.wrap),
+    })
+}
+
+/// https://doc.mapeditor.org/en/stable/reference/tmx-map-format/#tile
+fn tile(element: &Element) -> Result<Tile> {
+    let image = Image::read(element.child("image"))?;
+
+    Ok(Tile {
+        animation: element
+            .child("animation")
+            .map(|animation| {
+                animation
+                    .children_named("frame")
+                    .map(|frame| -> Result<Frame> {
+                        Ok(Frame {
+                            tileid: frame.parse_or("tileid", 0)?,
+                            duration: frame.parse_or("duration", 0)?,
+                        })
+                    })
+                    .collect()
+            })
+            .transpose()?,
+        id: element.parse_or("id", 0)?,
+        image: image.source,
+        imageheight: image.height,
+        imagewidth: image.width,
+        x: element.parse("x")?,
+        y: element.parse("y")?,
+        width: element.parse("width")?,
+        height: element.parse("height")?,
+        objectgroup: element
+            .child("objectgroup")
+            .map(|group| layer(group, LayerType::ObjectGroup))
+            .transpose()?,
+        probability: element.parse("probability")?,
+        properties: properties(element)?,
+        terrain: element
+            .attribute("terrain")
+            .map(|terrain| {
+                // Corners without a terrain are left empty
+                terrain
+                    .split(',')
+                    .map(|index| match index {
+                        "" => Ok(-1),
+                        _ => index
+                            .parse()
+                            .map_err(|_| element.invalid("terrain", terrain)),
+                    })
+                    .collect()
+            })
+            .transpose()?,
+        typ: element.string("type").or_else(|| element.string("class")),
+    })
+}
+
+/// https://doc.mapeditor.org/en/stable/reference/tmx-map-format/#wangset
+fn wang_set(element: &Element) -> Result<WangSet> {
+    Ok(WangSet {
+        class: element.string("class"),
+        colors: element
+            .children_named("wangcolor")
+            .map(|color| -> Result<WangColor> {
+                Ok(WangColor {
+                    class: color.string("class"),
+                    color: color.string("color").unwrap_or_default(),
+                    name: color.string("name").unwrap_or_default(),
+                    probability: color.parse_or("probability", 1.0)?,
+                    properties: properties(color)?,
+                    tile: color.parse_or("tile", -1)?,
+                })
+            })
+            .collect::<Result<_>>()?,
+        name: element.string("name").unwrap_or_default(),
+        properties: properties(element)?,
+        tile: element.parse_or("tile", -1)?,
+        typ: element.enumeration("type")?.unwrap_or_default(),
+        wangtiles: element
+            .children_named("wangtile")
+            .map(|tile| -> Result<WangTile> {
+                let wangid = tile.attribute("wangid").unwrap_or_default();
+                let invalid = || tile.invalid("wangid", wangid);
+                let ids: Vec<u8> = wangid
+                    .split(',')
+                    .map(|id| id.parse().map_err(|_| invalid()))
+                    .collect::<Result<_>>()?;
+
+                Ok(WangTile {
+                    tileid: tile.parse_or("tileid", 0)?,
+                    wangid: ids.try_into().map_err(|_| invalid())?,
+                })
+            })
+            .collect::<Result<_>>()?,
+    })
+}
+
+/// Reads the `<properties>` child of an element, if present.
+fn properties(element: &Element) -> Result<Option<Vec<Property>>> {
+    element
+        .child("properties")
+        .map(|properties| {
+            properties
+                .children_named("property")
+                .map(property)
+                .collect()
+        })
+        .transpose()
+}
+
+/// https://doc.mapeditor.org/en/stable/reference/tmx-map-format/#property
+fn property(element: &Element) -> Result<Property> {
+    let typ = element
+        .string("type")
+        .unwrap_or_else(|| property_type::STRING.to_string());
+
+    let value = match typ.as_str() {
+        property_type::CLASS => {
+            let members = properties(element)?.unwrap_or_default();
+            PropertyVal::Class(
+                members
+                    .into_iter()
+                    .map(|member| (member.name, member.value))
+                    .collect::<BTreeMap<_, _>>(),
+            )
+        }
+        _ => {
+            // Multi-line strings are written as the text of the element
+            let value = element.attribute("value").unwrap_or(&element.text);
+            property_value(element, &typ, value)?
+        }
+    };
+
+    Ok(Property {
+        name: element.string("name").unwrap_or_default(),
+        value,
+        typ,
+        propertytype: element.string("propertytype"),
+    })
+}
+
+fn property_value(element: &Element, typ: &str, value: &str) -> Result<PropertyVal> {
+    let invalid = || element.invalid("value", value);
+
+    Ok(match typ {
+        property_type::INT => PropertyVal::Integer(value.parse().map_err(|_| invalid())?),
+        property_type::FLOAT => PropertyVal::Float(value.parse().map_err(|_| invalid())?),
+        property_type::BOOL => PropertyVal::Boolean(value.parse().map_err(|_| invalid())?),
+        property_type::COLOR => PropertyVal::Color(value.to_string()),
+        property_type::FILE => PropertyVal::File(value.to_string()),
+        property_type::OBJECT => PropertyVal::Object(value.parse().map_err(|_| invalid())?),
+        _ => PropertyVal::String(value.to_string()),
+    })
+}
+
+/// The attributes of an `<image>` element, used by tilesets, tiles and image layers.
+#[derive(Default)]
+struct Image {
+    source: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    transparentcolor: Option<String>,
+}
+
+impl Image {
+    fn read(element: Option<&Element>) -> Result<Self> {
+        let Some(element) = element else {
+            return Ok(Self::default());
+        };
+
+        Ok(Self {
+            source: element.string("source"),
+            width: element.parse("width")?,
+            height: element.parse("height")?,
+            // TMX writes the transparent color without the leading '#'
+            transparentcolor: element
+                .attribute("trans")
+                .map(|trans| format!("#{}", trans.trim_start_matches('#'))),
+        })
+    }
+}
+
+/// Returns a list which is only present in the model when it has any items.
+fn optional_list<T>(items: Result<Vec<T>>) -> Result<Option<Vec<T>>> {
+    items.map(|items| (!items.is_empty()).then_some(items))
+}
+
+fn expect_name(element: &Element, name: &str) -> Result<()> {
+    if element.name != name {
+        return Err(Error::InvalidTmx {
+            element: element.name.clone(),
+            message: format!("expected <{}>", name),
+        });
+    }
+
+    Ok(())
+}
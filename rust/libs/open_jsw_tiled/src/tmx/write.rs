@@ -0,0 +1,554 @@
+use super::element::Element;
+use crate::{
+    Result,
+    tiled::{
+        layer::{Compression, Layer, LayerEncoding, LayerType},
+        layer_data::encode_base64,
+        map::Map,
+        object::Object,
+        point::Point,
+        property::{Property, PropertyVal, property_type},
+        text::Text,
+        tileset::{FillMode, Tile, Tileset, WangSet},
+    },
+};
+
+/// https://doc.mapeditor.org/en/stable/reference/tmx-map-format/#map
+pub(crate) fn map(map: &Map) -> Result<Element> {
+    let mut element = Element::new("map");
+    element.set("version", &map.version);
+    element.set("tiledversion", &map.tiledversion);
+    element.set_opt("class", map.class.as_ref());
+    element.set_enumeration("orientation", &map.orientation);
+    element.set_enumeration("renderorder", &map.renderorder);
+    if map.compressionlevel != -1 {
+        element.set("compressionlevel", map.compressionlevel);
+    }
+    element.set("width", map.width);
+    element.set("height", map.height);
+    element.set("tilewidth", map.tilewidth);
+    element.set("tileheight", map.tileheight);
+    element.set_opt("hexsidelength", map.hexsidelength);
+    if let Some(axis) = &map.staggeraxis {
+        element.set_enumeration("staggeraxis", axis);
+    }
+    if let Some(index) = &map.staggerindex {
+        element.set_enumeration("staggerindex", index);
+    }
+    if map.parallaxoriginx != 0.0 {
+        element.set("parallaxoriginx", map.parallaxoriginx);
+    }
+    if map.parallaxoriginy != 0.0 {
+        element.set("parallaxoriginy", map.parallaxoriginy);
+    }
+    element.set_opt("backgroundcolor", map.backgroundcolor.as_ref());
+    element.set_flag("infinite", map.infinite);
+    element.set("nextlayerid", map.nextlayerid);
+    element.set("nextobjectid", map.nextobjectid);
+
+    push_properties(&mut element, map.properties.as_deref());
+    for tileset in &map.tilesets {
+        element.push(self::tileset(tileset, true)?);
+    }
+    for layer in &map.layers {
+        element.push(self::layer(layer, map.compressionlevel)?);
+    }
+
+    Ok(element)
+}
+
+/// https://doc.mapeditor.org/en/stable/reference/tmx-map-format/#tileset
+///
+/// A tileset embedded in a map has a `firstgid`, and only refers to its file if it has a `source`.
+/// A tileset written as a TSX file has a version instead.
+pub(crate) fn tileset(tileset: &Tileset, embedded: bool) -> Result<Element> {
+    let mut element = Element::new("tileset");
+
+    if embedded {
+        element.set("firstgid", tileset.firstgid);
+        if let Some(source) = &tileset.source {
+            element.set("source", source);
+            return Ok(element);
+        }
+    } else {
+        element.set("version", &tileset.version);
+        element.set("tiledversion", &tileset.tiledversion);
+    }
+
+    element.set("name", &tileset.name);
+    element.set_opt("class", tileset.class.as_ref());
+    element.set("tilewidth", tileset.tilewidth);
+    element.set("tileheight", tileset.tileheight);
+    if tileset.spacing != 0 {
+        element.set("spacing", tileset.spacing);
+    }
+    if tileset.margin != 0 {
+        element.set("margin", tileset.margin);
+    }
+    element.set("tilecount", tileset.tilecount);
+    element.set("columns", tileset.columns);
+    if let Some(alignment) = &tileset.objectalignment {
+        element.set_enumeration("objectalignment", alignment);
+    }
+    if let Some(size) = &tileset.tilerendersize {
+        element.set_enumeration("tilerendersize", size);
+    }
+    if !matches!(tileset.fillmode, FillMode::Stretch) {
+        element.set_enumeration("fillmode", &tileset.fillmode);
+    }
+    element.set_opt("backgroundcolor", tileset.backgroundcolor.as_ref());
+
+    if let Some(offset) = &tileset.tileoffset {
+        let mut child = Element::new("tileoffset");
+        child.set("x", offset.x);
+        child.set("y", offset.y);
+        element.push(child);
+    }
+    if let Some(grid) = &tileset.grid {
+        let mut child = Element::new("grid");
+        child.set_enumeration("orientation", &grid.orientation);
+        child.set("width", grid.width);
+        child.set("height", grid.height);
+        element.push(child);
+    }
+    if let Some(transformations) = &tileset.transformations {
+        let mut child = Element::new("transformations");
+        child.set_flag("hflip", transformations.hflip);
+        child.set_flag("vflip", transformations.vflip);
+        child.set_flag("rotate", transformations.rotate);
+        child.set_flag("preferuntransformed", transformations.preferuntransformed);
+        element.push(child);
+    }
+    push_properties(&mut element, tileset.properties.as_deref());
+    if !tileset.image.is_empty() {
+        element.push(image(
+            &tileset.image,
+            Some(tileset.imagewidth),
+            Some(tileset.imageheight),
+            tileset.transparentcolor.as_deref(),
+        ));
+    }
+    if let Some(terrains) = &tileset.terrains {
+        let mut child = Element::new("terraintypes");
+        for terrain in terrains {
+            let mut terrain_element = Element::new("terrain");
+            terrain_element.set("name", &terrain.name);
+            terrain_element.set("tile", terrain.tile);
+            push_properties(&mut terrain_element, terrain.properties.as_deref());
+            child.push(terrain_element);
+        }
+        element.push(child);
+    }
+    for tile in tileset.tiles.iter().flatten() {
+        element.push(self::tile(tile)?);
+    }
+    if let Some(wangsets) = &tileset.wangsets {
+        let mut child = Element::new("wangsets");
+        for wangset in wangsets {
+            child.push(wang_set(wangset));
+        }
+        element.push(child);
+    }
+
+    Ok(element)
+}
+
+/// https://doc.mapeditor.org/en/stable/reference/tmx-map-format/#layer
+///
+/// `compression_level` is the level set by the map, where -1 is the default of the algorithm.
+fn layer(layer: &Layer, compression_level: i32) -> Result<Element> {
+    let name = match layer.typ {
+        LayerType::TileLayer => "layer",
+        LayerType::ObjectGroup => "objectgroup",
+        LayerType::ImageLayer => "imagelayer",
+        LayerType::Group => "group",
+    };
+
+    let mut element = Element::new(name);
+    element.set("id", layer.id);
+    element.set("name", &layer.name);
+    element.set_opt("class", layer.class.as_ref());
+    if layer.x != 0 {
+        element.set("x", layer.x);
+    }
+    if layer.y != 0 {
+        element.set("y", layer.y);
+    }
+    element.set_opt("width", layer.width);
+    element.set_opt("height", layer.height);
+    if !layer.visible {
+        element.set_flag("visible", false);
+    }
+    if layer.locked {
+        element.set_flag("locked", true);
+    }
+    if layer.opacity != 1.0 {
+        element.set("opacity", layer.opacity);
+    }
+    element.set_opt("tintcolor", layer.tintcolor.as_ref());
+    if layer.offsetx != 0.0 {
+        element.set("offsetx", layer.offsetx);
+    }
+    if layer.offsety != 0.0 {
+        element.set("offsety", layer.offsety);
+    }
+    if layer.parallaxx != 1.0 {
+        element.set("parallaxx", layer.parallaxx);
+    }
+    if layer.parallaxy != 1.0 {
+        element.set("parallaxy", layer.parallaxy);
+    }
+    if let Some(draworder) = &layer.draworder {
+        element.set_enumeration("draworder", draworder);
+    }
+    if let Some(repeatx) = layer.repeatx {
+        element.set_flag("repeatx", repeatx);
+    }
+    if let Some(repeaty) = layer.repeaty {
+        element.set_flag("repeaty", repeaty);
+    }
+
+    push_properties(&mut element, Some(&layer.properties));
+
+    match layer.typ {
+        LayerType::TileLayer => {
+            if layer.data.is_some() || layer.chunks.is_some() {
+                element.push(tile_data(layer, compression_level)?);
+            }
+        }
+        LayerType::ObjectGroup => {
+            for object in layer.objects.iter().flatten() {
+                element.push(self::object(object));
+            }
+        }
+        LayerType::ImageLayer => {
+            if let Some(source) = &layer.image {
+                element.push(image(
+                    source,
+                    layer.imagewidth,
+                    layer.imageheight,
+                    layer.transparentcolor.as_deref(),
+                ));
+            }
+        }
+        LayerType::Group => {
+            for layer in layer.layers.iter().flatten() {
+                element.push(self::layer(layer, compression_level)?);
+            }
+        }
+    }
+
+    Ok(element)
+}
+
+/// https://doc.mapeditor.org/en/stable/reference/tmx-map-format/#data
+fn tile_data(layer: &Layer, compression_level: i32) -> Result<Element> {
+    let encoding = layer.encoding.as_ref();
+    let compression = layer.compression.as_ref();
+
+    let mut element = Element::new("data");
+    if let Some(encoding) = encoding {
+        element.set_enumeration("encoding", encoding);
+    }
+    if let Some(compression) = compression {
+        element.set_enumeration("compression", compression);
+    }
+
+    if let Some(chunks) = &layer.chunks {
+        for chunk in chunks {
+            let mut child = Element::new("chunk");
+            child.set("x", chunk.x);
+            child.set("y", chunk.y);
+            child.set("width", chunk.width);
+            child.set("height", chunk.height);
+            set_tiles(
+                &mut child,
+                &chunk.data,
+                chunk.width,
+                encoding,
+                compression,
+                compression_level,
+            )?;
+            element.push(child);
+        }
+    } else if let Some(data) = &layer.data {
+        let width = layer.width.unwrap_or(data.len() as u32);
+        set_tiles(
+            &mut element,
+            data,
+            width,
+            encoding,
+            compression,
+            compression_level,
+        )?;
+    }
+
+    Ok(element)
+}
+
+/// Writes GIDs as the text of a `<data>` or `<chunk>` element, or as `<tile>` elements if the
+/// layer has no encoding.
+fn set_tiles(
+    element: &mut Element,
+    tiles: &[u32],
+    width: u32,
+    encoding: Option<&LayerEncoding>,
+    compression: Option<&Compression>,
+    compression_level: i32,
+) -> Result<()> {
+    match encoding {
+        Some(LayerEncoding::Csv) => {
+            // One row per line, as Tiled writes it
+            let rows: Vec<String> = tiles
+                .chunks(width.max(1) as usize)
+                .map(|row| row.iter().map(u32::to_string).collect::<Vec<_>>().join(","))
+                .collect();
+            element.text = format!("\n{}\n", rows.join(",\n"));
+        }
+        Some(LayerEncoding::Base64) => {
+            element.text = format!(
+                "\n{}\n",
+                encode_base64(tiles, compression, compression_level)?
+            );
+        }
+        None => {
+            for gid in tiles {
+                let mut tile = Element::new("tile");
+                if *gid != 0 {
+                    tile.set("gid", gid);
+                }
+                element.push(tile);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// https://doc.mapeditor.org/en/stable/reference/tmx-map-format/#object
+fn object(object: &Object) -> Element {
+    let mut element = Element::new("object");
+    element.set("id", object.id);
+    element.set_opt("template", object.template.as_ref());
+    if !object.name.is_empty() {
+        element.set("name", &object.name);
+    }
+    element.set_opt("type", object.class.as_ref());
+    element.set_opt("gid", object.gid);
+    element.set("x", object.x);
+    element.set("y", object.y);
+    if object.width != 0.0 {
+        element.set("width", object.width);
+    }
+    if object.height != 0.0 {
+        element.set("height", object.height);
+    }
+    if object.rotation != 0.0 {
+        element.set("rotation", object.rotation);
+    }
+    if !object.visible {
+        element.set_flag("visible", false);
+    }
+
+    push_properties(&mut element, object.properties.as_deref());
+    if object.ellipse == Some(true) {
+        element.push(Element::new("ellipse"));
+    }
+    if object.point == Some(true) {
+        element.push(Element::new("point"));
+    }
+    if let Some(polygon) = &object.polygon {
+        element.push(points("polygon", polygon));
+    }
+    if let Some(polyline) = &object.polyline {
+        element.push(points("polyline", polyline));
+    }
+    if let Some(text) = &object.text {
+        element.push(self::text(text));
+    }
+
+    element
+}
+
+fn points(name: &str, points: &[Point]) -> Element {
+    let points: Vec<String> = points
+        .iter()
+        .map(|point| format!("{},{}", point.x, point.y))
+        .collect();
+
+    let mut element = Element::new(name);
+    element.set("points", points.join(" "));
+    element
+}
+
+/// https://doc.mapeditor.org/en/stable/reference/tmx-map-format/#text
+///
+/// Only the attributes which differ from Tiled's defaults are written.
+fn text(text: &Text) -> Element {
+    let default = Text::default();
+
+    let mut element = Element::new("text");
+    if text.fontfamily != default.fontfamily {
+        element.set("fontfamily", &text.fontfamily);
+    }
+    if text.pixelsize != default.pixelsize {
+        element.set("pixelsize", text.pixelsize);
+    }
+    for (name, value, default) in [
+        ("wrap", text.wrap, default.wrap),
+        ("bold", text.bold, default.bold),
+        ("italic", text.italic, default.italic),
+        ("underline", text.underline, default.underline),
+        ("strikeout", text.strikeout, default.strikeout),
+        ("kerning", text.kerning, default.kerning),
+    ] {
+        if value != default {
+            element.set_flag(name, value);
+        }
+    }
+    if text.color != default.color {
+        element.set("color", &text.color);
+    }
+    if text.halign != default.halign {
+        element.set_enumeration("halign", &text.halign);
+    }
+    if text.valign != default.valign {
+        element.set_enumeration("valign", &text.valign);
+    }
+    element.text = text.text.clone();
+
+    element
+}
+
+/// https://doc.mapeditor.org/en/stable/reference/tmx-map-format/#tile
+fn tile(tile: &Tile) -> Result<Element> {
+    let mut element = Element::new("tile");
+    element.set("id", tile.id);
+    element.set_opt("type", tile.typ.as_ref());
+    if let Some(terrain) = &tile.terrain {
+        // Corners without a terrain are left empty
+        let terrain: Vec<String> = terrain
+            .iter()
+            .map(|index| match index {
+                -1 => String::new(),
+                _ => index.to_string(),
+            })
+            .collect();
+        element.set("terrain", terrain.join(","));
+    }
+    element.set_opt("probability", tile.probability);
+    element.set_opt("x", tile.x);
+    element.set_opt("y", tile.y);
+    element.set_opt("width", tile.width);
+    element.set_opt("height", tile.height);
+
+    push_properties(&mut element, tile.properties.as_deref());
+    if let Some(source) = &tile.image {
+        element.push(image(source, tile.imagewidth, tile.imageheight, None));
+    }
+    if let Some(objectgroup) = &tile.objectgroup {
+        // Object groups have no tile data to compress
+        element.push(layer(objectgroup, -1)?);
+    }
+    if let Some(animation) = &tile.animation {
+        let mut child = Element::new("animation");
+        for frame in animation {
+            let mut frame_element = Element::new("frame");
+            frame_element.set("tileid", frame.tileid);
+            frame_element.set("duration", frame.duration);
+            child.push(frame_element);
+        }
+        element.push(child);
+    }
+
+    Ok(element)
+}
+
+/// https://doc.mapeditor.org/en/stable/reference/tmx-map-format/#wangset
+fn wang_set(wangset: &WangSet) -> Element {
+    let mut element = Element::new("wangset");
+    element.set("name", &wangset.name);
+    element.set_opt("class", wangset.class.as_ref());
+    element.set_enumeration("type", &wangset.typ);
+    element.set("tile", wangset.tile);
+
+    push_properties(&mut element, wangset.properties.as_deref());
+    for color in &wangset.colors {
+        let mut child = Element::new("wangcolor");
+        child.set("name", &color.name);
+        child.set_opt("class", color.class.as_ref());
+        child.set("color", &color.color);
+        child.set("tile", color.tile);
+        child.set("probability", color.probability);
+        push_properties(&mut child, color.properties.as_deref());
+        element.push(child);
+    }
+    for tile in &wangset.wangtiles {
+        let wangid: Vec<String> = tile.wangid.iter().map(u8::to_string).collect();
+        let mut child = Element::new("wangtile");
+        child.set("tileid", tile.tileid);
+        child.set("wangid", wangid.join(","));
+        element.push(child);
+    }
+
+    element
+}
+
+/// Adds a `<properties>` child to an element, if there are any properties.
+fn push_properties(element: &mut Element, properties: Option<&[Property]>) {
+    let Some(properties) = properties.filter(|properties| !properties.is_empty()) else {
+        return;
+    };
+
+    let mut child = Element::new("properties");
+    for property in properties {
+        child.push(self::property(property));
+    }
+    element.push(child);
+}
+
+/// https://doc.mapeditor.org/en/stable/reference/tmx-map-format/#property
+///
+/// The members of a class are written with the type of their value, as the member types are only
+/// known from the project.
+fn property(property: &Property) -> Element {
+    let mut element = Element::new("property");
+    element.set("name", &property.name);
+    if property.typ != property_type::STRING {
+        element.set("type", &property.typ);
+    }
+    element.set_opt("propertytype", property.propertytype.as_ref());
+
+    match &property.value {
+        PropertyVal::Class(members) => {
+            let members: Vec<Property> = members
+                .iter()
+                .map(|(name, value)| Property::new(name, value.clone()))
+                .collect();
+            push_properties(&mut element, Some(&members));
+        }
+        // Multi-line strings are written as the text of the element
+        PropertyVal::String(value) if value.contains('\n') => element.text = value.clone(),
+        value => element.set("value", value),
+    }
+
+    element
+}
+
+fn image(
+    source: &str,
+    width: Option<u32>,
+    height: Option<u32>,
+    transparentcolor: Option<&str>,
+) -> Element {
+    let mut element = Element::new("image");
+    element.set("source", source);
+    // TMX writes the transparent color without the leading '#'
+    element.set_opt(
+        "trans",
+        transparentcolor.map(|trans| trans.trim_start_matches('#')),
+    );
+    element.set_opt("width", width);
+    element.set_opt("height", height);
+    element
+}
@@ -1,10 +1,22 @@
-use std::ops::{Index, IndexMut};
+use std::{
+    collections::VecDeque,
+    ops::{Index, IndexMut},
+};
 
 pub struct Matrix<T> {
     data: Vec<T>,
     cols: usize,
 }
 
+/// A rectangle of cells of a matrix.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub col: usize,
+    pub row: usize,
+    pub cols: usize,
+    pub rows: usize,
+}
+
 impl<T> Matrix<T> {
     pub fn new(cols: usize, rows: usize, default: Option<T>) -> Self
     where
@@ -16,10 +28,150 @@ impl<T> Matrix<T> {
         }
     }
 
+    /// Creates a matrix with the value returned by `f(col, row)` in each cell.
+    pub fn from_fn(cols: usize, rows: usize, mut f: impl FnMut(usize, usize) -> T) -> Self {
+        let data = (0..cols * rows).map(|i| f(i % cols, i / cols)).collect();
+        Self { data, cols }
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn rows(&self) -> usize {
+        if self.cols == 0 {
+            0
+        } else {
+            self.data.len() / self.cols
+        }
+    }
+
+    /// Returns the value of the cell at `(col, row)`, or `None` outside the matrix.
     pub fn get(&self, col: usize, row: usize) -> Option<&T> {
+        if col >= self.cols {
+            return None;
+        }
         let index = self.cols * row + col;
         self.data.get(index)
     }
+
+    /// Replaces the value of the cell at `(col, row)`, and of the cells connected to it by their
+    /// edges which have the same value, returning the number of cells changed.
+    pub fn flood_fill(&mut self, col: usize, row: usize, value: T) -> usize
+    where
+        T: Clone + PartialEq,
+    {
+        let Some(target) = self.get(col, row).cloned() else {
+            return 0;
+        };
+        if target == value {
+            return 0;
+        }
+
+        let region = self.region(
+            col,
+            row,
+            |cell| *cell == target,
+            &mut vec![false; self.data.len()],
+        );
+        for &(col, row) in &region {
+            self[row][col] = value.clone();
+        }
+
+        region.len()
+    }
+
+    /// Returns the groups of cells for which `is_in` is true and which are connected by their
+    /// edges, as (col, row) positions in the order found scanning the rows from the top.
+    pub fn connected_components(&self, is_in: impl Fn(&T) -> bool) -> Vec<Vec<(usize, usize)>> {
+        let mut visited = vec![false; self.data.len()];
+        let mut components = Vec::new();
+
+        for i in 0..self.data.len() {
+            if !visited[i] && is_in(&self.data[i]) {
+                components.push(self.region(i % self.cols, i / self.cols, &is_in, &mut visited));
+            }
+        }
+
+        components
+    }
+
+    /// Covers the cells for which `is_in` is true with rectangles which don't overlap, merging
+    /// runs of cells along the rows and then runs of rows of the same width.
+    ///
+    /// The rectangles are found greedily from the top-left cell, so there are few of them, though
+    /// not always the fewest possible.
+    pub fn merge_rectangles(&self, is_in: impl Fn(&T) -> bool) -> Vec<Rect> {
+        let mut covered = vec![false; self.data.len()];
+        let free = |covered: &[bool], col: usize, row: usize| {
+            let i = self.cols * row + col;
+            !covered[i] && is_in(&self.data[i])
+        };
+        let mut rects = Vec::new();
+
+        for row in 0..self.rows() {
+            for col in 0..self.cols {
+                if !free(&covered, col, row) {
+                    continue;
+                }
+
+                let cols = (col..self.cols)
+                    .take_while(|&c| free(&covered, c, row))
+                    .count();
+                let rows = (row..self.rows())
+                    .take_while(|&r| (col..col + cols).all(|c| free(&covered, c, r)))
+                    .count();
+                for r in row..row + rows {
+                    covered[self.cols * r + col..self.cols * r + col + cols].fill(true);
+                }
+                rects.push(Rect {
+                    col,
+                    row,
+                    cols,
+                    rows,
+                });
+            }
+        }
+
+        rects
+    }
+
+    /// Returns the unvisited cells for which `is_in` is true connected to `(col, row)` by their
+    /// edges, marking them as visited.
+    fn region(
+        &self,
+        col: usize,
+        row: usize,
+        is_in: impl Fn(&T) -> bool,
+        visited: &mut [bool],
+    ) -> Vec<(usize, usize)> {
+        let mut region = Vec::new();
+        let mut queue = VecDeque::from([(col, row)]);
+        visited[self.cols * row + col] = true;
+
+        while let Some((col, row)) = queue.pop_front() {
+            region.push((col, row));
+
+            let neighbours = [
+                (col.checked_sub(1), Some(row)),
+                (Some(col + 1).filter(|&c| c < self.cols), Some(row)),
+                (Some(col), row.checked_sub(1)),
+                (Some(col), Some(row + 1).filter(|&r| r < self.rows())),
+            ];
+            for (col, row) in neighbours {
+                let (Some(col), Some(row)) = (col, row) else {
+                    continue;
+                };
+                let i = self.cols * row + col;
+                if !visited[i] && is_in(&self.data[i]) {
+                    visited[i] = true;
+                    queue.push_back((col, row));
+                }
+            }
+        }
+
+        region
+    }
 }
 
 impl<T> Index<usize> for Matrix<T> {
@@ -51,7 +203,11 @@ impl<'a, T> MatrixWrapper<'a, T> {
         Self { data, cols }
     }
 
+    /// Returns the value of the cell at `(col, row)`, or `None` outside the matrix.
     pub fn get(&self, col: usize, row: usize) -> Option<&T> {
+        if col >= self.cols {
+            return None;
+        }
         let index = self.cols * row + col;
         self.data.get(index)
     }
@@ -74,3 +230,77 @@ impl<'a, T> IndexMut<usize> for MatrixWrapper<'a, T> {
         &mut self.data[start..start + self.cols]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a matrix from rows of '#' (true) and '.' (false).
+    fn matrix(rows: &[&str]) -> Matrix<bool> {
+        Matrix::from_fn(rows[0].len(), rows.len(), |col, row| {
+            rows[row].as_bytes()[col] == b'#'
+        })
+    }
+
+    #[test]
+    fn flood_fill_replaces_the_connected_cells() {
+        let mut m = matrix(&["##..", "#..#", "...#"]);
+
+        assert_eq!(m.flood_fill(2, 0, true), 7);
+        assert_eq!(m.flood_fill(0, 0, true), 0);
+        assert!((0..3).all(|row| m[row].iter().all(|cell| *cell)));
+    }
+
+    #[test]
+    fn cells_outside_the_matrix_are_not_found() {
+        let mut m = matrix(&["#..", "..."]);
+
+        assert_eq!(m.get(3, 0), None);
+        assert_eq!(m.get(0, 2), None);
+        assert_eq!(m.flood_fill(4, 0, true), 0);
+        assert_eq!(m.flood_fill(0, 2, true), 0);
+        assert_eq!(m.get(0, 1), Some(&false));
+    }
+
+    #[test]
+    fn components_are_connected_by_their_edges() {
+        let m = matrix(&["##..", "#..#", "..##"]);
+
+        let components = m.connected_components(|cell| *cell);
+
+        assert_eq!(components.len(), 2);
+        assert_eq!(components[0], [(0, 0), (1, 0), (0, 1)]);
+        assert_eq!(components[1].len(), 3);
+    }
+
+    #[test]
+    fn rectangles_are_merged_along_rows_then_down() {
+        let m = matrix(&["###.", "###.", "#..#"]);
+
+        let rects = m.merge_rectangles(|cell| *cell);
+
+        assert_eq!(
+            rects,
+            [
+                Rect {
+                    col: 0,
+                    row: 0,
+                    cols: 3,
+                    rows: 2
+                },
+                Rect {
+                    col: 0,
+                    row: 2,
+                    cols: 1,
+                    rows: 1
+                },
+                Rect {
+                    col: 3,
+                    row: 2,
+                    cols: 1,
+                    rows: 1
+                },
+            ]
+        );
+    }
+}
@@ -26,7 +26,7 @@ pub fn info() -> Result<()> {
 pub fn build(args: &BuildArgs) -> Result<()> {
     let packages = get_packages();
 
-    if let Some(target) = &args.target {
+    if let Some(_target) = &args.target {
         // let status = Command::new(cargo_path())
         //     .current_dir(project_root())
         //     .args(["run", "--bin", target])
@@ -70,7 +70,7 @@ pub fn build(args: &BuildArgs) -> Result<()> {
 pub fn run(args: &RunArgs) -> Result<()> {
     let packages = get_packages();
 
-    if let Some(target) = &args.target {
+    if let Some(_target) = &args.target {
         // let status = Command::new(cargo_path())
         //     .current_dir(project_root())
         //     .args(["run", "--bin", target])
@@ -1,12 +1,6 @@
-use std::{
-    fs,
-    process::{Command, Stdio},
-};
-
 pub use self::error::{Error, Result};
 mod error;
 
-use crate::project::{cargo_path, dist_dir, project_root};
 use clap::Parser;
 use cli::{Cli, Commands};
 